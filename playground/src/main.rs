@@ -1,12 +1,14 @@
+use std::time::Duration;
+
 use tokio::signal;
 use tracing:: { info, error, subscriber::SetGlobalDefaultError };
 use tracing_subscriber::EnvFilter;
-use unibus::rabbit;
+use unibus_rabbit::rabbit;
 
 #[tokio::main]
 async fn main() -> Result<(), SetGlobalDefaultError> {
     if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", "info,lapin=off,unibus=trace");
+        std::env::set_var("RUST_LOG", "info,lapin=off,unibus_rabbit=trace");
     }
     let fmt = tracing_subscriber::fmt::fmt()
         .with_env_filter(EnvFilter::from_default_env())
@@ -22,7 +24,7 @@ async fn main() -> Result<(), SetGlobalDefaultError> {
     
     let addr = std::env::var("AMQP_ADDR").unwrap_or_else(|_| "amqp://127.0.0.1:5672/%2f" .into());
 
-    let options = unibus::rabbit::ConnectionOptions::new(&addr, "main");
+    let options = unibus_rabbit::rabbit::ConnectionOptions::new(addr.as_str(), "main").unwrap();
     let con = rabbit_client.connect(options).await.unwrap();
     
     let mut watcher = con.state_watcher().await.unwrap();
@@ -43,10 +45,16 @@ async fn main() -> Result<(), SetGlobalDefaultError> {
     match signal::ctrl_c().await {
         Ok(()) => {
             info!("shutting down");
+            if let Err(err) = con.shutdown(Duration::from_secs(10)).await {
+                error!("Unable to close the connection: {}", err);
+            }
         }
         Err(err) => {
             error!("Unable to listen for shutdown signal: {}", err);
         }
     };
+    if let Err(err) = rabbit_client.close().await {
+        error!("Unable to stop the rabbit client: {}", err);
+    }
     Ok(())
 }
@@ -0,0 +1,34 @@
+//! The `BusMessage` derive used from a crate that depends on `unibus-rabbit` only.
+
+use serde::{Deserialize, Serialize};
+use unibus_rabbit::bus::{BusMessage, MESSAGE_VERSION};
+
+#[derive(Serialize, Deserialize, BusMessage)]
+#[bus(exchange = "orders", routing_key = "order.{region}", version = 2)]
+struct OrderPlaced {
+    region: String,
+    id: u64,
+}
+
+#[derive(Serialize, Deserialize, BusMessage)]
+#[bus(crate = unibus_rabbit::bus)]
+struct OrderShipped {
+    id: u64,
+}
+
+#[test]
+fn derives_through_the_rabbit_reexport() {
+    let placed = OrderPlaced {
+        region: "eu".to_owned(),
+        id: 7,
+    };
+    let envelope = placed.to_envelope().unwrap();
+    assert_eq!(envelope.destination, "orders");
+    assert_eq!(envelope.routing_key, "order.eu");
+    assert_eq!(envelope.message_type, "order-placed");
+    assert_eq!(envelope.headers[MESSAGE_VERSION], 2);
+    assert_eq!(placed.id, 7);
+
+    assert_eq!(OrderShipped::EXCHANGE, "order-shipped");
+    assert_eq!(OrderShipped { id: 1 }.routing_key(), "");
+}
@@ -0,0 +1,402 @@
+use std::{fs, io, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use crate::{
+    memory::{MemoryError, MemoryTransport, Routing},
+    transport::{Ack, Publish, Route, Subscribe},
+    BusMessage, Envelope, MESSAGE_VERSION,
+};
+
+#[derive(Debug, Error)]
+pub enum ContractError {
+    #[error("contract io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("contract serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("no contract for message {message} from consumer {consumer}")]
+    Missing { consumer: String, message: String },
+    #[error("contract of {consumer} broken at {path}: expected {expected}, found {found}")]
+    Violation {
+        consumer: String,
+        path: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("contract of {consumer} for {message} expects {field} {expected}, found {found}")]
+    Mismatch {
+        consumer: String,
+        message: String,
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+    #[error("contract transport error: {0}")]
+    Transport(#[from] MemoryError),
+}
+
+/// Message accepted by a consumer, described by where it is bound and the payload
+/// examples it understands.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Contract {
+    pub consumer: String,
+    pub message: String,
+    pub exchange: String,
+    /// Topic pattern the consumer binds with.
+    pub routing_key: String,
+    pub content_type: String,
+    pub version: u32,
+    pub examples: Vec<Value>,
+}
+
+impl Contract {
+    /// Checks that `payload` carries every field of every example with the same json kind.
+    pub fn verify(&self, payload: &Value) -> Result<(), ContractError> {
+        self.examples
+            .iter()
+            .try_for_each(|example| self.verify_shape("$", example, payload))
+    }
+
+    /// Checks the metadata of `envelope` against the contract, then its payload when JSON.
+    pub fn verify_envelope(&self, envelope: &Envelope) -> Result<(), ContractError> {
+        if envelope.destination != self.exchange {
+            return Err(self.mismatch("exchange", &self.exchange, &envelope.destination));
+        }
+        if !Routing::Topic.matches(&self.routing_key, &envelope.routing_key) {
+            return Err(self.mismatch("routing key", &self.routing_key, &envelope.routing_key));
+        }
+        if envelope.content_type != self.content_type {
+            return Err(self.mismatch("content type", &self.content_type, &envelope.content_type));
+        }
+        let version = envelope
+            .headers
+            .get(MESSAGE_VERSION)
+            .and_then(Value::as_u64);
+        if version != Some(u64::from(self.version)) {
+            let found = version.map_or_else(|| "none".to_owned(), |v| v.to_string());
+            return Err(self.mismatch(MESSAGE_VERSION, &self.version.to_string(), &found));
+        }
+        if self.content_type == JSON {
+            self.verify(&serde_json::from_slice(&envelope.payload)?)?;
+        }
+        Ok(())
+    }
+
+    fn verify_shape(
+        &self,
+        path: &str,
+        expected: &Value,
+        found: &Value,
+    ) -> Result<(), ContractError> {
+        match (expected, found) {
+            (Value::Null, _) => Ok(()),
+            (Value::Object(expected), Value::Object(found)) => {
+                expected.iter().try_for_each(|(key, value)| {
+                    let path = format!("{path}.{key}");
+                    match found.get(key) {
+                        Some(found) => self.verify_shape(&path, value, found),
+                        None => Err(self.violation(path, kind(value), "nothing")),
+                    }
+                })
+            }
+            (Value::Array(expected), Value::Array(found)) => {
+                match (expected.first(), found.first()) {
+                    (Some(expected), Some(found)) => {
+                        self.verify_shape(&format!("{path}[0]"), expected, found)
+                    }
+                    _ => Ok(()),
+                }
+            }
+            (expected, found) if kind(expected) == kind(found) => Ok(()),
+            (expected, found) => Err(self.violation(path.to_owned(), kind(expected), kind(found))),
+        }
+    }
+
+    fn violation(
+        &self,
+        path: String,
+        expected: &'static str,
+        found: &'static str,
+    ) -> ContractError {
+        ContractError::Violation {
+            consumer: self.consumer.clone(),
+            path,
+            expected,
+            found,
+        }
+    }
+
+    fn mismatch(&self, field: &'static str, expected: &str, found: &str) -> ContractError {
+        ContractError::Mismatch {
+            consumer: self.consumer.clone(),
+            message: self.message.clone(),
+            field,
+            expected: expected.to_owned(),
+            found: found.to_owned(),
+        }
+    }
+}
+
+const JSON: &str = "application/json";
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Contracts exported by one consumer, loaded by producers in their tests.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ContractSet {
+    pub consumer: String,
+    pub contracts: Vec<Contract>,
+}
+
+impl ContractSet {
+    pub fn new(consumer: impl Into<String>) -> Self {
+        ContractSet {
+            consumer: consumer.into(),
+            contracts: Vec::new(),
+        }
+    }
+
+    /// Accepts `T` bound with `routing_key`, a topic pattern, shaped like `examples`.
+    pub fn accepts<T: BusMessage>(
+        mut self,
+        routing_key: impl Into<String>,
+        examples: &[T],
+    ) -> Result<Self, ContractError> {
+        let examples = examples
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.contracts.push(Contract {
+            consumer: self.consumer.clone(),
+            message: T::MESSAGE_TYPE.to_owned(),
+            exchange: T::EXCHANGE.to_owned(),
+            routing_key: routing_key.into(),
+            content_type: T::CONTENT_TYPE.to_owned(),
+            version: T::VERSION,
+            examples,
+        });
+        Ok(self)
+    }
+
+    pub fn contract(&self, message: &str) -> Result<&Contract, ContractError> {
+        self.contracts
+            .iter()
+            .find(|c| c.message == message)
+            .ok_or_else(|| ContractError::Missing {
+                consumer: self.consumer.clone(),
+                message: message.to_owned(),
+            })
+    }
+
+    pub fn export(&self, path: impl AsRef<Path>) -> Result<(), ContractError> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ContractError> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+}
+
+/// Producer side check: publishes the envelope of `produced` through a
+/// [`MemoryTransport`] bound like the consumer of the contract for its message type,
+/// and verifies what the consumer receives.
+pub async fn verify_producer<T: BusMessage + Sync>(
+    contracts: &ContractSet,
+    produced: &T,
+) -> Result<(), ContractError> {
+    let envelope = produced.to_envelope()?;
+    let contract = contracts.contract(&envelope.message_type)?;
+    let transport = MemoryTransport::new();
+    let (received, mut receiver) = mpsc::unbounded_channel();
+    let route = Route::new(&contract.exchange, &contract.routing_key);
+    let subscription = transport
+        .subscribe(&contract.consumer, &[route], move |envelope: Envelope| {
+            _ = received.send(envelope);
+            async { Ok(Ack) }
+        })
+        .await?;
+    transport.publish(envelope.clone()).await?;
+    // routed envelopes are queued by `publish`, so this only waits for the consumer task
+    let delivered = tokio::time::timeout(Duration::from_secs(1), receiver.recv()).await;
+    subscription.cancel().await;
+    let Ok(Some(delivered)) = delivered else {
+        // not routed to the consumer, the metadata check names the reason when it can
+        contract.verify_envelope(&envelope)?;
+        return Err(MemoryError::Unroutable {
+            destination: envelope.destination,
+            routing_key: envelope.routing_key,
+        }
+        .into());
+    };
+    contract.verify_envelope(&delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    mod consumer {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, crate::BusMessage)]
+        #[bus(crate = crate, exchange = "orders", routing_key = "order.{region}", version = 2)]
+        pub struct OrderPlaced {
+            pub region: String,
+            pub id: u64,
+        }
+    }
+
+    mod producer {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, crate::BusMessage)]
+        #[bus(crate = crate, exchange = "orders", routing_key = "order.{region}", version = 2)]
+        pub struct OrderPlaced {
+            pub region: String,
+            pub id: u64,
+            pub total: f64,
+        }
+    }
+
+    mod moved {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, crate::BusMessage)]
+        #[bus(crate = crate, exchange = "sales", routing_key = "order.{region}", version = 2)]
+        pub struct OrderPlaced {
+            pub region: String,
+            pub id: u64,
+        }
+    }
+
+    mod bumped {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, crate::BusMessage)]
+        #[bus(crate = crate, exchange = "orders", routing_key = "order.{region}", version = 3)]
+        pub struct OrderPlaced {
+            pub region: String,
+            pub id: u64,
+        }
+    }
+
+    mod text {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, crate::BusMessage)]
+        #[bus(
+            crate = crate,
+            exchange = "orders",
+            routing_key = "order.{region}",
+            version = 2,
+            content_type = "text/plain"
+        )]
+        pub struct OrderPlaced {
+            pub region: String,
+            pub id: u64,
+        }
+    }
+
+    fn contracts() -> ContractSet {
+        let example = consumer::OrderPlaced {
+            region: "eu".to_owned(),
+            id: 1,
+        };
+        ContractSet::new("billing")
+            .accepts("order.eu", &[example])
+            .unwrap()
+    }
+
+    fn produced(region: &str) -> producer::OrderPlaced {
+        producer::OrderPlaced {
+            region: region.to_owned(),
+            id: 7,
+            total: 9.5,
+        }
+    }
+
+    fn mismatch(error: ContractError) -> &'static str {
+        match error {
+            ContractError::Mismatch { field, .. } => field,
+            other => panic!("expected a mismatch, got {other}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_a_compatible_producer() {
+        verify_producer(&contracts(), &produced("eu"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_a_routing_key_the_consumer_is_not_bound_with() {
+        let error = verify_producer(&contracts(), &produced("us"))
+            .await
+            .unwrap_err();
+        assert_eq!(mismatch(error), "routing key");
+    }
+
+    #[tokio::test]
+    async fn rejects_another_exchange() {
+        let produced = moved::OrderPlaced {
+            region: "eu".to_owned(),
+            id: 7,
+        };
+        let error = verify_producer(&contracts(), &produced).await.unwrap_err();
+        assert_eq!(mismatch(error), "exchange");
+    }
+
+    #[tokio::test]
+    async fn rejects_another_version() {
+        let produced = bumped::OrderPlaced {
+            region: "eu".to_owned(),
+            id: 7,
+        };
+        let error = verify_producer(&contracts(), &produced).await.unwrap_err();
+        assert_eq!(mismatch(error), MESSAGE_VERSION);
+    }
+
+    #[tokio::test]
+    async fn rejects_another_content_type() {
+        let produced = text::OrderPlaced {
+            region: "eu".to_owned(),
+            id: 7,
+        };
+        let error = verify_producer(&contracts(), &produced).await.unwrap_err();
+        assert_eq!(mismatch(error), "content type");
+    }
+
+    #[tokio::test]
+    async fn rejects_a_payload_missing_a_field() {
+        let contracts = ContractSet::new("billing")
+            .accepts("order.*", &[produced("eu")])
+            .unwrap();
+        let produced = consumer::OrderPlaced {
+            region: "eu".to_owned(),
+            id: 7,
+        };
+        match verify_producer(&contracts, &produced).await.unwrap_err() {
+            ContractError::Violation { path, found, .. } => {
+                assert_eq!(path, "$.total");
+                assert_eq!(found, "nothing");
+            }
+            other => panic!("expected a violation, got {other}"),
+        }
+    }
+}
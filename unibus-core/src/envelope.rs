@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Header carrying [`BusMessage::VERSION`].
+pub const MESSAGE_VERSION: &str = "x-message-version";
+
+/// Serialized message with its routing metadata, converted by a transport into its
+/// own outgoing message.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Envelope {
+    /// Exchange, topic or channel the message goes to.
+    pub destination: String,
+    pub routing_key: String,
+    pub payload: Vec<u8>,
+    pub content_type: String,
+    pub message_type: String,
+    pub headers: BTreeMap<String, Value>,
+}
+
+/// Routing and serialization metadata of a message type, usually derived with
+/// `#[derive(BusMessage)]`.
+pub trait BusMessage: Serialize + DeserializeOwned + Send + 'static {
+    const EXCHANGE: &'static str;
+    /// Sent as the message type, e.g. for routing by type on the consumer side.
+    const MESSAGE_TYPE: &'static str;
+    const VERSION: u32 = 1;
+    const CONTENT_TYPE: &'static str = "application/json";
+
+    fn routing_key(&self) -> String;
+
+    /// JSON encoded message to the exchange of the type with its metadata set.
+    fn to_envelope(&self) -> Result<Envelope, serde_json::Error> {
+        Ok(Envelope {
+            destination: Self::EXCHANGE.to_owned(),
+            routing_key: self.routing_key(),
+            payload: serde_json::to_vec(self)?,
+            content_type: Self::CONTENT_TYPE.to_owned(),
+            message_type: Self::MESSAGE_TYPE.to_owned(),
+            headers: BTreeMap::from([(MESSAGE_VERSION.to_owned(), Self::VERSION.into())]),
+        })
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use rdkafka::{
+    consumer::{Consumer, StreamConsumer},
+    message::{Headers, Message, OwnedHeaders},
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig,
+};
+use serde::Deserialize;
+use thiserror::Error;
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::warn;
+
+use crate::{
+    memory::Routing,
+    transport::{EnvelopeHandler, Nack, Publish, Route, Subscribe},
+    Envelope,
+};
+
+/// Kafka header carrying [`Envelope::content_type`].
+pub const CONTENT_TYPE: &str = "content-type";
+/// Kafka header carrying [`Envelope::message_type`].
+pub const MESSAGE_TYPE: &str = "message-type";
+
+#[derive(Debug, Error)]
+pub enum KafkaError {
+    #[error("kafka error: {0}")]
+    Kafka(#[from] rdkafka::error::KafkaError),
+    #[error("kafka serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Client settings, e.g. read from the deployment configuration next to the rabbit ones.
+#[derive(Clone, Debug, Deserialize)]
+pub struct KafkaOptions {
+    /// `bootstrap.servers`, comma separated `host:port` pairs.
+    pub brokers: String,
+    /// Bounds waiting for a full producer queue and the delivery report.
+    #[serde(default = "default_send_timeout")]
+    pub send_timeout: Duration,
+    /// Delay before a message nacked with requeue is handled again.
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: Duration,
+    /// Further librdkafka settings for producers and consumers.
+    #[serde(default)]
+    pub properties: BTreeMap<String, String>,
+}
+
+fn default_send_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_retry_delay() -> Duration {
+    Duration::from_secs(1)
+}
+
+impl KafkaOptions {
+    pub fn new(brokers: impl Into<String>) -> Self {
+        KafkaOptions {
+            brokers: brokers.into(),
+            send_timeout: default_send_timeout(),
+            retry_delay: default_retry_delay(),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_send_timeout(mut self, timeout: Duration) -> Self {
+        self.send_timeout = timeout;
+        self
+    }
+
+    pub fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    pub fn with_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+
+    fn config(&self) -> ClientConfig {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", &self.brokers);
+        for (key, value) in &self.properties {
+            config.set(key, value);
+        }
+        config
+    }
+}
+
+/// Transport over Kafka: the destination of an envelope is the topic, its routing key
+/// the message key, so messages of one key stay ordered in their partition.
+///
+/// A subscription queue is a consumer group; routes select topics and match keys with
+/// topic semantics, non-matching messages are skipped. Offsets are stored once the
+/// handler settled a message, so delivery is at least once. Kafka has no redelivery:
+/// a message nacked with requeue is handled again in place after the retry delay,
+/// holding up its partition; nacked without requeue it is skipped.
+#[derive(Clone)]
+pub struct KafkaTransport {
+    options: KafkaOptions,
+    producer: FutureProducer,
+}
+
+impl KafkaTransport {
+    pub fn new(options: KafkaOptions) -> Result<Self, KafkaError> {
+        let producer = options.config().create()?;
+        Ok(KafkaTransport { options, producer })
+    }
+}
+
+/// Envelope headers as Kafka headers, each value JSON encoded.
+fn encode_headers(envelope: &Envelope) -> Result<OwnedHeaders, KafkaError> {
+    let mut headers = OwnedHeaders::new_with_capacity(envelope.headers.len() + 2)
+        .add(CONTENT_TYPE, envelope.content_type.as_str())
+        .add(MESSAGE_TYPE, envelope.message_type.as_str());
+    for (key, value) in &envelope.headers {
+        headers = headers.add(key, &serde_json::to_vec(value)?);
+    }
+    Ok(headers)
+}
+
+fn decode(message: &impl Message) -> Envelope {
+    let mut envelope = Envelope {
+        destination: message.topic().to_owned(),
+        routing_key: String::from_utf8_lossy(message.key().unwrap_or_default()).into_owned(),
+        payload: message.payload().unwrap_or_default().to_vec(),
+        content_type: String::new(),
+        message_type: String::new(),
+        headers: BTreeMap::new(),
+    };
+    let Some(headers) = message.headers() else {
+        return envelope;
+    };
+    for (key, value) in (0..headers.count()).filter_map(|i| headers.get(i)) {
+        match key {
+            CONTENT_TYPE => envelope.content_type = String::from_utf8_lossy(value).into_owned(),
+            MESSAGE_TYPE => envelope.message_type = String::from_utf8_lossy(value).into_owned(),
+            _ => match serde_json::from_slice(value) {
+                Ok(value) => {
+                    envelope.headers.insert(key.to_owned(), value);
+                }
+                Err(e) => warn!(error = format!("{e}"), header = key, "undecodable header"),
+            },
+        }
+    }
+    envelope
+}
+
+#[async_trait]
+impl Publish for KafkaTransport {
+    type Error = KafkaError;
+
+    /// Waits for the delivery report of the broker.
+    async fn publish(&self, envelope: Envelope) -> Result<(), KafkaError> {
+        let record = FutureRecord::to(&envelope.destination)
+            .key(&envelope.routing_key)
+            .payload(&envelope.payload)
+            .headers(encode_headers(&envelope)?);
+        self.producer
+            .send(record, self.options.send_timeout)
+            .await
+            .map_err(|(e, _)| e)?;
+        Ok(())
+    }
+}
+
+/// Member of a consumer group; leaves the group when cancelled or dropped.
+pub struct KafkaSubscription {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl KafkaSubscription {
+    /// Stops taking messages and waits for the one in hand to be handled.
+    pub async fn cancel(self) {
+        _ = self.stop.send(());
+        _ = self.task.await;
+    }
+}
+
+#[async_trait]
+impl Subscribe for KafkaTransport {
+    type Error = KafkaError;
+    type Subscription = KafkaSubscription;
+
+    async fn subscribe<H: EnvelopeHandler>(
+        &self,
+        queue: &str,
+        routes: &[Route],
+        handler: H,
+    ) -> Result<KafkaSubscription, KafkaError> {
+        let consumer: StreamConsumer = self
+            .options
+            .config()
+            .set("group.id", queue)
+            .set("enable.auto.offset.store", "false")
+            .create()?;
+        let mut topics: Vec<&str> = routes.iter().map(|r| r.destination.as_str()).collect();
+        topics.sort_unstable();
+        topics.dedup();
+        consumer.subscribe(&topics)?;
+
+        let routes = routes.to_vec();
+        let retry_delay = self.options.retry_delay;
+        let handler = Arc::new(handler);
+        let (stop, mut stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            loop {
+                let received = tokio::select! {
+                    _ = &mut stopped => break,
+                    received = consumer.recv() => received
+                        .map(|m| (decode(&m), m.partition(), m.offset())),
+                };
+                let (envelope, partition, offset) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!(error = format!("{e}"), "kafka receive failed");
+                        tokio::time::sleep(retry_delay).await;
+                        continue;
+                    }
+                };
+                let wanted = routes.iter().any(|route| {
+                    route.destination == envelope.destination
+                        && Routing::Topic.matches(&route.pattern, &envelope.routing_key)
+                });
+                if wanted {
+                    while let Err(Nack { requeue: true }) = handler.handle(envelope.clone()).await {
+                        tokio::time::sleep(retry_delay).await;
+                    }
+                }
+                if let Err(e) = consumer.store_offset(&envelope.destination, partition, offset) {
+                    warn!(error = format!("{e}"), "kafka offset not stored");
+                }
+            }
+        });
+        Ok(KafkaSubscription { stop, task })
+    }
+}
@@ -0,0 +1,17 @@
+//! Transport-agnostic part of unibus: message contracts, envelopes, transport traits,
+//! middleware, position stores and shutdown, plus the in-memory transport and, behind the
+//! `kafka` feature, a Kafka one. Message type crates depend only on this one.
+
+pub mod contracts;
+mod envelope;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod memory;
+pub mod middleware;
+pub mod position;
+pub mod rpc;
+pub mod shutdown;
+pub mod transport;
+
+pub use envelope::{BusMessage, Envelope, MESSAGE_VERSION};
+pub use unibus_macros::BusMessage;
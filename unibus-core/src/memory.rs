@@ -0,0 +1,338 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tokio::{
+    sync::{mpsc, oneshot, Mutex as AsyncMutex},
+    task::JoinHandle,
+};
+
+use crate::{
+    rpc::RpcError,
+    transport::{Call, EnvelopeHandler, Nack, Publish, Route, Subscribe},
+    Envelope,
+};
+
+const JSON_CONTENT_TYPE: &str = "application/json";
+
+#[derive(Debug, Error)]
+pub enum MemoryError {
+    #[error("no queue for destination {destination:?} and routing key {routing_key:?}")]
+    Unroutable {
+        destination: String,
+        routing_key: String,
+    },
+    #[error("memory transport serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("rpc error: {0}")]
+    Rpc(#[from] RpcError),
+    #[error("no reply within {0:?}")]
+    Timeout(Duration),
+    #[error("request on {queue} was consumed without a reply")]
+    NoReply { queue: String },
+}
+
+/// How a destination matches routing keys against the patterns of its routes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Routing {
+    Direct,
+    /// Dot separated words, `*` matching one word and `#` zero or more.
+    Topic,
+    Fanout,
+}
+
+impl Routing {
+    pub fn matches(self, pattern: &str, routing_key: &str) -> bool {
+        match self {
+            Routing::Direct => pattern == routing_key,
+            Routing::Fanout => true,
+            Routing::Topic => {
+                let pattern: Vec<&str> = pattern.split('.').collect();
+                let key: Vec<&str> = routing_key.split('.').collect();
+                topic_matches(&pattern, &key)
+            }
+        }
+    }
+}
+
+fn topic_matches(pattern: &[&str], key: &[&str]) -> bool {
+    match (pattern.split_first(), key.split_first()) {
+        (None, None) => true,
+        (Some((&"#", rest)), _) => {
+            topic_matches(rest, key) || (!key.is_empty() && topic_matches(pattern, &key[1..]))
+        }
+        (Some((&word, rest)), Some((&first, tail))) => {
+            (word == "*" || word == first) && topic_matches(rest, tail)
+        }
+        _ => false,
+    }
+}
+
+struct Item {
+    envelope: Envelope,
+    reply: Option<oneshot::Sender<Envelope>>,
+}
+
+struct Queue {
+    sender: mpsc::UnboundedSender<Item>,
+    receiver: Arc<AsyncMutex<mpsc::UnboundedReceiver<Item>>>,
+}
+
+#[derive(Default)]
+struct State {
+    routing: HashMap<String, Routing>,
+    bindings: Vec<(Route, String)>,
+    queues: HashMap<String, Queue>,
+}
+
+/// In-process transport for tests and local development without a broker.
+///
+/// Destinations route like rabbit exchanges, topic unless set otherwise; the default
+/// destination `""` delivers to the queue named by the routing key. Queues are created
+/// by the first subscription and keep their messages while nobody consumes them.
+/// Messages nacked without requeue are dropped.
+#[derive(Clone, Default)]
+pub struct MemoryTransport {
+    state: Arc<Mutex<State>>,
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_destination(self, name: impl Into<String>, routing: Routing) -> Self {
+        self.state
+            .lock()
+            .expect("memory transport lock")
+            .routing
+            .insert(name.into(), routing);
+        self
+    }
+
+    fn route(&self, envelope: &Envelope) -> Vec<mpsc::UnboundedSender<Item>> {
+        let state = self.state.lock().expect("memory transport lock");
+        if envelope.destination.is_empty() {
+            return state
+                .queues
+                .get(&envelope.routing_key)
+                .map(|queue| queue.sender.clone())
+                .into_iter()
+                .collect();
+        }
+        let routing = state
+            .routing
+            .get(&envelope.destination)
+            .copied()
+            .unwrap_or(Routing::Topic);
+        let mut queues: Vec<&str> = state
+            .bindings
+            .iter()
+            .filter(|(route, _)| {
+                route.destination == envelope.destination
+                    && routing.matches(&route.pattern, &envelope.routing_key)
+            })
+            .map(|(_, queue)| queue.as_str())
+            .collect();
+        queues.sort_unstable();
+        queues.dedup();
+        queues
+            .into_iter()
+            .filter_map(|queue| state.queues.get(queue))
+            .map(|queue| queue.sender.clone())
+            .collect()
+    }
+
+    /// Declares `queue` with its routes and hands each of its messages to `handle`,
+    /// putting back the ones it returns.
+    fn consume<F, Fut>(&self, queue: &str, routes: &[Route], handle: F) -> MemorySubscription
+    where
+        F: Fn(Item) -> Fut + Send + 'static,
+        Fut: Future<Output = Option<Item>> + Send,
+    {
+        let (sender, receiver) = {
+            let mut state = self.state.lock().expect("memory transport lock");
+            for route in routes {
+                let binding = (route.clone(), queue.to_owned());
+                if !state.bindings.contains(&binding) {
+                    state.bindings.push(binding);
+                }
+            }
+            let queue = state.queues.entry(queue.to_owned()).or_insert_with(|| {
+                let (sender, receiver) = mpsc::unbounded_channel();
+                Queue {
+                    sender,
+                    receiver: Arc::new(AsyncMutex::new(receiver)),
+                }
+            });
+            (queue.sender.clone(), queue.receiver.clone())
+        };
+        let (stop, mut stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            loop {
+                let item = tokio::select! {
+                    _ = &mut stopped => break,
+                    item = async { receiver.lock().await.recv().await } => item,
+                };
+                let Some(item) = item else { break };
+                if let Some(item) = handle(item).await {
+                    _ = sender.send(item);
+                }
+            }
+        });
+        MemorySubscription { stop, task }
+    }
+}
+
+/// Consumer of a memory queue; stops when cancelled or dropped.
+pub struct MemorySubscription {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl MemorySubscription {
+    /// Stops taking messages and waits for the one in hand to be handled.
+    pub async fn cancel(self) {
+        _ = self.stop.send(());
+        _ = self.task.await;
+    }
+}
+
+fn json_envelope(routing_key: &str, payload: Vec<u8>) -> Envelope {
+    Envelope {
+        destination: String::new(),
+        routing_key: routing_key.to_owned(),
+        payload,
+        content_type: JSON_CONTENT_TYPE.to_owned(),
+        message_type: String::new(),
+        headers: Default::default(),
+    }
+}
+
+#[async_trait]
+impl Publish for MemoryTransport {
+    type Error = MemoryError;
+
+    /// Envelopes no queue is bound for are dropped.
+    async fn publish(&self, envelope: Envelope) -> Result<(), MemoryError> {
+        for queue in self.route(&envelope) {
+            _ = queue.send(Item {
+                envelope: envelope.clone(),
+                reply: None,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Subscribe for MemoryTransport {
+    type Error = MemoryError;
+    type Subscription = MemorySubscription;
+
+    async fn subscribe<H: EnvelopeHandler>(
+        &self,
+        queue: &str,
+        routes: &[Route],
+        handler: H,
+    ) -> Result<MemorySubscription, MemoryError> {
+        let handler = Arc::new(handler);
+        Ok(self.consume(queue, routes, move |item: Item| {
+            let handler = handler.clone();
+            async move {
+                match handler.handle(item.envelope.clone()).await {
+                    Err(Nack { requeue: true }) => Some(item),
+                    _ => None,
+                }
+            }
+        }))
+    }
+}
+
+#[async_trait]
+impl Call for MemoryTransport {
+    type Error = MemoryError;
+    type Server = MemorySubscription;
+
+    /// Fails with [`MemoryError::Unroutable`] right away when nobody serves `queue`.
+    async fn call<Req, Resp>(
+        &self,
+        queue: &str,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Resp, MemoryError>
+    where
+        Req: Serialize + Sync,
+        Resp: DeserializeOwned + Send,
+    {
+        let envelope = json_envelope(queue, serde_json::to_vec(request)?);
+        let Some(sender) = self.route(&envelope).pop() else {
+            return Err(MemoryError::Unroutable {
+                destination: envelope.destination,
+                routing_key: envelope.routing_key,
+            });
+        };
+        let (reply, replied) = oneshot::channel();
+        _ = sender.send(Item {
+            envelope,
+            reply: Some(reply),
+        });
+        let reply = match tokio::time::timeout(timeout, replied).await {
+            Ok(Ok(reply)) => reply,
+            Ok(Err(_)) => {
+                return Err(MemoryError::NoReply {
+                    queue: queue.to_owned(),
+                })
+            }
+            Err(_) => return Err(MemoryError::Timeout(timeout)),
+        };
+        if let Some(e) = RpcError::from_headers(&reply.headers) {
+            return Err(e.into());
+        }
+        Ok(serde_json::from_slice(&reply.payload)?)
+    }
+
+    /// Requests that do not decode as `Req` are answered with
+    /// [`RpcStatus::InvalidArgument`](crate::rpc::RpcStatus::InvalidArgument).
+    async fn serve<Req, Resp, H, Fut>(
+        &self,
+        queue: &str,
+        handler: H,
+    ) -> Result<MemorySubscription, MemoryError>
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Resp: Serialize + Send + 'static,
+        H: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, RpcError>> + Send,
+    {
+        let handler = Arc::new(handler);
+        Ok(self.consume(queue, &[], move |item: Item| {
+            let handler = handler.clone();
+            async move {
+                let outcome = match serde_json::from_slice::<Req>(&item.envelope.payload) {
+                    Ok(request) => handler(request).await.and_then(|response| {
+                        serde_json::to_vec(&response).map_err(|e| {
+                            RpcError::internal(format!("response does not serialize: {e}"))
+                        })
+                    }),
+                    Err(e) => Err(RpcError::invalid_argument(format!("{e}"))),
+                };
+                let mut envelope = json_envelope("", Vec::new());
+                match outcome {
+                    Ok(payload) => envelope.payload = payload,
+                    Err(e) => e.write_headers(&mut envelope.headers),
+                }
+                if let Some(reply) = item.reply {
+                    _ = reply.send(envelope);
+                }
+                None
+            }
+        }))
+    }
+}
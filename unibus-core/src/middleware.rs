@@ -0,0 +1,97 @@
+use std::{future::Future, sync::Arc};
+
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+
+/// Path a [`Middleware`] is stacked on, e.g. publishing or consuming a message of a
+/// transport; `Output` is what the end of the path returns.
+pub trait Flow: Send + 'static {
+    type Output: Send + 'static;
+}
+
+/// Cross-cutting step around publishing or consuming, e.g. logging, auth headers or
+/// payload encryption. Implement `Middleware<C>` for every `C: Flow` to stack the
+/// same middleware on both paths.
+#[async_trait]
+pub trait Middleware<C: Flow>: Send + Sync + 'static {
+    /// Changes or inspects `ctx` and passes it on with [`Next::run`], or returns
+    /// without calling the rest of the stack.
+    async fn handle(&self, ctx: C, next: Next<C>) -> C::Output;
+}
+
+type Terminal<C> = Arc<dyn Fn(C) -> BoxFuture<'static, <C as Flow>::Output> + Send + Sync>;
+
+/// The rest of the stack after a middleware.
+pub struct Next<C: Flow> {
+    stack: Arc<[Arc<dyn Middleware<C>>]>,
+    index: usize,
+    terminal: Terminal<C>,
+}
+
+impl<C: Flow> Next<C> {
+    pub async fn run(self, ctx: C) -> C::Output {
+        match self.stack.get(self.index).cloned() {
+            Some(middleware) => {
+                let next = Next {
+                    index: self.index + 1,
+                    ..self
+                };
+                middleware.handle(ctx, next).await
+            }
+            None => (self.terminal)(ctx).await,
+        }
+    }
+}
+
+/// Middlewares run in the order added, the first one outermost.
+pub struct MiddlewareStack<C: Flow> {
+    stack: Arc<[Arc<dyn Middleware<C>>]>,
+}
+
+impl<C: Flow> Clone for MiddlewareStack<C> {
+    fn clone(&self) -> Self {
+        MiddlewareStack {
+            stack: self.stack.clone(),
+        }
+    }
+}
+
+impl<C: Flow> Default for MiddlewareStack<C> {
+    fn default() -> Self {
+        MiddlewareStack {
+            stack: Arc::new([]),
+        }
+    }
+}
+
+impl<C: Flow> MiddlewareStack<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(self, middleware: impl Middleware<C>) -> Self {
+        let mut stack = self.stack.to_vec();
+        stack.push(Arc::new(middleware));
+        MiddlewareStack {
+            stack: stack.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Passes `ctx` through the stack into `terminal`.
+    pub async fn run<F, Fut>(&self, ctx: C, terminal: F) -> C::Output
+    where
+        F: Fn(C) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = C::Output> + Send + 'static,
+    {
+        let next = Next {
+            stack: self.stack.clone(),
+            index: 0,
+            terminal: Arc::new(move |ctx| Box::pin(terminal(ctx))),
+        };
+        next.run(ctx).await
+    }
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use super::{PositionError, PositionStore};
+
+/// One file per consumer holding its position, replaced atomically on commit.
+pub struct FilePositionStore {
+    dir: PathBuf,
+}
+
+impl FilePositionStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FilePositionStore { dir: dir.into() }
+    }
+
+    fn path(&self, consumer: &str) -> PathBuf {
+        self.dir.join(format!("{consumer}.position"))
+    }
+}
+
+#[async_trait]
+impl PositionStore for FilePositionStore {
+    async fn load(&self, consumer: &str) -> Result<Option<u64>, PositionError> {
+        match tokio::fs::read_to_string(self.path(consumer)).await {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map(Some)
+                .map_err(|_| PositionError::Corrupt {
+                    consumer: consumer.to_owned(),
+                    value,
+                }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn commit(&self, consumer: &str, position: u64) -> Result<(), PositionError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let path = self.path(consumer);
+        let tmp = path.with_extension("position.tmp");
+        tokio::fs::write(&tmp, position.to_string()).await?;
+        tokio::fs::rename(tmp, path).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,100 @@
+mod file;
+#[cfg(feature = "redis")]
+mod redis;
+
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use thiserror::Error;
+
+pub use file::FilePositionStore;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisPositionStore;
+
+#[derive(Debug, Error)]
+pub enum PositionError {
+    #[error("position store io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("corrupt position for {consumer}: {value}")]
+    Corrupt { consumer: String, value: String },
+    #[cfg(feature = "redis")]
+    #[error("position store redis error: {0}")]
+    Redis(#[from] ::redis::RedisError),
+}
+
+/// Durable last committed offset per consumer of a replayable source (stream queue, log).
+#[async_trait]
+pub trait PositionStore: Send + Sync {
+    async fn load(&self, consumer: &str) -> Result<Option<u64>, PositionError>;
+    async fn commit(&self, consumer: &str, position: u64) -> Result<(), PositionError>;
+}
+
+/// When a [`PositionTracker`] writes the processed position to its store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommitInterval {
+    /// After every `n` processed messages.
+    Messages(usize),
+    /// On the first processed message after the period elapsed.
+    Elapsed(Duration),
+}
+
+/// At-least-once position bookkeeping of one consumer.
+///
+/// Positions are reported after a message was handled and only committed every
+/// [`CommitInterval`], so after a crash or restart the consumer resumes right after
+/// the last commit and messages handled since then are delivered again.
+/// Handlers must tolerate those duplicates; a shorter interval means fewer of them
+/// at the cost of more store writes.
+pub struct PositionTracker<S> {
+    store: S,
+    consumer: String,
+    interval: CommitInterval,
+    pending: Option<u64>,
+    uncommitted: usize,
+    last_commit: Instant,
+}
+
+impl<S: PositionStore> PositionTracker<S> {
+    /// Loads the last committed position and returns the offset to resume from.
+    pub async fn resume(
+        store: S,
+        consumer: impl Into<String>,
+        interval: CommitInterval,
+    ) -> Result<(Self, Option<u64>), PositionError> {
+        let consumer = consumer.into();
+        let next = store.load(&consumer).await?.map(|p| p + 1);
+        let tracker = PositionTracker {
+            store,
+            consumer,
+            interval,
+            pending: None,
+            uncommitted: 0,
+            last_commit: Instant::now(),
+        };
+        Ok((tracker, next))
+    }
+
+    /// Reports `position` as handled; returns whether it was committed now.
+    pub async fn processed(&mut self, position: u64) -> Result<bool, PositionError> {
+        self.pending = Some(position);
+        self.uncommitted += 1;
+        let due = match self.interval {
+            CommitInterval::Messages(n) => self.uncommitted >= n,
+            CommitInterval::Elapsed(period) => self.last_commit.elapsed() >= period,
+        };
+        if due {
+            self.flush().await?;
+        }
+        Ok(due)
+    }
+
+    /// Commits the last handled position, e.g. on graceful shutdown.
+    pub async fn flush(&mut self) -> Result<(), PositionError> {
+        if let Some(position) = self.pending.take() {
+            self.store.commit(&self.consumer, position).await?;
+        }
+        self.uncommitted = 0;
+        self.last_commit = Instant::now();
+        Ok(())
+    }
+}
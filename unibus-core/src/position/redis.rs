@@ -0,0 +1,39 @@
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use super::{PositionError, PositionStore};
+
+/// Positions kept in redis under `<prefix>:<consumer>`.
+pub struct RedisPositionStore {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+impl RedisPositionStore {
+    pub async fn new(client: redis::Client, prefix: impl Into<String>) -> Result<Self, PositionError> {
+        Ok(RedisPositionStore {
+            connection: ConnectionManager::new(client).await?,
+            prefix: prefix.into(),
+        })
+    }
+
+    fn key(&self, consumer: &str) -> String {
+        format!("{}:{consumer}", self.prefix)
+    }
+}
+
+#[async_trait]
+impl PositionStore for RedisPositionStore {
+    async fn load(&self, consumer: &str) -> Result<Option<u64>, PositionError> {
+        let mut connection = self.connection.clone();
+        Ok(connection.get(self.key(consumer)).await?)
+    }
+
+    async fn commit(&self, consumer: &str, position: u64) -> Result<(), PositionError> {
+        let mut connection = self.connection.clone();
+        connection
+            .set::<_, _, ()>(self.key(consumer), position)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde_json::Value;
+
+const STATUS: &str = "x-rpc-status";
+const MESSAGE: &str = "x-rpc-message";
+const DETAILS: &str = "x-rpc-details";
+
+/// Outcome of an RPC call, with the codes of gRPC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RpcStatus {
+    Ok,
+    InvalidArgument,
+    DeadlineExceeded,
+    NotFound,
+    Internal,
+    Unavailable,
+}
+
+impl RpcStatus {
+    pub fn code(self) -> u64 {
+        match self {
+            RpcStatus::Ok => 0,
+            RpcStatus::InvalidArgument => 3,
+            RpcStatus::DeadlineExceeded => 4,
+            RpcStatus::NotFound => 5,
+            RpcStatus::Internal => 13,
+            RpcStatus::Unavailable => 14,
+        }
+    }
+
+    /// Status of `code`; codes this crate does not know are [`RpcStatus::Internal`].
+    pub fn from_code(code: u64) -> Self {
+        match code {
+            0 => RpcStatus::Ok,
+            3 => RpcStatus::InvalidArgument,
+            4 => RpcStatus::DeadlineExceeded,
+            5 => RpcStatus::NotFound,
+            14 => RpcStatus::Unavailable,
+            _ => RpcStatus::Internal,
+        }
+    }
+}
+
+impl fmt::Display for RpcStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RpcStatus::Ok => "ok",
+            RpcStatus::InvalidArgument => "invalid argument",
+            RpcStatus::DeadlineExceeded => "deadline exceeded",
+            RpcStatus::NotFound => "not found",
+            RpcStatus::Internal => "internal",
+            RpcStatus::Unavailable => "unavailable",
+        })
+    }
+}
+
+/// Failed RPC call as returned by the server handler and seen by the caller.
+/// Travels in the `x-rpc-status`, `x-rpc-message` and `x-rpc-details` headers of the
+/// reply [`Envelope`](crate::Envelope).
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("{status}: {message}")]
+pub struct RpcError {
+    pub status: RpcStatus,
+    pub message: String,
+    pub details: BTreeMap<String, String>,
+}
+
+impl RpcError {
+    pub fn new(status: RpcStatus, message: impl Into<String>) -> Self {
+        RpcError {
+            status,
+            message: message.into(),
+            details: BTreeMap::new(),
+        }
+    }
+
+    pub fn invalid_argument(message: impl Into<String>) -> Self {
+        RpcError::new(RpcStatus::InvalidArgument, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        RpcError::new(RpcStatus::NotFound, message)
+    }
+
+    pub fn unavailable(message: impl Into<String>) -> Self {
+        RpcError::new(RpcStatus::Unavailable, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        RpcError::new(RpcStatus::Internal, message)
+    }
+
+    pub fn deadline_exceeded(message: impl Into<String>) -> Self {
+        RpcError::new(RpcStatus::DeadlineExceeded, message)
+    }
+
+    pub fn with_detail(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.details.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn write_headers(&self, headers: &mut BTreeMap<String, Value>) {
+        headers.insert(STATUS.into(), self.status.code().into());
+        headers.insert(MESSAGE.into(), self.message.clone().into());
+        let details = self
+            .details
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::from(value.clone())))
+            .collect();
+        headers.insert(DETAILS.into(), Value::Object(details));
+    }
+
+    /// Error carried by reply headers; `None` without a status or with [`RpcStatus::Ok`].
+    pub fn from_headers(headers: &BTreeMap<String, Value>) -> Option<Self> {
+        let status = RpcStatus::from_code(headers.get(STATUS)?.as_u64()?);
+        if status == RpcStatus::Ok {
+            return None;
+        }
+        let details = match headers.get(DETAILS) {
+            Some(Value::Object(details)) => details
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_owned())))
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        Some(RpcError {
+            status,
+            message: headers
+                .get(MESSAGE)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_owned(),
+            details,
+        })
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Shutdown stages, in the order subsystems are quiesced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    Running,
+    /// Consumers stop fetching and drain in-flight handlers.
+    Consumers,
+    /// Publishers refuse new messages and settle pending confirms.
+    Publishers,
+    /// Connections close and stop reconnecting.
+    Connections,
+    Stopped,
+}
+
+const DRAINED_STAGES: [Stage; 3] = [Stage::Consumers, Stage::Publishers, Stage::Connections];
+
+fn slot(stage: Stage) -> Option<usize> {
+    DRAINED_STAGES.iter().position(|s| *s == stage)
+}
+
+struct Inner {
+    stage: watch::Sender<Stage>,
+    active: [watch::Sender<usize>; 3],
+}
+
+/// Crate-wide shutdown token observed by connections, publishers and consumers.
+///
+/// Components hold a [`ShutdownGuard`] for their stage while they have work to finish;
+/// [`Shutdown::advance`] moves to the next stage only once every guard of it is gone.
+#[derive(Clone)]
+pub struct Shutdown(Arc<Inner>);
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown(Arc::new(Inner {
+            stage: watch::channel(Stage::Running).0,
+            active: [0; 3].map(|n| watch::channel(n).0),
+        }))
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether both are handles on the same shutdown.
+    pub fn is_same(&self, other: &Shutdown) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    pub fn stage(&self) -> Stage {
+        *self.0.stage.borrow()
+    }
+
+    pub fn reached(&self, stage: Stage) -> bool {
+        self.stage() >= stage
+    }
+
+    /// Resolves once the shutdown reached `stage`.
+    pub async fn wait(&self, stage: Stage) {
+        let mut rx = self.0.stage.subscribe();
+        while *rx.borrow_and_update() < stage {
+            if rx.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Marks work that has to finish before `stage` is over.
+    pub fn guard(&self, stage: Stage) -> ShutdownGuard {
+        if let Some(slot) = slot(stage) {
+            self.0.active[slot].send_modify(|n| *n += 1);
+        }
+        ShutdownGuard {
+            shutdown: self.clone(),
+            stage,
+        }
+    }
+
+    /// Enters `stage` and waits until all of its guards are dropped.
+    pub async fn advance(&self, stage: Stage) {
+        self.0.stage.send_if_modified(|current| {
+            let advanced = *current < stage;
+            if advanced {
+                *current = stage;
+            }
+            advanced
+        });
+        if let Some(slot) = slot(stage) {
+            let mut rx = self.0.active[slot].subscribe();
+            while *rx.borrow_and_update() > 0 {
+                if rx.changed().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Runs every stage in order.
+    pub async fn run(&self) {
+        for stage in DRAINED_STAGES {
+            self.advance(stage).await;
+        }
+        self.advance(Stage::Stopped).await;
+    }
+}
+
+pub struct ShutdownGuard {
+    shutdown: Shutdown,
+    stage: Stage,
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        if let Some(slot) = slot(self.stage) {
+            self.shutdown.0.active[slot].send_modify(|n| *n -= 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lets spawned tasks run until they block again.
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn advance_waits_for_the_guards_of_the_stage() {
+        let shutdown = Shutdown::new();
+        let guard = shutdown.guard(Stage::Consumers);
+        let other = shutdown.guard(Stage::Publishers);
+
+        let advancing = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.advance(Stage::Consumers).await }
+        });
+        settle().await;
+        assert!(shutdown.reached(Stage::Consumers));
+        assert!(!advancing.is_finished());
+
+        drop(guard);
+        advancing.await.unwrap();
+        drop(other);
+    }
+
+    #[tokio::test]
+    async fn run_drains_the_stages_in_order() {
+        let shutdown = Shutdown::new();
+        let consumers = shutdown.guard(Stage::Consumers);
+        let publishers = shutdown.guard(Stage::Publishers);
+
+        let running = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.run().await }
+        });
+        settle().await;
+        assert_eq!(shutdown.stage(), Stage::Consumers);
+
+        drop(consumers);
+        settle().await;
+        assert_eq!(shutdown.stage(), Stage::Publishers);
+        assert!(!running.is_finished());
+
+        drop(publishers);
+        running.await.unwrap();
+        assert_eq!(shutdown.stage(), Stage::Stopped);
+    }
+}
@@ -0,0 +1,108 @@
+use std::{future::Future, time::Duration};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{rpc::RpcError, BusMessage, Envelope};
+
+/// Handler succeeded, the message is acked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Ack;
+
+/// Handler failed, the message is nacked and optionally requeued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Nack {
+    pub requeue: bool,
+}
+
+/// Sends envelopes to their destination, routed by their routing key.
+#[async_trait]
+pub trait Publish: Send + Sync {
+    type Error: std::error::Error + From<serde_json::Error> + Send + Sync + 'static;
+
+    async fn publish(&self, envelope: Envelope) -> Result<(), Self::Error>;
+
+    /// Publishes `message` as the envelope of its type.
+    async fn publish_message<T: BusMessage + Sync>(&self, message: &T) -> Result<(), Self::Error> {
+        self.publish(message.to_envelope()?).await
+    }
+}
+
+#[async_trait]
+pub trait EnvelopeHandler: Send + Sync + 'static {
+    async fn handle(&self, envelope: Envelope) -> Result<Ack, Nack>;
+}
+
+#[async_trait]
+impl<F, Fut> EnvelopeHandler for F
+where
+    F: Fn(Envelope) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    async fn handle(&self, envelope: Envelope) -> Result<Ack, Nack> {
+        self(envelope).await
+    }
+}
+
+/// Messages published to `destination` whose routing key matches `pattern`, with the
+/// semantics of the destination: exact for direct, `*` and `#` wildcards for topic.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Route {
+    pub destination: String,
+    pub pattern: String,
+}
+
+impl Route {
+    pub fn new(destination: impl Into<String>, pattern: impl Into<String>) -> Self {
+        Route {
+            destination: destination.into(),
+            pattern: pattern.into(),
+        }
+    }
+}
+
+/// Consumes queues bound to destinations.
+#[async_trait]
+pub trait Subscribe: Send + Sync {
+    type Error: std::error::Error + Send + Sync + 'static;
+    type Subscription: Send;
+
+    /// Binds `queue` to every route and hands its messages to `handler`; handlers
+    /// subscribed to the same queue compete for its messages.
+    async fn subscribe<H: EnvelopeHandler>(
+        &self,
+        queue: &str,
+        routes: &[Route],
+        handler: H,
+    ) -> Result<Self::Subscription, Self::Error>;
+}
+
+/// Request/response calls over queues, failures travelling as [`RpcError`].
+#[async_trait]
+pub trait Call: Send + Sync {
+    type Error: std::error::Error + From<RpcError> + Send + Sync + 'static;
+    type Server: Send;
+
+    /// Sends `request` to `queue` and waits up to `timeout` for the reply.
+    async fn call<Req, Resp>(
+        &self,
+        queue: &str,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Resp, Self::Error>
+    where
+        Req: Serialize + Sync,
+        Resp: DeserializeOwned + Send;
+
+    /// Answers each request on `queue` with the result of `handler`.
+    async fn serve<Req, Resp, H, Fut>(
+        &self,
+        queue: &str,
+        handler: H,
+    ) -> Result<Self::Server, Self::Error>
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Resp: Serialize + Send + 'static,
+        H: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, RpcError>> + Send;
+}
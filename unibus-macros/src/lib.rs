@@ -0,0 +1,412 @@
+//! Derive and attribute macros for `unibus-core` message types and `unibus-rabbit`.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::{format_ident, quote};
+use syn::{
+    ext::IdentExt, parse_macro_input, Data, DeriveInput, Error, Fields, Ident, LitInt, LitStr,
+    Path,
+};
+
+/// Implements `unibus_core::BusMessage`.
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize, BusMessage)]
+/// #[bus(exchange = "orders", routing_key = "order.{region}", version = 2)]
+/// struct OrderPlaced { region: String, id: u64 }
+/// ```
+///
+/// All `bus` arguments are optional: the exchange and message type default to the
+/// kebab-cased type name, the routing key to empty, the version to 1 and the content
+/// type to JSON. `{field}` placeholders in the routing key are replaced with the
+/// `Display` of the named field.
+///
+/// The trait is found through `unibus-core` when the crate depends on it, otherwise
+/// through the re-export in `unibus-rabbit`; `#[bus(crate = path::to::core)]` names
+/// it explicitly.
+#[proc_macro_derive(BusMessage, attributes(bus))]
+pub fn derive_bus_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let message_type = kebab(&name.to_string());
+    let mut exchange = message_type.clone();
+    let mut routing_key = LitStr::new("", Span::call_site());
+    let mut version = 1u32;
+    let mut content_type = String::from("application/json");
+    let mut krate: Option<Path> = None;
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("bus")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("exchange") {
+                exchange = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("routing_key") {
+                routing_key = meta.value()?.parse()?;
+            } else if meta.path.is_ident("version") {
+                version = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+            } else if meta.path.is_ident("content_type") {
+                content_type = meta.value()?.parse::<LitStr>()?.value();
+            } else if meta.path.is_ident("crate") {
+                krate = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error(
+                    "expected exchange, routing_key, version, content_type or crate",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+
+    let (format, fields) = routing_template(&routing_key)?;
+    check_fields(&input, &fields, &routing_key)?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let krate = match krate {
+        Some(krate) => quote!(#krate),
+        None => core_path(),
+    };
+
+    Ok(quote! {
+        impl #impl_generics #krate::BusMessage for #name #ty_generics #where_clause {
+            const EXCHANGE: &'static str = #exchange;
+            const MESSAGE_TYPE: &'static str = #message_type;
+            const VERSION: u32 = #version;
+            const CONTENT_TYPE: &'static str = #content_type;
+
+            fn routing_key(&self) -> ::std::string::String {
+                ::std::format!(#format, #(self.#fields),*)
+            }
+        }
+    })
+}
+
+/// Path of the crate exporting `BusMessage`, as named in the caller's manifest.
+fn core_path() -> proc_macro2::TokenStream {
+    let found = |name: String| {
+        let name = format_ident!("{}", name.replace('-', "_"));
+        quote!(::#name)
+    };
+    match crate_name("unibus-core") {
+        // also taken by the tests and examples of unibus-core, where `crate` is not it
+        Ok(FoundCrate::Itself) => quote!(::unibus_core),
+        Ok(FoundCrate::Name(name)) => found(name),
+        Err(_) => match crate_name("unibus-rabbit") {
+            Ok(FoundCrate::Itself) => quote!(::unibus_rabbit::bus),
+            Ok(FoundCrate::Name(name)) => {
+                let rabbit = found(name);
+                quote!(#rabbit::bus)
+            }
+            Err(_) => quote!(::unibus_core),
+        },
+    }
+}
+
+/// Splits `order.{region}` into the format string `order.{}` and the field names.
+fn routing_template(template: &LitStr) -> syn::Result<(String, Vec<Ident>)> {
+    let value = template.value();
+    let span = template.span();
+    let mut format = String::with_capacity(value.len());
+    let mut fields = Vec::new();
+    let mut rest = value.as_str();
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            return Err(Error::new(span, "unclosed `{` in routing_key"));
+        };
+        format.push_str(&rest[..start]);
+        format.push_str("{}");
+        let name = rest[start + 1..start + len].trim();
+        let mut field = syn::parse_str::<Ident>(name).map_err(|_| {
+            Error::new(
+                span,
+                format!("`{{{name}}}` in routing_key is not a field name"),
+            )
+        })?;
+        field.set_span(span);
+        fields.push(field);
+        rest = &rest[start + len + 1..];
+    }
+    if rest.contains('}') {
+        return Err(Error::new(span, "unmatched `}` in routing_key"));
+    }
+    format.push_str(rest);
+    Ok((format, fields))
+}
+
+/// Fails for routing key placeholders naming no field of the struct.
+fn check_fields(input: &DeriveInput, fields: &[Ident], template: &LitStr) -> syn::Result<()> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new(
+            template.span(),
+            "routing_key placeholders need a struct with named fields",
+        ));
+    };
+    let declared: Vec<&Ident> = match &data.fields {
+        Fields::Named(named) => named.named.iter().filter_map(|f| f.ident.as_ref()).collect(),
+        _ => Vec::new(),
+    };
+    for field in fields {
+        if !declared.iter().any(|d| d.unraw() == field.unraw()) {
+            return Err(Error::new(
+                template.span(),
+                format!("no field `{}` on `{}`", field.unraw(), input.ident),
+            ));
+        }
+    }
+    Ok(())
+}
+
+// same rule as `unibus_rabbit::bus::message_type_name`
+fn kebab(name: &str) -> String {
+    let mut kebab = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                kebab.push('-');
+            }
+            kebab.extend(c.to_lowercase());
+        } else {
+            kebab.push(c);
+        }
+    }
+    kebab
+}
+
+/// Declares broker topology with names checked at compile time.
+///
+/// ```ignore
+/// let topology = topology! {
+///     exchange "orders": topic;
+///     queue "billing.orders";
+///     bind "billing.orders" => "orders": "order.*";
+/// };
+/// ```
+///
+/// Expands to the `Vec<Box<dyn Topology>>` taken by `Connection::declare`. A binding
+/// naming a queue or exchange not declared in the same block is a compile error; the
+/// default exchange and `amq.*` exchanges are always known. Exchange kinds are
+/// `direct`, `fanout`, `topic` and `headers`.
+#[proc_macro]
+pub fn topology(input: TokenStream) -> TokenStream {
+    let items = parse_macro_input!(input as topology::Items);
+    items
+        .expand()
+        // a block, so several errors still form one expression
+        .unwrap_or_else(|e| {
+            let errors = e.into_compile_error();
+            quote!({ #errors })
+        })
+        .into()
+}
+
+mod topology {
+    use std::collections::HashSet;
+
+    use proc_macro2::TokenStream;
+    use quote::quote;
+    use syn::{
+        parse::{Parse, ParseStream},
+        Error, Ident, LitStr, Token,
+    };
+
+    pub enum Item {
+        Exchange {
+            name: LitStr,
+            kind: Ident,
+        },
+        Queue {
+            name: LitStr,
+        },
+        Bind {
+            queue: LitStr,
+            exchange: LitStr,
+            key: Option<LitStr>,
+        },
+    }
+
+    pub struct Items(Vec<Item>);
+
+    impl Parse for Item {
+        fn parse(input: ParseStream) -> syn::Result<Self> {
+            let keyword: Ident = input.parse()?;
+            let item = match keyword.to_string().as_str() {
+                "exchange" => {
+                    let name = input.parse()?;
+                    input.parse::<Token![:]>()?;
+                    let kind: Ident = input.parse()?;
+                    if !["direct", "fanout", "topic", "headers"]
+                        .contains(&kind.to_string().as_str())
+                    {
+                        return Err(Error::new(
+                            kind.span(),
+                            "expected direct, fanout, topic or headers",
+                        ));
+                    }
+                    Item::Exchange { name, kind }
+                }
+                "queue" => Item::Queue {
+                    name: input.parse()?,
+                },
+                "bind" => {
+                    let queue = input.parse()?;
+                    input.parse::<Token![=>]>()?;
+                    let exchange = input.parse()?;
+                    let key = match input.parse::<Option<Token![:]>>()? {
+                        Some(_) => Some(input.parse()?),
+                        None => None,
+                    };
+                    Item::Bind {
+                        queue,
+                        exchange,
+                        key,
+                    }
+                }
+                _ => {
+                    return Err(Error::new(
+                        keyword.span(),
+                        "expected exchange, queue or bind",
+                    ))
+                }
+            };
+            input.parse::<Token![;]>()?;
+            Ok(item)
+        }
+    }
+
+    impl Parse for Items {
+        fn parse(input: ParseStream) -> syn::Result<Self> {
+            let mut items = Vec::new();
+            while !input.is_empty() {
+                items.push(input.parse()?);
+            }
+            Ok(Items(items))
+        }
+    }
+
+    impl Items {
+        pub fn expand(&self) -> syn::Result<TokenStream> {
+            let mut exchanges = HashSet::new();
+            let mut queues = HashSet::new();
+            for item in &self.0 {
+                match item {
+                    Item::Exchange { name, .. } => _ = exchanges.insert(name.value()),
+                    Item::Queue { name } => _ = queues.insert(name.value()),
+                    Item::Bind { .. } => {}
+                }
+            }
+            let mut errors: Option<Error> = None;
+            let mut fail = |error: Error| match &mut errors {
+                Some(errors) => errors.combine(error),
+                None => errors = Some(error),
+            };
+            let mut parts = Vec::with_capacity(self.0.len());
+            for item in &self.0 {
+                let part = match item {
+                    Item::Exchange { name, kind } => {
+                        quote!(::unibus_rabbit::rabbit::topology::Exchange::#kind(#name))
+                    }
+                    Item::Queue { name } => quote!(::unibus_rabbit::rabbit::topology::Queue::new(#name)),
+                    Item::Bind {
+                        queue,
+                        exchange,
+                        key,
+                    } => {
+                        if !queues.contains(&queue.value()) {
+                            fail(Error::new(
+                                queue.span(),
+                                "queue is not declared in this topology",
+                            ));
+                        }
+                        let known =
+                            exchange.value().is_empty() || exchange.value().starts_with("amq.");
+                        if !known && !exchanges.contains(&exchange.value()) {
+                            fail(Error::new(
+                                exchange.span(),
+                                "exchange is not declared in this topology",
+                            ));
+                        }
+                        let key = key.as_ref().map_or_else(|| quote!(""), |key| quote!(#key));
+                        quote!(::unibus_rabbit::rabbit::topology::Binding::new(#queue, #exchange, #key))
+                    }
+                };
+                parts.push(part);
+            }
+            if let Some(errors) = errors {
+                return Err(errors);
+            }
+            Ok(quote! {{
+                let topology: ::std::vec::Vec<::std::boxed::Box<dyn ::unibus_rabbit::rabbit::topology::Topology>> =
+                    ::std::vec![#(::std::boxed::Box::new(#parts)),*];
+                topology
+            }})
+        }
+    }
+}
+
+/// Turns `async fn name(delivery: Delivery) -> Result<Ack, Nack>` into a consumer
+/// registration: the function stays callable, and a `NAME: HandlerSpec` constant is
+/// generated next to it for `BusHost::with_handler`.
+///
+/// ```ignore
+/// #[unibus_rabbit::handler(queue = "billing.orders", bind = "orders/order.*")]
+/// async fn bill_order(delivery: Delivery) -> Result<Ack, Nack> { ... }
+///
+/// let host = BusHost::new(client).with_handler(BILL_ORDER);
+/// ```
+///
+/// `bind` may repeat; it takes `exchange/routing key`, split at the first `/`.
+/// The queue and the bindings are declared by `BusHost::start_handlers`.
+#[proc_macro_attribute]
+pub fn handler(args: TokenStream, item: TokenStream) -> TokenStream {
+    let function = parse_macro_input!(item as syn::ItemFn);
+    let mut queue: Option<LitStr> = None;
+    let mut bindings = Vec::new();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("queue") {
+            queue = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("bind") {
+            let bind: LitStr = meta.value()?.parse()?;
+            let value = bind.value();
+            let (exchange, key) = value.split_once('/').unwrap_or((&value, ""));
+            bindings.push((exchange.to_owned(), key.to_owned()));
+        } else {
+            return Err(meta.error("expected queue or bind"));
+        }
+        Ok(())
+    });
+    parse_macro_input!(args with parser);
+
+    let name = &function.sig.ident;
+    let Some(queue) = queue else {
+        return Error::new(Span::call_site(), "missing queue = \"...\"")
+            .into_compile_error()
+            .into();
+    };
+    if function.sig.asyncness.is_none() {
+        return Error::new_spanned(function.sig.fn_token, "handler must be an async fn")
+            .into_compile_error()
+            .into();
+    }
+    let vis = &function.vis;
+    let spec = format_ident!("{}", name.unraw().to_string().to_uppercase(), span = name.span());
+    let label = name.to_string();
+    let bindings = bindings.iter().map(|(e, k)| quote!((#e, #k)));
+    quote! {
+        #function
+
+        #vis const #spec: ::unibus_rabbit::bus::HandlerSpec = ::unibus_rabbit::bus::HandlerSpec {
+            name: #label,
+            queue: #queue,
+            bindings: &[#(#bindings),*],
+            handler: |delivery| ::std::boxed::Box::pin(#name(delivery)),
+        };
+    }
+    .into()
+}
@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::level_filters::LevelFilter;
+
+use super::{replay_faults, BusHost, ConnectionDiagnostics, Diagnostics};
+use crate::rabbit::{Connection, Publisher};
+
+const REPLAY_MAX: usize = 100;
+
+type Render = Arc<dyn Fn() -> String + Send + Sync>;
+
+/// Ops surface of a [`BusHost`] as an axum router, to nest into the service router:
+///
+/// - `GET /diagnostics`: the [`Diagnostics`] snapshot;
+/// - `GET /health`: 200 when every connection is ready, 503 otherwise;
+/// - `GET /metrics`: the output of [`AdminRouter::with_metrics`];
+/// - `POST /consumers/:queue/pause` and `POST /consumers/:queue/resume`;
+/// - `POST /faults/:queue/replay?max=n`: [`replay_faults`], 100 messages by default;
+/// - `GET /telemetry` and `PUT /telemetry` with `{"level": "debug", "sampling": 0.1}`:
+///   [`BusHost::set_telemetry`].
+#[derive(Clone)]
+pub struct AdminRouter {
+    host: Arc<BusHost>,
+    replay: Option<(Connection, Publisher)>,
+    metrics: Option<Render>,
+}
+
+#[derive(Serialize)]
+struct Health {
+    ready: bool,
+    connections: Vec<ConnectionDiagnostics>,
+}
+
+#[derive(Deserialize)]
+struct ReplayQuery {
+    max: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TelemetryBody {
+    level: String,
+    sampling: f64,
+}
+
+#[derive(Serialize)]
+struct Replayed {
+    replayed: usize,
+}
+
+impl AdminRouter {
+    pub fn new(host: Arc<BusHost>) -> Self {
+        AdminRouter {
+            host,
+            replay: None,
+            metrics: None,
+        }
+    }
+
+    /// Enables fault replay, reading and republishing on `connection`.
+    pub fn with_replay(mut self, connection: Connection) -> Self {
+        let publisher = self.host.publisher(connection.clone());
+        self.replay = Some((connection, publisher));
+        self
+    }
+
+    /// Serves `render()` on `/metrics`, e.g. the text of a prometheus exporter handle.
+    pub fn with_metrics(mut self, render: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        self.metrics = Some(Arc::new(render));
+        self
+    }
+
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/diagnostics", get(diagnostics))
+            .route("/health", get(health))
+            .route("/metrics", get(metrics))
+            .route("/consumers/:queue/pause", post(pause))
+            .route("/consumers/:queue/resume", post(resume))
+            .route("/faults/:queue/replay", post(replay))
+            .route("/telemetry", get(telemetry).put(set_telemetry))
+            .with_state(self)
+    }
+}
+
+async fn diagnostics(State(admin): State<AdminRouter>) -> Json<Diagnostics> {
+    Json(admin.host.diagnostics().await)
+}
+
+async fn health(State(admin): State<AdminRouter>) -> (StatusCode, Json<Health>) {
+    let connections = admin.host.diagnostics().await.connections;
+    let ready = !connections.is_empty() && connections.iter().all(|c| c.state == "ready");
+    let status = match ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (status, Json(Health { ready, connections }))
+}
+
+async fn metrics(State(admin): State<AdminRouter>) -> Response {
+    match &admin.metrics {
+        Some(render) => render().into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn pause(State(admin): State<AdminRouter>, Path(queue): Path<String>) -> StatusCode {
+    match admin.host.pause(&queue).await {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn resume(State(admin): State<AdminRouter>, Path(queue): Path<String>) -> StatusCode {
+    match admin.host.resume(&queue) {
+        true => StatusCode::NO_CONTENT,
+        false => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn replay(
+    State(admin): State<AdminRouter>,
+    Path(queue): Path<String>,
+    Query(query): Query<ReplayQuery>,
+) -> Response {
+    let Some((connection, publisher)) = &admin.replay else {
+        return (StatusCode::NOT_IMPLEMENTED, "fault replay is not enabled").into_response();
+    };
+    let max = query.max.unwrap_or(REPLAY_MAX);
+    match replay_faults(connection, publisher, &queue, max).await {
+        Ok(replayed) => Json(Replayed { replayed }).into_response(),
+        Err(e) => (StatusCode::SERVICE_UNAVAILABLE, format!("{e}")).into_response(),
+    }
+}
+
+async fn telemetry(State(admin): State<AdminRouter>) -> Json<TelemetryBody> {
+    let telemetry = admin.host.telemetry();
+    Json(TelemetryBody {
+        level: telemetry.level.to_string(),
+        sampling: telemetry.sampling,
+    })
+}
+
+async fn set_telemetry(
+    State(admin): State<AdminRouter>,
+    Json(body): Json<TelemetryBody>,
+) -> Response {
+    match body.level.parse::<LevelFilter>() {
+        Ok(level) => {
+            admin.host.set_telemetry(level, body.sampling);
+            telemetry(State(admin)).await.into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, format!("{e}")).into_response(),
+    }
+}
@@ -0,0 +1,80 @@
+use lapin::types::{AMQPValue, FieldTable};
+
+use crate::rabbit::topology::{Exchange, Queue, Topology};
+
+/// Org-wide arguments for the exchanges and queues the bus declares, e.g. quorum
+/// queues dead-lettering to a convention-named exchange. Arguments set on an object
+/// itself win over the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct TopologyDefaults {
+    pub exchange_arguments: FieldTable,
+    pub queue_arguments: FieldTable,
+    pub dead_letter_exchange: Option<String>,
+}
+
+impl TopologyDefaults {
+    pub fn new() -> Self {
+        TopologyDefaults::default()
+    }
+
+    pub fn with_exchange_argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.exchange_arguments.insert(key.into(), value);
+        self
+    }
+
+    pub fn with_queue_argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.queue_arguments.insert(key.into(), value);
+        self
+    }
+
+    /// Durable queues are declared as quorum queues.
+    pub fn with_quorum_queues(self) -> Self {
+        self.with_queue_argument("x-queue-type", AMQPValue::LongString("quorum".into()))
+    }
+
+    /// Durable queues dead-letter to the topic exchange `exchange`, declared along
+    /// with them; see [`NamingConvention::dead_letter_exchange`](super::NamingConvention::dead_letter_exchange).
+    pub fn with_dead_letter_exchange(mut self, exchange: impl Into<String>) -> Self {
+        self.dead_letter_exchange = Some(exchange.into());
+        self
+    }
+
+    pub fn exchange(&self, mut exchange: Exchange) -> Exchange {
+        inherit(&mut exchange.arguments, &self.exchange_arguments);
+        exchange
+    }
+
+    /// Applies to durable shared queues only: quorum queues and dead-lettering make
+    /// no sense for transient, exclusive or auto-delete ones.
+    pub fn queue(&self, mut queue: Queue) -> Queue {
+        if !queue.durable || queue.exclusive || queue.auto_delete {
+            return queue;
+        }
+        inherit(&mut queue.arguments, &self.queue_arguments);
+        if let Some(exchange) = &self.dead_letter_exchange {
+            let mut dead_letter = FieldTable::default();
+            dead_letter.insert(
+                "x-dead-letter-exchange".into(),
+                AMQPValue::LongString(exchange.as_str().into()),
+            );
+            inherit(&mut queue.arguments, &dead_letter);
+        }
+        queue
+    }
+
+    /// The dead letter exchange, declared before the queues referring to it.
+    pub fn topology(&self) -> Vec<Box<dyn Topology>> {
+        self.dead_letter_exchange
+            .iter()
+            .map(|exchange| Box::new(self.exchange(Exchange::topic(exchange))) as Box<dyn Topology>)
+            .collect()
+    }
+}
+
+fn inherit(arguments: &mut FieldTable, defaults: &FieldTable) {
+    for (key, value) in defaults.inner() {
+        if !arguments.inner().contains_key(key) {
+            arguments.insert(key.clone(), value.clone());
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::rabbit::{
+    consumer::ConsumerStatus, Connection, ConnectionEvent, ConnectionState, PublisherProbe,
+};
+
+/// Snapshot of what a [`BusHost`](super::BusHost) runs, e.g. for an admin endpoint.
+/// Times are unix milliseconds.
+#[derive(Clone, Debug, Serialize)]
+pub struct Diagnostics {
+    pub taken_at: u64,
+    pub connections: Vec<ConnectionDiagnostics>,
+    pub consumers: Vec<ConsumerStatus>,
+    pub publishers: Vec<PublisherDiagnostics>,
+    pub topology: Vec<TopologyStatus>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ConnectionDiagnostics {
+    pub name: String,
+    /// `none`, `ready`, `error` or `closed`; `stopped` once the connection actor is gone.
+    pub state: &'static str,
+    /// Endpoint connected to while ready.
+    pub endpoint: Option<String>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<u64>,
+    /// When the topology of the options was last declared.
+    pub topology_applied_at: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PublisherDiagnostics {
+    pub connection: String,
+    /// Publishes waiting for the broker confirm.
+    pub pending: usize,
+    pub republished: u64,
+    pub suppressed_duplicates: u64,
+}
+
+/// Outcome of declaring the queue and bindings of a registered handler.
+#[derive(Clone, Debug, Serialize)]
+pub struct TopologyStatus {
+    pub queue: String,
+    pub applied: bool,
+    pub error: Option<String>,
+}
+
+pub(super) fn now() -> u64 {
+    millis(SystemTime::now())
+}
+
+fn millis(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as u64)
+}
+
+pub(super) async fn connection(connection: &Connection) -> ConnectionDiagnostics {
+    let state = connection
+        .state_watcher()
+        .await
+        .ok()
+        .map(|watcher| watcher.borrow().clone());
+    let (state, endpoint) = match state {
+        None => ("stopped", None),
+        Some(ConnectionState::None) => ("none", None),
+        Some(ConnectionState::Ready { endpoint }) => ("ready", Some(endpoint.to_string())),
+        Some(ConnectionState::Error(_)) => ("error", None),
+        Some(ConnectionState::Closed) => ("closed", None),
+        Some(ConnectionState::TopologyFailed { .. }) => ("topology-failed", None),
+    };
+    let history = connection.state_history(usize::MAX);
+    let last_error = history.iter().rev().find_map(|e| match &e.event {
+        ConnectionEvent::Error(error) => Some((format!("{error}"), millis(e.at))),
+        _ => None,
+    });
+    let topology_applied_at = history
+        .iter()
+        .rev()
+        .find(|e| matches!(e.event, ConnectionEvent::TopologyApplied))
+        .map(|e| millis(e.at));
+    ConnectionDiagnostics {
+        name: connection.name().to_owned(),
+        state,
+        endpoint,
+        last_error_at: last_error.as_ref().map(|(_, at)| *at),
+        last_error: last_error.map(|(error, _)| error),
+        topology_applied_at,
+    }
+}
+
+pub(super) fn publisher(publisher: &PublisherProbe) -> PublisherDiagnostics {
+    PublisherDiagnostics {
+        connection: publisher.connection().to_owned(),
+        pending: publisher.pending(),
+        republished: publisher.republished(),
+        suppressed_duplicates: publisher.suppressed_duplicates(),
+    }
+}
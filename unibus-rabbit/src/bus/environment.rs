@@ -0,0 +1,68 @@
+use crate::rabbit::OutgoingMessage;
+
+use super::TopologyDefaults;
+
+/// Per-environment rewrite of the names the bus declares and publishes to, so several
+/// environments can share one broker or vhost, e.g. `staging.orders`.
+///
+/// Exchange and queue names get the prefix and the suffix; routing and binding keys
+/// get the prefix only, so topic wildcards keep matching: `#` becomes `staging.#`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnvironmentOverlay {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl EnvironmentOverlay {
+    /// Prefixes names and keys with `<environment>.`.
+    pub fn new(environment: &str) -> Self {
+        EnvironmentOverlay::default().with_prefix(format!("{environment}."))
+    }
+
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn with_suffix(mut self, suffix: impl Into<String>) -> Self {
+        self.suffix = suffix.into();
+        self
+    }
+
+    /// Exchange or queue `name` in this environment; the default exchange stays unnamed.
+    pub fn name(&self, name: &str) -> String {
+        if name.is_empty() {
+            return String::new();
+        }
+        format!("{}{name}{}", self.prefix, self.suffix)
+    }
+
+    /// Routing or binding `key` in this environment; empty keys stay empty.
+    pub fn routing_key(&self, key: &str) -> String {
+        if key.is_empty() {
+            return String::new();
+        }
+        format!("{}{key}", self.prefix)
+    }
+
+    /// `message` addressed in this environment; a message to a queue through the
+    /// default exchange gets the queue name of the environment as its routing key.
+    pub fn message(&self, mut message: OutgoingMessage) -> OutgoingMessage {
+        message.routing_key = if message.exchange.is_empty() {
+            self.name(&message.routing_key)
+        } else {
+            self.routing_key(&message.routing_key)
+        };
+        message.exchange = self.name(&message.exchange);
+        message
+    }
+
+    /// `defaults` with the dead letter exchange renamed into this environment.
+    pub fn defaults(&self, defaults: &TopologyDefaults) -> TopologyDefaults {
+        let mut defaults = defaults.clone();
+        defaults.dead_letter_exchange = defaults
+            .dead_letter_exchange
+            .map(|exchange| self.name(&exchange));
+        defaults
+    }
+}
@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::warn;
+
+use super::{Middleware, Next};
+use crate::rabbit::{
+    consumer::{Ack, Delivery, Nack},
+    OutgoingMessage, RabbitError,
+};
+
+/// Staging-only middleware failing and delaying a share of handler invocations and
+/// publishes at random, to see retries, dead letter queues and alerts at work before a
+/// real incident does. Does nothing unless `enabled`, so it can stay wired in and be
+/// switched on by configuration, see
+/// [`Bus::with_failure_injection`](super::Bus::with_failure_injection).
+///
+/// A failed handler invocation is nacked without requeue, a failed publish returns
+/// [`RabbitError::Injected`]; neither reaches the handler or the broker.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FailureInjection {
+    pub enabled: bool,
+    /// Share of invocations, from 0 to 1, that fail.
+    pub failure_rate: f64,
+    /// Share of invocations, from 0 to 1, held up by `delay` first.
+    pub delay_rate: f64,
+    pub delay: Duration,
+}
+
+impl FailureInjection {
+    /// Enabled, failing `failure_rate` of the invocations.
+    pub fn new(failure_rate: f64) -> Self {
+        FailureInjection {
+            enabled: true,
+            failure_rate,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_delay(mut self, delay_rate: f64, delay: Duration) -> Self {
+        self.delay_rate = delay_rate;
+        self.delay = delay;
+        self
+    }
+
+    fn hit(rate: f64) -> bool {
+        rate > 0.0 && rand::random::<f64>() < rate
+    }
+
+    /// Delays when drawn; true when the invocation is to fail.
+    async fn inject(&self) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if Self::hit(self.delay_rate) {
+            tokio::time::sleep(self.delay).await;
+        }
+        Self::hit(self.failure_rate)
+    }
+}
+
+#[async_trait]
+impl Middleware<OutgoingMessage> for FailureInjection {
+    async fn handle(
+        &self,
+        message: OutgoingMessage,
+        next: Next<OutgoingMessage>,
+    ) -> Result<(), RabbitError> {
+        if self.inject().await {
+            warn!(
+                exchange = message.exchange,
+                routing_key = message.routing_key,
+                "injected publish failure"
+            );
+            return Err(RabbitError::Injected);
+        }
+        next.run(message).await
+    }
+}
+
+#[async_trait]
+impl Middleware<Delivery> for FailureInjection {
+    async fn handle(&self, delivery: Delivery, next: Next<Delivery>) -> Result<Ack, Nack> {
+        if self.inject().await {
+            warn!(
+                exchange = delivery.exchange,
+                routing_key = delivery.routing_key,
+                "injected handler failure"
+            );
+            return Err(Nack { requeue: false });
+        }
+        next.run(delivery).await
+    }
+}
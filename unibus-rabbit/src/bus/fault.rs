@@ -0,0 +1,310 @@
+use std::collections::BTreeMap;
+
+use lapin::{
+    options::{BasicAckOptions, BasicGetOptions, BasicNackOptions},
+    types::FieldTable,
+    BasicProperties,
+};
+use tracing::warn;
+
+use crate::rabbit::{
+    consumer::{Ack, Death, Delivery, Nack},
+    headers, ChannelPurpose, Connection, OutgoingMessage, PublishGuarantee, Publisher, RabbitError,
+};
+
+pub const FAULT_REASON: &str = "x-fault-reason";
+pub const FAULT_EXCHANGE: &str = "x-fault-exchange";
+pub const FAULT_ROUTING_KEY: &str = "x-fault-routing-key";
+pub const FAULT_RETRIES: &str = "x-fault-retries";
+
+/// Why and where from a message ended up in an error queue.
+///
+/// Read from the `x-fault-*` headers, or from the broker `x-death` header
+/// for messages dead-lettered by RabbitMQ itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaultInfo {
+    pub reason: Option<String>,
+    pub exchange: String,
+    pub routing_key: String,
+    pub retries: u64,
+}
+
+impl FaultInfo {
+    pub fn from_headers(table: &FieldTable) -> Self {
+        let mut info = FaultInfo {
+            reason: headers::get_str(table, FAULT_REASON),
+            exchange: headers::get_str(table, FAULT_EXCHANGE).unwrap_or_default(),
+            routing_key: headers::get_str(table, FAULT_ROUTING_KEY).unwrap_or_default(),
+            retries: headers::get_u64(table, FAULT_RETRIES).unwrap_or_default(),
+        };
+        if let Some(death) = Death::from_headers(table).into_iter().next() {
+            if info.exchange.is_empty() {
+                info.exchange = death.exchange;
+            }
+            if info.routing_key.is_empty() {
+                info.routing_key = death.routing_keys.into_iter().next().unwrap_or_default();
+            }
+            info.reason = info.reason.or(Some(death.reason).filter(|r| !r.is_empty()));
+        }
+        info
+    }
+
+    pub fn write_headers(&self, table: &mut FieldTable) {
+        if let Some(reason) = &self.reason {
+            headers::set_str(table, FAULT_REASON, reason);
+        }
+        headers::set_str(table, FAULT_EXCHANGE, &self.exchange);
+        headers::set_str(table, FAULT_ROUTING_KEY, &self.routing_key);
+        headers::set_u64(table, FAULT_RETRIES, self.retries);
+    }
+}
+
+/// Headers of `properties` without the fault metadata of an earlier failure.
+fn original_headers(properties: &BasicProperties) -> FieldTable {
+    headers::headers(properties)
+        .inner()
+        .iter()
+        .filter(|(k, _)| !k.as_str().starts_with("x-fault-") && k.as_str() != "x-death")
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect::<BTreeMap<_, _>>()
+        .into()
+}
+
+/// Copy of `delivery` for the error queue `queue`, telling why and where from it failed.
+fn faulted_message(delivery: Delivery, queue: &str, reason: String) -> OutgoingMessage {
+    let retries = FaultInfo::from_headers(&headers::headers(&delivery.properties)).retries;
+    let fault = FaultInfo {
+        reason: Some(reason),
+        exchange: delivery.exchange,
+        routing_key: delivery.routing_key,
+        retries,
+    };
+    let mut table = original_headers(&delivery.properties);
+    fault.write_headers(&mut table);
+    OutgoingMessage::new("", queue, delivery.data)
+        .with_properties(delivery.properties.with_headers(table))
+        .with_mandatory(true)
+}
+
+/// Moves `delivery` into the error queue `queue` and acks it once the broker confirmed
+/// the copy; when that fails it is rejected instead, dead-lettered if its queue does.
+pub(crate) async fn move_to_error_queue(
+    publisher: &Publisher,
+    delivery: Delivery,
+    queue: &str,
+    reason: String,
+) -> Result<Ack, Nack> {
+    let message = faulted_message(delivery, queue, reason);
+    match publisher
+        .resend_with(message, PublishGuarantee::Confirmed)
+        .await
+    {
+        Ok(()) => Ok(Ack),
+        Err(e) => {
+            warn!(error = format!("{e}"), queue, "error queue publish failed");
+            Err(Nack { requeue: false })
+        }
+    }
+}
+
+/// Message of type `T` taken from its error queue together with the failure metadata.
+#[derive(Clone, Debug)]
+pub struct Faulted<T> {
+    pub message: T,
+    pub fault: FaultInfo,
+}
+
+/// Remediation actions for one faulted message.
+pub struct FaultContext {
+    publisher: Publisher,
+    delivery: Delivery,
+    fault: FaultInfo,
+}
+
+impl FaultContext {
+    pub(crate) fn new(publisher: Publisher, delivery: Delivery, fault: FaultInfo) -> Self {
+        FaultContext {
+            publisher,
+            delivery,
+            fault,
+        }
+    }
+
+    /// Republishes the original payload to where it failed from, counting the retry.
+    /// The faulted copy is acked once the republish is confirmed; it is requeued when
+    /// the republish fails or is unroutable, or when its origin is unknown.
+    pub async fn retry(self) -> Result<Ack, Nack> {
+        let Some(message) = self.retry_message() else {
+            warn!("fault retry without an origin, message requeued");
+            return Err(Nack { requeue: true });
+        };
+        match self
+            .publisher
+            .resend_with(message, PublishGuarantee::Confirmed)
+            .await
+        {
+            Ok(()) => Ok(Ack),
+            Err(e) => {
+                warn!(error = format!("{e}"), "fault retry failed");
+                Err(Nack { requeue: true })
+            }
+        }
+    }
+
+    fn retry_message(&self) -> Option<OutgoingMessage> {
+        if self.fault.exchange.is_empty() && self.fault.routing_key.is_empty() {
+            return None;
+        }
+        let mut table = original_headers(&self.delivery.properties);
+        headers::set_u64(&mut table, FAULT_RETRIES, self.fault.retries + 1);
+        let properties = self.delivery.properties.clone().with_headers(table);
+        let message = OutgoingMessage::new(
+            &self.fault.exchange,
+            &self.fault.routing_key,
+            self.delivery.data.clone(),
+        );
+        Some(message.with_properties(properties).with_mandatory(true))
+    }
+
+    /// Drops the faulted message for good.
+    pub fn discard(self) -> Result<Ack, Nack> {
+        Ok(Ack)
+    }
+}
+
+/// Moves up to `max` messages from the error queue `queue` back to where they failed
+/// from, as [`FaultContext::retry`] does; stops at the first failed republish, or the
+/// first message without an origin, which stays in the error queue. Returns how many
+/// were replayed.
+pub async fn replay_faults(
+    connection: &Connection,
+    publisher: &Publisher,
+    queue: &str,
+    max: usize,
+) -> Result<usize, RabbitError> {
+    let channel = connection.create_channel_for(ChannelPurpose::Admin).await?;
+    let mut replayed = 0;
+    while replayed < max {
+        let Some(message) = channel
+            .basic_get(queue, BasicGetOptions { no_ack: false })
+            .await?
+        else {
+            break;
+        };
+        let (delivery, acker) = Delivery::from_lapin(message.delivery);
+        let fault = FaultInfo::from_headers(&headers::headers(&delivery.properties));
+        let context = FaultContext::new(publisher.clone(), delivery, fault);
+        match context.retry().await {
+            Ok(Ack) => acker.ack(BasicAckOptions::default()).await?,
+            Err(_) => {
+                acker
+                    .nack(BasicNackOptions {
+                        requeue: true,
+                        ..Default::default()
+                    })
+                    .await?;
+                break;
+            }
+        }
+        replayed += 1;
+    }
+    _ = channel.close(0, "replay finished").await;
+    Ok(replayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use lapin::types::AMQPValue;
+
+    use super::*;
+    use crate::rabbit::Unreachable;
+
+    fn delivery(table: FieldTable) -> Delivery {
+        let mut delivery = Delivery::test(table, "{}");
+        delivery.exchange = "orders".into();
+        delivery.routing_key = "created".into();
+        delivery
+    }
+
+    fn earlier_failure() -> FieldTable {
+        let mut table = FieldTable::default();
+        headers::set_str(&mut table, "tenant", "a");
+        headers::set_str(&mut table, FAULT_REASON, "earlier");
+        headers::set_u64(&mut table, FAULT_RETRIES, 2);
+        table.insert("x-death".into(), AMQPValue::FieldArray(Default::default()));
+        table
+    }
+
+    #[test]
+    fn faulted_copies_tell_where_they_failed_from() {
+        let message = faulted_message(delivery(earlier_failure()), "svc.order.error", "bad".into());
+        assert_eq!(
+            (message.exchange.as_str(), message.routing_key.as_str()),
+            ("", "svc.order.error")
+        );
+        assert!(message.mandatory);
+        let table = headers::headers(&message.properties);
+        let expected = FaultInfo {
+            reason: Some("bad".into()),
+            exchange: "orders".into(),
+            routing_key: "created".into(),
+            retries: 2,
+        };
+        assert_eq!(FaultInfo::from_headers(&table), expected);
+        assert_eq!(headers::get_str(&table, "tenant").as_deref(), Some("a"));
+        assert!(!table.inner().contains_key("x-death"));
+    }
+
+    #[test]
+    fn retries_go_back_to_the_origin_mandatory() {
+        let delivery = delivery(earlier_failure());
+        let fault = FaultInfo::from_headers(&headers::headers(&delivery.properties));
+        let publisher = Publisher::new(Unreachable::default());
+        let context = FaultContext::new(
+            publisher,
+            delivery,
+            FaultInfo {
+                exchange: "orders".into(),
+                routing_key: "created".into(),
+                ..fault
+            },
+        );
+        let message = context.retry_message().unwrap();
+        assert_eq!(
+            (message.exchange.as_str(), message.routing_key.as_str()),
+            ("orders", "created")
+        );
+        assert!(message.mandatory);
+        let table = headers::headers(&message.properties);
+        assert_eq!(headers::get_u64(&table, FAULT_RETRIES), Some(3));
+        assert_eq!(headers::get_str(&table, FAULT_REASON), None);
+        assert!(!table.inner().contains_key("x-death"));
+    }
+
+    #[tokio::test]
+    async fn retries_without_an_origin_are_requeued() {
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone());
+        let context = FaultContext::new(
+            publisher,
+            delivery(FieldTable::default()),
+            FaultInfo::default(),
+        );
+        assert_eq!(context.retry().await, Err(Nack { requeue: true }));
+        assert_eq!(source.attempts(), 0);
+    }
+
+    #[tokio::test]
+    async fn failed_retries_are_requeued() {
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone());
+        let delivery = delivery(FieldTable::default());
+        let fault = FaultInfo {
+            exchange: "orders".into(),
+            ..Default::default()
+        };
+        let context = FaultContext::new(publisher, delivery, fault);
+        assert_eq!(context.retry().await, Err(Nack { requeue: true }));
+        assert_eq!(source.attempts(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+use futures::future::BoxFuture;
+
+use crate::rabbit::{
+    consumer::{Ack, Delivery, Nack},
+    topology::{Binding, Queue, Topology},
+};
+
+use super::{EnvironmentOverlay, TopologyDefaults};
+
+/// Consumer function with the queue and bindings it implies, usually generated by
+/// `#[unibus_rabbit::handler]` and started through [`BusHost::with_handler`](super::BusHost::with_handler).
+#[derive(Clone, Copy)]
+pub struct HandlerSpec {
+    pub name: &'static str,
+    pub queue: &'static str,
+    /// `(exchange, routing key)` pairs the queue is bound with.
+    pub bindings: &'static [(&'static str, &'static str)],
+    pub handler: fn(Delivery) -> BoxFuture<'static, Result<Ack, Nack>>,
+}
+
+impl HandlerSpec {
+    /// The durable queue and its bindings; the exchanges are expected to exist.
+    pub fn topology(&self) -> Vec<Box<dyn Topology>> {
+        self.topology_with(&TopologyDefaults::default(), &EnvironmentOverlay::default())
+    }
+
+    /// [`HandlerSpec::topology`] in `environment`, with `defaults` applied to the queue.
+    pub fn topology_with(
+        &self,
+        defaults: &TopologyDefaults,
+        environment: &EnvironmentOverlay,
+    ) -> Vec<Box<dyn Topology>> {
+        let defaults = environment.defaults(defaults);
+        let queue = environment.name(self.queue);
+        let mut topology = defaults.topology();
+        topology.push(Box::new(defaults.queue(Queue::new(&queue))));
+        for (exchange, key) in self.bindings {
+            topology.push(Box::new(Binding::new(
+                &queue,
+                environment.name(exchange),
+                environment.routing_key(key),
+            )));
+        }
+        topology
+    }
+}
@@ -0,0 +1,240 @@
+use std::sync::{Mutex, MutexGuard};
+
+use tracing::level_filters::LevelFilter;
+
+use crate::{
+    rabbit::{
+        consumer::{Consumer, ConsumerOptions, ConsumerProbe},
+        telemetry::{self, Telemetry},
+        Connection, ConnectionOptions, Publisher, PublisherProbe, RabbitClient, RabbitError,
+    },
+    shutdown::Shutdown,
+};
+
+use super::{
+    diagnostics::{self, Diagnostics, TopologyStatus},
+    Bus, EnvironmentOverlay, HandlerSpec, Identity, TopologyDefaults,
+};
+
+/// What the host created, for [`BusHost::diagnostics`].
+#[derive(Default)]
+struct Tracked {
+    connections: Vec<Connection>,
+    consumers: Vec<ConsumerProbe>,
+    publishers: Vec<PublisherProbe>,
+    topology: Vec<TopologyStatus>,
+}
+
+impl Tracked {
+    /// Forgets the consumers cancelled and the publishers dropped since.
+    fn prune(&mut self) {
+        self.consumers.retain(|probe| !probe.cancelled());
+        self.publishers.retain(PublisherProbe::alive);
+    }
+}
+
+/// Owns the rabbit client and the shutdown token every component created through it observes.
+pub struct BusHost {
+    client: RabbitClient,
+    shutdown: Shutdown,
+    handlers: Vec<HandlerSpec>,
+    defaults: TopologyDefaults,
+    environment: EnvironmentOverlay,
+    tracked: Mutex<Tracked>,
+}
+
+impl BusHost {
+    pub fn new(client: RabbitClient) -> Self {
+        BusHost {
+            client,
+            shutdown: Shutdown::new(),
+            handlers: Vec::new(),
+            defaults: TopologyDefaults::default(),
+            environment: EnvironmentOverlay::default(),
+            tracked: Default::default(),
+        }
+    }
+
+    /// Registers a handler, usually the `HandlerSpec` generated by `#[unibus_rabbit::handler]`.
+    pub fn with_handler(mut self, handler: HandlerSpec) -> Self {
+        self.handlers.push(handler);
+        self
+    }
+
+    /// Arguments inherited by the handler queues and the buses created through the host.
+    pub fn with_defaults(mut self, defaults: TopologyDefaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Environment of the handler queues and the buses created through the host.
+    pub fn with_environment(mut self, environment: EnvironmentOverlay) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Declares the queues and bindings of the registered handlers and starts them.
+    pub async fn start_handlers(
+        &self,
+        connection: &Connection,
+    ) -> Result<Vec<Consumer>, RabbitError> {
+        let mut consumers = Vec::with_capacity(self.handlers.len());
+        for spec in &self.handlers {
+            let queue = self.environment.name(spec.queue);
+            let declared = connection
+                .declare(&spec.topology_with(&self.defaults, &self.environment))
+                .await;
+            self.tracked().topology.push(TopologyStatus {
+                queue: queue.clone(),
+                applied: declared.is_ok(),
+                error: declared.as_ref().err().map(|e| format!("{e}")),
+            });
+            declared?;
+            let options = ConsumerOptions::new(queue).with_shutdown(self.shutdown.clone());
+            let consumer = Consumer::start(connection, options, spec.handler).await?;
+            self.tracked().consumers.push(consumer.probe());
+            consumers.push(consumer);
+        }
+        Ok(consumers)
+    }
+
+    /// What the host created and is still around.
+    fn tracked(&self) -> MutexGuard<'_, Tracked> {
+        let mut tracked = self.tracked.lock().unwrap();
+        tracked.prune();
+        tracked
+    }
+
+    pub fn shutdown_token(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    pub async fn connect(&self, options: ConnectionOptions) -> Result<Connection, RabbitError> {
+        let connection = self
+            .client
+            .connect(options.with_shutdown(self.shutdown.clone()))
+            .await?;
+        self.tracked().connections.push(connection.clone());
+        Ok(connection)
+    }
+
+    pub fn publisher(&self, connection: Connection) -> Publisher {
+        let publisher = Publisher::new(connection).with_shutdown(self.shutdown.clone());
+        self.tracked().publishers.push(publisher.probe());
+        publisher
+    }
+
+    /// Pauses the handler consumers of `queue`, see [`Consumer::pause`]; `false` when
+    /// the host started none.
+    pub async fn pause(&self, queue: &str) -> bool {
+        let probes = self.probes(queue);
+        for probe in &probes {
+            probe.pause().await;
+        }
+        !probes.is_empty()
+    }
+
+    pub fn resume(&self, queue: &str) -> bool {
+        let probes = self.probes(queue);
+        for probe in &probes {
+            probe.resume();
+        }
+        !probes.is_empty()
+    }
+
+    fn probes(&self, queue: &str) -> Vec<ConsumerProbe> {
+        let tracked = self.tracked();
+        tracked
+            .consumers
+            .iter()
+            .filter(|probe| probe.queue() == queue)
+            .cloned()
+            .collect()
+    }
+
+    /// Lets events and spans of the bus up to `level` through the
+    /// [`telemetry filter`](telemetry::filter) and traces the payload of a `sampling`
+    /// fraction of the published and consumed messages, from now on.
+    pub fn set_telemetry(&self, level: LevelFilter, sampling: f64) {
+        telemetry::set(Telemetry { level, sampling });
+    }
+
+    pub fn telemetry(&self) -> Telemetry {
+        telemetry::current()
+    }
+
+    /// Snapshot of the connections, handler consumers and publishers created through
+    /// the host and of the handler topology, serializable for an admin endpoint.
+    pub async fn diagnostics(&self) -> Diagnostics {
+        let (connections, consumers, publishers, topology) = {
+            let tracked = self.tracked();
+            (
+                tracked.connections.clone(),
+                tracked
+                    .consumers
+                    .iter()
+                    .map(ConsumerProbe::status)
+                    .collect(),
+                tracked
+                    .publishers
+                    .iter()
+                    .map(diagnostics::publisher)
+                    .collect(),
+                tracked.topology.clone(),
+            )
+        };
+        let mut snapshot = Diagnostics {
+            taken_at: diagnostics::now(),
+            connections: Vec::with_capacity(connections.len()),
+            consumers,
+            publishers,
+            topology,
+        };
+        for connection in &connections {
+            snapshot
+                .connections
+                .push(diagnostics::connection(connection).await);
+        }
+        snapshot
+    }
+
+    pub fn bus(&self, connection: Connection, identity: Identity) -> Bus {
+        Bus::new(connection, identity)
+            .with_shutdown(self.shutdown.clone())
+            .with_defaults(self.defaults.clone())
+            .with_environment(self.environment.clone())
+    }
+
+    /// Quiesces consumers, then publishers, then connections, and stops the rabbit system.
+    pub async fn shutdown(self) -> Result<(), RabbitError> {
+        self.shutdown.run().await;
+        self.client.close().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::rabbit;
+
+    #[tokio::test]
+    async fn diagnostics_forget_dropped_publishers() {
+        let host = BusHost::new(rabbit::start().await);
+        let options = ConnectionOptions::new("amqp://127.0.0.1:1/%2f", "host-test")
+            .unwrap()
+            .with_startup_jitter(Duration::from_secs(86_400));
+        let connection = host.connect(options).await.unwrap();
+
+        let publisher = host.publisher(connection.clone());
+        let kept = host.publisher(connection);
+        assert_eq!(host.diagnostics().await.publishers.len(), 2);
+
+        drop(publisher);
+        assert_eq!(host.diagnostics().await.publishers.len(), 1);
+        drop(kept);
+        assert!(host.diagnostics().await.publishers.is_empty());
+        host.shutdown().await.unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::rabbit::{
+    topology::{Binding, Exchange, Queue, Topology},
+    ReplyAddress,
+};
+
+/// Stable identity of one running bus instance.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Identity {
+    pub service: String,
+    pub instance: String,
+}
+
+impl Identity {
+    /// Identity with a random instance id, fixed for the lifetime of the process.
+    pub fn new(service: impl Into<String>) -> Self {
+        Identity {
+            service: service.into(),
+            instance: Uuid::new_v4().simple().to_string(),
+        }
+    }
+
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = instance.into();
+        self
+    }
+
+    /// Routing key addressing this instance on a control exchange.
+    pub fn address(&self) -> String {
+        self.to_string()
+    }
+
+    pub fn instance_queue(&self) -> String {
+        format!("{}.instance", self.address())
+    }
+
+    /// Auto-delete queue of this instance bound by [`Identity::address`] to `control_exchange`.
+    pub fn instance_topology(&self, control_exchange: &str) -> Vec<Box<dyn Topology>> {
+        let queue = self.instance_queue();
+        vec![
+            Box::new(Exchange::direct(control_exchange)),
+            Box::new(Queue::new(&queue).with_durable(false).with_auto_delete(true)),
+            Box::new(Binding::new(queue, control_exchange, self.address())),
+        ]
+    }
+
+    /// Reply address of this instance, carried in headers of long-running requests.
+    pub fn reply_address(&self, control_exchange: &str, fallback_queue: &str) -> ReplyAddress {
+        ReplyAddress {
+            exchange: control_exchange.to_owned(),
+            instance: self.address(),
+            fallback_queue: fallback_queue.to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.service, self.instance)
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use lapin::BasicProperties;
+pub use unibus_core::middleware::{Flow, Middleware, MiddlewareStack, Next};
+
+use crate::rabbit::{
+    consumer::{Ack, AckHandle, Delivery, DeliveryHandler, Nack, Settler, Validation},
+    OutgoingMessage, RabbitError,
+};
+
+impl Flow for OutgoingMessage {
+    type Output = Result<(), RabbitError>;
+}
+
+impl Flow for Delivery {
+    type Output = Result<Ack, Nack>;
+}
+
+/// Delivery handler behind a [`MiddlewareStack`]; the validation phase is the handler's.
+pub struct Intercepted<H> {
+    stack: MiddlewareStack<Delivery>,
+    handler: Arc<H>,
+}
+
+impl<H: DeliveryHandler> Intercepted<H> {
+    /// `handler` behind `stack`, to start a consumer of its own with.
+    pub fn new(stack: MiddlewareStack<Delivery>, handler: H) -> Self {
+        Intercepted {
+            stack,
+            handler: Arc::new(handler),
+        }
+    }
+}
+
+#[async_trait]
+impl<H: DeliveryHandler> DeliveryHandler for Intercepted<H> {
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        self.handler.validate(properties)
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        let handler = self.handler.clone();
+        self.stack
+            .run(delivery, move |delivery| {
+                let handler = handler.clone();
+                async move { handler.handle(delivery).await }
+            })
+            .await
+    }
+
+    /// The middlewares see how the handler settled the delivery as its result; the
+    /// delivery is settled by the result of the stack.
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        let settler = Arc::new(Settler::new(ack));
+        let handler = self.handler.clone();
+        let inner = settler.clone();
+        let outcome = self
+            .stack
+            .run(delivery, move |delivery| {
+                let handler = handler.clone();
+                let settler = inner.clone();
+                async move { settler.run(&*handler, delivery).await }
+            })
+            .await;
+        settler.finish(outcome).await
+    }
+}
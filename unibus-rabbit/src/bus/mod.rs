@@ -0,0 +1,367 @@
+#[cfg(feature = "admin")]
+mod admin;
+mod defaults;
+mod diagnostics;
+mod environment;
+mod failure;
+mod fault;
+mod handler;
+mod host;
+mod identity;
+mod middleware;
+mod naming;
+mod presence;
+mod subscription;
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use serde::de::DeserializeOwned;
+use tracing::error;
+
+#[cfg(feature = "admin")]
+pub use admin::AdminRouter;
+pub use defaults::TopologyDefaults;
+pub use diagnostics::{ConnectionDiagnostics, Diagnostics, PublisherDiagnostics, TopologyStatus};
+pub use environment::EnvironmentOverlay;
+pub use failure::FailureInjection;
+use fault::move_to_error_queue;
+pub use fault::*;
+pub use handler::HandlerSpec;
+pub use host::BusHost;
+pub use identity::Identity;
+pub use middleware::{Flow, Intercepted, Middleware, MiddlewareStack, Next};
+pub use naming::{message_type_name, DefaultNaming, NamingConvention};
+pub use presence::{Announcement, Peer, Presence, PresenceOptions, PRESENCE_EXCHANGE};
+pub use subscription::Subscription;
+pub use unibus_core::{BusMessage, Envelope, MESSAGE_VERSION};
+
+use crate::rabbit::{
+    consumer::{Ack, Consumer, ConsumerOptions, Delivery, DeliveryContext, Extensions, Nack},
+    headers,
+    topology::{Binding, Exchange, Queue},
+    Connection, OutgoingMessage, Publisher, RabbitError,
+};
+use crate::shutdown::Shutdown;
+
+/// Typed messaging on top of a rabbit connection, named after the service identity.
+pub struct Bus {
+    connection: Connection,
+    publisher: Publisher,
+    identity: Identity,
+    naming: Arc<dyn NamingConvention>,
+    shutdown: Option<Shutdown>,
+    extensions: Arc<Extensions>,
+    declared: Arc<Mutex<HashSet<String>>>,
+    defaults: TopologyDefaults,
+    environment: EnvironmentOverlay,
+    publish_middleware: MiddlewareStack<OutgoingMessage>,
+    consume_middleware: MiddlewareStack<Delivery>,
+}
+
+impl Bus {
+    pub fn new(connection: Connection, identity: Identity) -> Self {
+        Bus {
+            publisher: Publisher::new(connection.clone()),
+            connection,
+            identity,
+            naming: Arc::new(DefaultNaming),
+            shutdown: None,
+            extensions: Default::default(),
+            declared: Default::default(),
+            defaults: TopologyDefaults::default(),
+            environment: EnvironmentOverlay::default(),
+            publish_middleware: MiddlewareStack::new(),
+            consume_middleware: MiddlewareStack::new(),
+        }
+    }
+
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.publisher = self.publisher.with_shutdown(shutdown.clone());
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    fn consumer_options(&self, queue: String) -> ConsumerOptions {
+        let options = ConsumerOptions::new(queue);
+        match &self.shutdown {
+            Some(shutdown) => options.with_shutdown(shutdown.clone()),
+            None => options,
+        }
+    }
+
+    /// Dependencies handed to subscription handlers through [`DeliveryContext`].
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = Arc::new(extensions);
+        self
+    }
+
+    /// Arguments every exchange and queue declared by the bus inherits.
+    pub fn with_defaults(mut self, defaults: TopologyDefaults) -> Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Renames everything the bus declares, publishes to and binds into `environment`.
+    pub fn with_environment(mut self, environment: EnvironmentOverlay) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Runs `middleware` around every publish, after the ones added before.
+    pub fn with_publish_middleware(mut self, middleware: impl Middleware<OutgoingMessage>) -> Self {
+        self.publish_middleware = self.publish_middleware.with(middleware);
+        self
+    }
+
+    /// Runs `middleware` around the handlers of the subscriptions started afterwards.
+    pub fn with_consume_middleware(mut self, middleware: impl Middleware<Delivery>) -> Self {
+        self.consume_middleware = self.consume_middleware.with(middleware);
+        self
+    }
+
+    /// Injects failures and delays into publishes and the handlers of the subscriptions
+    /// started afterwards, when `injection` is enabled.
+    pub fn with_failure_injection(self, injection: FailureInjection) -> Self {
+        if !injection.enabled {
+            return self;
+        }
+        self.with_publish_middleware(injection.clone())
+            .with_consume_middleware(injection)
+    }
+
+    pub fn with_naming(mut self, naming: impl NamingConvention + 'static) -> Self {
+        self.naming = Arc::new(naming);
+        self
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    pub fn naming(&self) -> &dyn NamingConvention {
+        self.naming.as_ref()
+    }
+
+    pub fn environment(&self) -> &EnvironmentOverlay {
+        &self.environment
+    }
+
+    /// Starts announcing this instance on [`PRESENCE_EXCHANGE`] and tracking its peers.
+    pub async fn presence(&self, options: PresenceOptions) -> Result<Presence, RabbitError> {
+        let options = options.with_environment(self.environment.clone());
+        Presence::start(&self.connection, &self.identity, options).await
+    }
+
+    /// Publishes `message` to the topic exchange of `T` with its routing key, content
+    /// type, type and version headers, and waits for the broker confirm. The exchange
+    /// is declared on first use.
+    pub async fn publish<T: BusMessage>(&self, message: &T) -> Result<(), RabbitError> {
+        let message = self
+            .environment
+            .message(OutgoingMessage::from(message.to_envelope()?));
+        self.ensure_topic(&message.exchange).await?;
+        let publisher = self.publisher.clone();
+        self.publish_middleware
+            .run(message, move |message| {
+                let publisher = publisher.clone();
+                async move { publisher.publish(message).await }
+            })
+            .await
+    }
+
+    fn defaults(&self) -> TopologyDefaults {
+        self.environment.defaults(&self.defaults)
+    }
+
+    async fn ensure_topic(&self, exchange: &str) -> Result<(), RabbitError> {
+        if self.declared.lock().unwrap().contains(exchange) {
+            return Ok(());
+        }
+        self.connection
+            .declare(&[Box::new(
+                self.defaults().exchange(Exchange::topic(exchange)),
+            )])
+            .await?;
+        self.declared.lock().unwrap().insert(exchange.to_owned());
+        Ok(())
+    }
+
+    /// Consumes every `T` published with [`Bus::publish`] from the service queue of `T`,
+    /// bound to the exchange of `T` with `#`. See [`Bus::subscribe`] for narrower keys.
+    pub async fn subscribe_message<T, H, Fut>(
+        &self,
+        handler: H,
+    ) -> Result<Subscription, RabbitError>
+    where
+        T: BusMessage,
+        H: Fn(T, DeliveryContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Ack, Nack>> + Send,
+    {
+        let exchange = self.environment.name(T::EXCHANGE);
+        let queue = self
+            .environment
+            .name(&self.naming.queue(&self.identity.service, T::MESSAGE_TYPE));
+        let error_queue = self.error_queue::<T>();
+        self.subscribe_on(exchange, queue, error_queue, &["#"], handler)
+            .await
+    }
+
+    /// Consumes `T` from the service queue, bound to the topic exchange of `T` with `keys`.
+    /// The returned [`Subscription`] adjusts the bindings at runtime.
+    ///
+    /// Messages the handler rejects without requeue, and payloads that do not decode as
+    /// `T`, move to the error queue of `T`, see [`Bus::subscribe_faults`].
+    pub async fn subscribe<T, H, Fut>(
+        &self,
+        keys: &[&str],
+        handler: H,
+    ) -> Result<Subscription, RabbitError>
+    where
+        T: DeserializeOwned + Send + 'static,
+        H: Fn(T, DeliveryContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Ack, Nack>> + Send,
+    {
+        let message_type = message_type_name::<T>();
+        let exchange = self.environment.name(&self.naming.exchange(&message_type));
+        let queue = self
+            .environment
+            .name(&self.naming.queue(&self.identity.service, &message_type));
+        let error_queue = self.error_queue::<T>();
+        self.subscribe_on(exchange, queue, error_queue, keys, handler)
+            .await
+    }
+
+    /// Error queue of `T`, named after the Rust type so [`Bus::subscribe_faults`] finds
+    /// it from both [`Bus::subscribe`] and [`Bus::subscribe_message`].
+    fn error_queue<T: ?Sized>(&self) -> String {
+        self.environment.name(
+            &self
+                .naming
+                .error_queue(&self.identity.service, &message_type_name::<T>()),
+        )
+    }
+
+    async fn subscribe_on<T, H, Fut>(
+        &self,
+        exchange: String,
+        queue: String,
+        error_queue: String,
+        keys: &[&str],
+        handler: H,
+    ) -> Result<Subscription, RabbitError>
+    where
+        T: DeserializeOwned + Send + 'static,
+        H: Fn(T, DeliveryContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Ack, Nack>> + Send,
+    {
+        let keys: BTreeSet<String> = keys.iter().map(|k| k.to_string()).collect();
+        let defaults = self.defaults();
+        let mut topology = defaults.topology();
+        topology.push(Box::new(defaults.exchange(Exchange::topic(&exchange))));
+        topology.push(Box::new(defaults.queue(Queue::new(&queue))));
+        topology.push(Box::new(Queue::new(&error_queue)));
+        for key in &keys {
+            let key = self.environment.routing_key(key);
+            topology.push(Box::new(Binding::new(&queue, &exchange, key)));
+        }
+        self.connection.declare(&topology).await?;
+        let handler = Arc::new(handler);
+        let extensions = self.extensions.clone();
+        let publisher = self.publisher.clone();
+        let error_queue = Arc::new(error_queue);
+        let consumer = Consumer::start(
+            &self.connection,
+            self.consumer_options(queue.clone()),
+            Intercepted::new(
+                self.consume_middleware.clone(),
+                move |delivery: Delivery| {
+                    let handler = handler.clone();
+                    let extensions = extensions.clone();
+                    let publisher = publisher.clone();
+                    let error_queue = error_queue.clone();
+                    async move {
+                        let original = delivery.clone();
+                        let reason = match delivery.json() {
+                            Ok(message) => {
+                                let context = DeliveryContext::new(delivery, extensions);
+                                match handler(message, context).await {
+                                    Err(Nack { requeue: false }) => {
+                                        "rejected by the handler".into()
+                                    }
+                                    outcome => return outcome,
+                                }
+                            }
+                            Err(e) => {
+                                error!(error = format!("{e}"), "undecodable message");
+                                format!("undecodable: {e}")
+                            }
+                        };
+                        move_to_error_queue(&publisher, original, &error_queue, reason).await
+                    }
+                },
+            ),
+        )
+        .await?;
+        Ok(Subscription::new(
+            consumer,
+            self.connection.clone(),
+            queue,
+            exchange,
+            keys,
+            self.environment.clone(),
+        ))
+    }
+
+    /// Consumes the error queue of `T`, handing each message with its failure metadata
+    /// to `handler`, which settles it through [`FaultContext::retry`] or [`FaultContext::discard`].
+    /// Payloads that no longer decode as `T` are rejected.
+    pub async fn subscribe_faults<T, H, Fut>(&self, handler: H) -> Result<Consumer, RabbitError>
+    where
+        T: DeserializeOwned + Send + 'static,
+        H: Fn(Faulted<T>, FaultContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Ack, Nack>> + Send,
+    {
+        let queue = self.error_queue::<T>();
+        self.connection
+            .declare(&[Box::new(Queue::new(&queue))])
+            .await?;
+        let publisher = self.publisher.clone();
+        let handler = Arc::new(handler);
+        Consumer::start(
+            &self.connection,
+            self.consumer_options(queue),
+            Intercepted::new(
+                self.consume_middleware.clone(),
+                move |delivery: Delivery| {
+                    let publisher = publisher.clone();
+                    let handler = handler.clone();
+                    async move {
+                        let message = match delivery.json() {
+                            Ok(message) => message,
+                            Err(e) => {
+                                error!(error = format!("{e}"), "undecodable faulted message");
+                                return Err(Nack { requeue: false });
+                            }
+                        };
+                        let fault =
+                            FaultInfo::from_headers(&headers::headers(&delivery.properties));
+                        let faulted = Faulted {
+                            message,
+                            fault: fault.clone(),
+                        };
+                        handler(faulted, FaultContext::new(publisher, delivery, fault)).await
+                    }
+                },
+            ),
+        )
+        .await
+    }
+}
@@ -0,0 +1,57 @@
+use std::any::type_name;
+
+/// Derives broker object names for a message type, so every part of the bus
+/// (and every deployment) agrees on them.
+pub trait NamingConvention: Send + Sync {
+    fn exchange(&self, message_type: &str) -> String;
+    fn queue(&self, service: &str, message_type: &str) -> String;
+    fn error_queue(&self, service: &str, message_type: &str) -> String;
+    fn retry_queue(&self, service: &str, message_type: &str) -> String;
+
+    /// Exchange the queues of `service` dead-letter to, see
+    /// [`TopologyDefaults::with_dead_letter_exchange`](super::TopologyDefaults::with_dead_letter_exchange).
+    fn dead_letter_exchange(&self, service: &str) -> String {
+        format!("{service}.dead-letter")
+    }
+}
+
+/// `message-type` exchanges and `service.message-type` queues with `.error`/`.retry` suffixes.
+#[derive(Clone, Debug, Default)]
+pub struct DefaultNaming;
+
+impl NamingConvention for DefaultNaming {
+    fn exchange(&self, message_type: &str) -> String {
+        message_type.to_owned()
+    }
+
+    fn queue(&self, service: &str, message_type: &str) -> String {
+        format!("{service}.{message_type}")
+    }
+
+    fn error_queue(&self, service: &str, message_type: &str) -> String {
+        format!("{}.error", self.queue(service, message_type))
+    }
+
+    fn retry_queue(&self, service: &str, message_type: &str) -> String {
+        format!("{}.retry", self.queue(service, message_type))
+    }
+}
+
+/// Kebab-cased name of `T` without its module path, e.g. `OrderPlaced` -> `order-placed`.
+pub fn message_type_name<T: ?Sized>() -> String {
+    let name = type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    let name = name.rsplit("::").next().unwrap_or(name);
+    let mut kebab = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                kebab.push('-');
+            }
+            kebab.extend(c.to_lowercase());
+        } else {
+            kebab.push(c);
+        }
+    }
+    kebab
+}
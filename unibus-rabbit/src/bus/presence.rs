@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use lapin::types::AMQPValue;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::oneshot, task::JoinHandle};
+use tracing::{trace_span, warn, Instrument};
+
+use crate::rabbit::{
+    consumer::{Ack, Consumer, ConsumerOptions, Delivery},
+    topology::{Binding, Exchange, Queue, Topology},
+    Connection, OutgoingMessage, Publisher, RabbitError,
+};
+
+use super::{EnvironmentOverlay, Identity};
+
+/// Fanout exchange every instance announces itself on.
+pub const PRESENCE_EXCHANGE: &str = "unibus.presence";
+
+/// What an instance announces about itself.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Announcement {
+    pub service: String,
+    pub instance: String,
+    pub version: String,
+    pub capabilities: Vec<String>,
+    /// Sent once when the instance leaves, so peers forget it right away.
+    #[serde(default)]
+    pub leaving: bool,
+}
+
+/// Live peer as last announced.
+#[derive(Clone, Debug)]
+pub struct Peer {
+    pub announcement: Announcement,
+    pub last_seen: Instant,
+}
+
+/// Settings of the presence announcements of one instance.
+#[derive(Clone, Debug)]
+pub struct PresenceOptions {
+    pub version: String,
+    pub capabilities: Vec<String>,
+    pub interval: Duration,
+    pub environment: EnvironmentOverlay,
+}
+
+impl PresenceOptions {
+    pub fn new(version: impl Into<String>) -> Self {
+        PresenceOptions {
+            version: version.into(),
+            capabilities: Vec::new(),
+            interval: Duration::from_secs(10),
+            environment: EnvironmentOverlay::default(),
+        }
+    }
+
+    pub fn with_capability(mut self, capability: impl Into<String>) -> Self {
+        self.capabilities.push(capability.into());
+        self
+    }
+
+    /// Announcement interval; a peer silent for three intervals is considered gone.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Announces on the presence exchange of `environment`.
+    pub fn with_environment(mut self, environment: EnvironmentOverlay) -> Self {
+        self.environment = environment;
+        self
+    }
+}
+
+type Peers = Arc<Mutex<HashMap<String, Peer>>>;
+
+/// Periodic announcements of this instance on [`PRESENCE_EXCHANGE`] and the registry
+/// of the peers heard from, this instance included.
+pub struct Presence {
+    peers: Peers,
+    ttl: Duration,
+    listener: Consumer,
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Presence {
+    pub async fn start(
+        connection: &Connection,
+        identity: &Identity,
+        options: PresenceOptions,
+    ) -> Result<Self, RabbitError> {
+        let exchange = options.environment.name(PRESENCE_EXCHANGE);
+        let queue = options
+            .environment
+            .name(&format!("{}.presence", identity.address()));
+        let topology: Vec<Box<dyn Topology>> = vec![
+            Box::new(Exchange::fanout(&exchange)),
+            Box::new(
+                Queue::new(&queue)
+                    .with_durable(false)
+                    .with_auto_delete(true)
+                    .with_argument("x-expires", AMQPValue::LongInt(60_000)),
+            ),
+            Box::new(Binding::new(&queue, &exchange, "")),
+        ];
+        connection.declare(&topology).await?;
+
+        let peers: Peers = Default::default();
+        let seen = peers.clone();
+        let listener = Consumer::start(
+            connection,
+            ConsumerOptions::new(queue),
+            move |delivery: Delivery| {
+                if let Ok(announcement) = serde_json::from_slice::<Announcement>(&delivery.data) {
+                    let mut peers = seen.lock().unwrap();
+                    let key = announcement.instance.clone();
+                    if announcement.leaving {
+                        peers.remove(&key);
+                    } else {
+                        let last_seen = Instant::now();
+                        peers.insert(
+                            key,
+                            Peer {
+                                announcement,
+                                last_seen,
+                            },
+                        );
+                    }
+                }
+                async { Ok(Ack) }
+            },
+        )
+        .await?;
+
+        let announcement = Announcement {
+            service: identity.service.clone(),
+            instance: identity.address(),
+            version: options.version,
+            capabilities: options.capabilities,
+            leaving: false,
+        };
+        let (stop, stopped) = oneshot::channel();
+        let span = trace_span!("presence", instance = announcement.instance);
+        let task = tokio::spawn(
+            announce(
+                Publisher::new(connection.clone()),
+                exchange,
+                announcement,
+                options.interval,
+                stopped,
+            )
+            .instrument(span),
+        );
+        Ok(Presence {
+            peers,
+            ttl: options.interval * 3,
+            listener,
+            stop,
+            task,
+        })
+    }
+
+    /// Peers announced within the last three intervals.
+    pub fn peers(&self) -> Vec<Peer> {
+        let mut peers = self.peers.lock().unwrap();
+        peers.retain(|_, peer| peer.last_seen.elapsed() < self.ttl);
+        peers.values().cloned().collect()
+    }
+
+    /// Live instances of `service`, e.g. to size a scatter-gather.
+    pub fn instances_of(&self, service: &str) -> Vec<Peer> {
+        self.peers()
+            .into_iter()
+            .filter(|peer| peer.announcement.service == service)
+            .collect()
+    }
+
+    /// Live peers announcing `capability`.
+    pub fn with_capability(&self, capability: &str) -> Vec<Peer> {
+        self.peers()
+            .into_iter()
+            .filter(|peer| {
+                peer.announcement
+                    .capabilities
+                    .iter()
+                    .any(|c| c == capability)
+            })
+            .collect()
+    }
+
+    /// Announces leaving and stops listening.
+    pub async fn stop(self) -> Result<(), RabbitError> {
+        _ = self.stop.send(());
+        _ = self.task.await;
+        self.listener.cancel().await
+    }
+}
+
+async fn announce(
+    publisher: Publisher,
+    exchange: String,
+    mut announcement: Announcement,
+    interval: Duration,
+    mut stopped: oneshot::Receiver<()>,
+) {
+    loop {
+        publish(&publisher, &exchange, &announcement).await;
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = &mut stopped => break,
+        }
+    }
+    announcement.leaving = true;
+    publish(&publisher, &exchange, &announcement).await;
+}
+
+async fn publish(publisher: &Publisher, exchange: &str, announcement: &Announcement) {
+    let res = match OutgoingMessage::json(exchange, "", announcement) {
+        Ok(message) => publisher.publish(message).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = res {
+        warn!(error = format!("{e}"), "presence announcement failed");
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeSet;
+
+use tokio::sync::Mutex;
+
+use crate::rabbit::{
+    consumer::Consumer, topology::Binding, ChannelPurpose, Connection, RabbitError,
+};
+
+use super::EnvironmentOverlay;
+
+/// Running subscription of a queue to a topic exchange, with bindings adjustable at runtime.
+pub struct Subscription {
+    consumer: Consumer,
+    connection: Connection,
+    queue: String,
+    exchange: String,
+    keys: Mutex<BTreeSet<String>>,
+    environment: EnvironmentOverlay,
+}
+
+impl Subscription {
+    pub(super) fn new(
+        consumer: Consumer,
+        connection: Connection,
+        queue: String,
+        exchange: String,
+        keys: BTreeSet<String>,
+        environment: EnvironmentOverlay,
+    ) -> Self {
+        Subscription {
+            consumer,
+            connection,
+            queue,
+            exchange,
+            keys: Mutex::new(keys),
+            environment,
+        }
+    }
+
+    pub fn queue(&self) -> &str {
+        &self.queue
+    }
+
+    /// Routing keys the queue is currently bound with, before the environment overlay.
+    pub async fn bindings(&self) -> Vec<String> {
+        self.keys.lock().await.iter().cloned().collect()
+    }
+
+    /// Binds the queue with `key` as well; a no-op for a key already bound.
+    pub async fn add_binding(&self, key: &str) -> Result<(), RabbitError> {
+        let mut keys = self.keys.lock().await;
+        if keys.contains(key) {
+            return Ok(());
+        }
+        let binding = self.binding(key);
+        self.connection.declare(&[Box::new(binding)]).await?;
+        keys.insert(key.to_string());
+        Ok(())
+    }
+
+    /// Unbinds `key`; messages already in the queue are still delivered.
+    pub async fn remove_binding(&self, key: &str) -> Result<(), RabbitError> {
+        let mut keys = self.keys.lock().await;
+        if !keys.contains(key) {
+            return Ok(());
+        }
+        let channel = self.connection.create_channel_for(ChannelPurpose::Admin).await?;
+        self.binding(key).unbind(&channel).await?;
+        _ = channel.close(0, "binding removed").await;
+        keys.remove(key);
+        Ok(())
+    }
+
+    fn binding(&self, key: &str) -> Binding {
+        Binding::new(
+            &self.queue,
+            &self.exchange,
+            self.environment.routing_key(key),
+        )
+    }
+
+    pub async fn cancel(self) -> Result<(), RabbitError> {
+        self.consumer.cancel().await
+    }
+}
@@ -0,0 +1,10 @@
+pub mod bus;
+pub mod outbox;
+pub mod rabbit;
+
+pub use unibus_core::{contracts, memory, position, shutdown, transport};
+#[cfg(feature = "kafka")]
+pub use unibus_core::kafka;
+
+/// Turns an async consumer function into a [`bus::HandlerSpec`].
+pub use unibus_macros::handler;
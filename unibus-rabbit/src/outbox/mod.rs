@@ -0,0 +1,286 @@
+#[cfg(feature = "postgres")]
+mod postgres;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn, Instrument};
+
+#[cfg(feature = "postgres")]
+pub use self::postgres::PostgresOutbox;
+use crate::{
+    rabbit::{OutgoingMessage, Publisher, RabbitError},
+    shutdown::{Shutdown, Stage},
+};
+
+#[derive(Debug, Error)]
+pub enum OutboxError {
+    #[error("outbox serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+    #[error("outbox publish failed: {0}")]
+    Publish(#[from] RabbitError),
+    #[cfg(feature = "postgres")]
+    #[error("outbox database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Message written to the outbox and not yet confirmed by the broker.
+#[derive(Clone, Debug)]
+pub struct PendingMessage {
+    pub id: i64,
+    pub message: OutgoingMessage,
+}
+
+/// Storage the [`OutboxRelay`] reads from. Writing happens in the transaction of the
+/// caller with the method of the implementation, e.g. [`PostgresOutbox::enqueue`].
+#[async_trait]
+pub trait OutboxStore: Send + Sync + 'static {
+    /// Unsent and unparked messages by ascending id, at most `limit`. Ids follow the
+    /// writes, not the commits, so messages of concurrent transactions may come late.
+    async fn pending(&self, limit: usize) -> Result<Vec<PendingMessage>, OutboxError>;
+    async fn mark_sent(&self, ids: &[i64]) -> Result<(), OutboxError>;
+    /// Counts a failed attempt of `id` and parks it once it failed `max_attempts` times,
+    /// leaving it out of `pending`. Returns whether it is parked.
+    async fn mark_failed(&self, id: i64, max_attempts: u32) -> Result<bool, OutboxError>;
+}
+
+/// `message` with a message id, a new one when it has none, so consumers can drop the
+/// duplicates a relay sends when it fails between publishing and marking sent.
+/// Store implementations apply it when writing.
+pub fn with_message_id(message: OutgoingMessage) -> OutgoingMessage {
+    if message.properties.message_id().is_some() {
+        return message;
+    }
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let properties = message.properties.clone().with_message_id(id.into());
+    message.with_properties(properties)
+}
+
+/// Publishes the messages of an [`OutboxStore`] with confirms and marks them sent.
+///
+/// Delivery is at least once: a message confirmed but not yet marked when the relay
+/// stops is published again; every message carries a message id for deduplication.
+/// The relay itself publishes past the [deduplication window](Publisher::with_dedup)
+/// of its publisher, since a message it relays again did not make it the first time.
+pub struct OutboxRelay<S> {
+    store: S,
+    publisher: Publisher,
+    batch: usize,
+    interval: Duration,
+    max_attempts: u32,
+}
+
+impl<S: OutboxStore> OutboxRelay<S> {
+    pub fn new(store: S, publisher: Publisher) -> Self {
+        OutboxRelay {
+            store,
+            publisher,
+            batch: 100,
+            interval: Duration::from_secs(1),
+            max_attempts: 5,
+        }
+    }
+
+    /// Messages read and published at once.
+    pub fn with_batch(mut self, batch: usize) -> Self {
+        self.batch = batch.max(1);
+        self
+    }
+
+    /// Wait before polling again once the outbox is drained.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Failed attempts after which a message the broker keeps refusing, e.g. unroutable
+    /// or too large, is parked so the messages behind it move on. 5 by default.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Publishes one batch and returns the number of messages marked sent. Stops at
+    /// the first failed publish, so later messages do not overtake it, unless the
+    /// failed message is parked by it.
+    pub async fn relay_once(&self) -> Result<usize, OutboxError> {
+        let pending = self.store.pending(self.batch).await?;
+        let mut receipts = Vec::with_capacity(pending.len());
+        for PendingMessage { id, message } in pending {
+            match self.publisher.resend(message).await {
+                Ok(receipt) => receipts.push((id, receipt)),
+                Err(e) => {
+                    warn!(id, error = format!("{e}"), "outbox publish failed");
+                    if !self.park(id, &e).await? {
+                        break;
+                    }
+                }
+            }
+        }
+        let mut sent = Vec::with_capacity(receipts.len());
+        let mut receipts = receipts.into_iter();
+        for (id, receipt) in receipts.by_ref() {
+            match receipt.await {
+                Ok(()) => sent.push(id),
+                Err(e) => {
+                    warn!(id, error = format!("{e}"), "outbox publish not confirmed");
+                    if !self.park(id, &e).await? {
+                        break;
+                    }
+                }
+            }
+        }
+        // published after a failure, relayed again with the failed one
+        receipts.for_each(|(_, receipt)| receipt.detach());
+        if !sent.is_empty() {
+            self.store.mark_sent(&sent).await?;
+        }
+        Ok(sent.len())
+    }
+
+    /// Counts the failure of `id` when the message itself is refused, not the broker
+    /// unreachable, and returns whether it is parked now.
+    async fn park(&self, id: i64, error: &RabbitError) -> Result<bool, OutboxError> {
+        let refused = matches!(
+            error,
+            RabbitError::Nacked
+                | RabbitError::Unroutable { .. }
+                | RabbitError::UndeclaredExchange(_)
+                | RabbitError::PayloadTooLarge { .. }
+        );
+        if !refused {
+            return Ok(false);
+        }
+        let parked = self.store.mark_failed(id, self.max_attempts).await?;
+        if parked {
+            warn!(id, attempts = self.max_attempts, "outbox message parked");
+        }
+        Ok(parked)
+    }
+
+    /// Relays in the background until `shutdown` reaches [`Stage::Publishers`].
+    pub fn start(self, shutdown: Shutdown) -> JoinHandle<()> {
+        let span = tracing::trace_span!("outbox");
+        tokio::spawn(
+            async move {
+                loop {
+                    let wait = match self.relay_once().await {
+                        Ok(sent) if sent == self.batch => Duration::ZERO,
+                        Ok(sent) => {
+                            debug!(sent, "outbox drained");
+                            self.interval
+                        }
+                        Err(e) => {
+                            warn!(error = format!("{e}"), "outbox relay failed");
+                            self.interval
+                        }
+                    };
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = shutdown.wait(Stage::Publishers) => break,
+                    }
+                }
+            }
+            .instrument(span),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::rabbit::{SizeLimit, Unreachable};
+
+    #[derive(Default)]
+    struct Memory {
+        rows: Vec<PendingMessage>,
+        sent: Mutex<Vec<i64>>,
+        failures: Mutex<HashMap<i64, u32>>,
+        parked: Mutex<Vec<i64>>,
+    }
+
+    impl Memory {
+        fn with(messages: impl IntoIterator<Item = OutgoingMessage>) -> Arc<Self> {
+            let rows = (1..)
+                .zip(messages)
+                .map(|(id, message)| PendingMessage {
+                    id,
+                    message: with_message_id(message),
+                })
+                .collect();
+            Arc::new(Memory {
+                rows,
+                ..Default::default()
+            })
+        }
+    }
+
+    #[async_trait]
+    impl OutboxStore for Arc<Memory> {
+        async fn pending(&self, limit: usize) -> Result<Vec<PendingMessage>, OutboxError> {
+            let sent = self.sent.lock().unwrap();
+            let parked = self.parked.lock().unwrap();
+            let pending = self
+                .rows
+                .iter()
+                .filter(|row| !sent.contains(&row.id) && !parked.contains(&row.id));
+            Ok(pending.take(limit).cloned().collect())
+        }
+
+        async fn mark_sent(&self, ids: &[i64]) -> Result<(), OutboxError> {
+            self.sent.lock().unwrap().extend_from_slice(ids);
+            Ok(())
+        }
+
+        async fn mark_failed(&self, id: i64, max_attempts: u32) -> Result<bool, OutboxError> {
+            let mut failures = self.failures.lock().unwrap();
+            let attempts = failures.entry(id).or_default();
+            *attempts += 1;
+            if *attempts < max_attempts {
+                return Ok(false);
+            }
+            self.parked.lock().unwrap().push(id);
+            Ok(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn relays_again_past_the_dedup_window() {
+        let store = Memory::with([OutgoingMessage::new("orders", "a", "{}")]);
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone()).with_dedup(Duration::from_secs(60));
+        let relay = OutboxRelay::new(store.clone(), publisher);
+        assert_eq!(relay.relay_once().await.unwrap(), 0);
+        assert_eq!(relay.relay_once().await.unwrap(), 0);
+        assert_eq!(source.attempts(), 2);
+        assert!(store.sent.lock().unwrap().is_empty());
+        assert!(store.failures.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn parks_refused_messages_and_moves_on() {
+        let store = Memory::with([
+            OutgoingMessage::new("orders", "a", vec![0; 64]),
+            OutgoingMessage::new("orders", "b", "{}"),
+        ]);
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone()).with_size_limit(SizeLimit::reject_above(32));
+        let relay = OutboxRelay::new(store.clone(), publisher).with_max_attempts(2);
+        relay.relay_once().await.unwrap();
+        assert!(store.parked.lock().unwrap().is_empty());
+        assert_eq!(source.attempts(), 0);
+        relay.relay_once().await.unwrap();
+        assert_eq!(*store.parked.lock().unwrap(), [1]);
+        assert_eq!(source.attempts(), 1);
+        relay.relay_once().await.unwrap();
+        assert_eq!(source.attempts(), 2);
+        assert!(store.sent.lock().unwrap().is_empty());
+    }
+}
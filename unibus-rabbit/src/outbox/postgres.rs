@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use lapin::BasicProperties;
+use sqlx::{types::Json, PgExecutor, PgPool, Row};
+
+use super::{with_message_id, OutboxError, OutboxStore, PendingMessage};
+use crate::rabbit::OutgoingMessage;
+
+/// Outbox in a Postgres table, `unibus_outbox` by default.
+pub struct PostgresOutbox {
+    pool: PgPool,
+    table: String,
+}
+
+impl PostgresOutbox {
+    pub fn new(pool: PgPool) -> Self {
+        PostgresOutbox {
+            pool,
+            table: "unibus_outbox".to_owned(),
+        }
+    }
+
+    pub fn with_table(mut self, table: impl Into<String>) -> Self {
+        self.table = table.into();
+        self
+    }
+
+    /// Creates the table and its index of unsent messages if missing, and adds the
+    /// attempt columns to tables created before them.
+    pub async fn migrate(&self) -> Result<(), OutboxError> {
+        let table = &self.table;
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (
+                id BIGSERIAL PRIMARY KEY,
+                exchange TEXT NOT NULL,
+                routing_key TEXT NOT NULL,
+                payload BYTEA NOT NULL,
+                properties JSONB NOT NULL,
+                mandatory BOOLEAN NOT NULL,
+                created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                sent_at TIMESTAMPTZ,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                failed_at TIMESTAMPTZ
+            )"
+        ))
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(&format!(
+            "ALTER TABLE {table}
+                ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 0,
+                ADD COLUMN IF NOT EXISTS failed_at TIMESTAMPTZ"
+        ))
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS {table}_unsent ON {table} (id) WHERE sent_at IS NULL"
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Writes `message` with `executor`, usually the transaction of the business
+    /// change, so it is published exactly when that transaction commits.
+    pub async fn enqueue<'c>(
+        &self,
+        executor: impl PgExecutor<'c>,
+        message: OutgoingMessage,
+    ) -> Result<i64, OutboxError> {
+        let message = with_message_id(message);
+        let row = sqlx::query(&format!(
+            "INSERT INTO {} (exchange, routing_key, payload, properties, mandatory)
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+            self.table
+        ))
+        .bind(&message.exchange)
+        .bind(&message.routing_key)
+        .bind(&message.payload)
+        .bind(Json(&message.properties))
+        .bind(message.mandatory)
+        .fetch_one(executor)
+        .await?;
+        Ok(row.try_get("id")?)
+    }
+
+    /// Gives the parked messages their attempts back, e.g. after fixing the topology
+    /// they were refused for, and returns how many there were.
+    pub async fn unpark(&self) -> Result<u64, OutboxError> {
+        let result = sqlx::query(&format!(
+            "UPDATE {} SET attempts = 0, failed_at = NULL WHERE failed_at IS NOT NULL",
+            self.table
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Deletes messages sent more than `days` days ago.
+    pub async fn purge_sent(&self, days: u32) -> Result<u64, OutboxError> {
+        let result = sqlx::query(&format!(
+            "DELETE FROM {} WHERE sent_at < now() - make_interval(days => $1)",
+            self.table
+        ))
+        .bind(days as i32)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[async_trait]
+impl OutboxStore for PostgresOutbox {
+    async fn pending(&self, limit: usize) -> Result<Vec<PendingMessage>, OutboxError> {
+        let rows = sqlx::query(&format!(
+            "SELECT id, exchange, routing_key, payload, properties, mandatory
+             FROM {} WHERE sent_at IS NULL AND failed_at IS NULL ORDER BY id LIMIT $1",
+            self.table
+        ))
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| {
+                let Json(properties): Json<BasicProperties> = row.try_get("properties")?;
+                let message = OutgoingMessage::new(
+                    row.try_get::<String, _>("exchange")?,
+                    row.try_get::<String, _>("routing_key")?,
+                    row.try_get::<Vec<u8>, _>("payload")?,
+                )
+                .with_properties(properties)
+                .with_mandatory(row.try_get("mandatory")?);
+                Ok(PendingMessage {
+                    id: row.try_get("id")?,
+                    message,
+                })
+            })
+            .collect()
+    }
+
+    async fn mark_sent(&self, ids: &[i64]) -> Result<(), OutboxError> {
+        sqlx::query(&format!(
+            "UPDATE {} SET sent_at = now() WHERE id = ANY($1)",
+            self.table
+        ))
+        .bind(ids)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn mark_failed(&self, id: i64, max_attempts: u32) -> Result<bool, OutboxError> {
+        let row = sqlx::query(&format!(
+            "UPDATE {} SET attempts = attempts + 1,
+                failed_at = CASE WHEN attempts + 1 >= $2 THEN now() END
+             WHERE id = $1 RETURNING failed_at IS NOT NULL AS parked",
+            self.table
+        ))
+        .bind(id)
+        .bind(max_attempts as i32)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(row.try_get("parked")?)
+    }
+}
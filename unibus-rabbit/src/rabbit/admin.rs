@@ -0,0 +1,74 @@
+use lapin::options::{
+    BasicAckOptions, BasicGetOptions, BasicNackOptions, BasicPublishOptions, ConfirmSelectOptions,
+    QueuePurgeOptions,
+};
+use tracing::info;
+
+use super::{ChannelPurpose, Connection, PublishReceipt, RabbitError};
+
+impl Connection {
+    /// Drops all ready messages of `queue`; returns how many there were.
+    /// Messages delivered and not yet acknowledged stay.
+    pub async fn purge_queue(&self, queue: &str) -> Result<u32, RabbitError> {
+        let channel = self.create_channel_for(ChannelPurpose::Admin).await?;
+        let purged = channel
+            .queue_purge(queue, QueuePurgeOptions::default())
+            .await?;
+        _ = channel.close(0, "queue purged").await;
+        info!(queue, purged, "queue purged");
+        Ok(purged)
+    }
+
+    /// Moves up to `limit` messages from `src` to the queue `dst`, one at a time: each
+    /// is republished with its properties through the default exchange and removed from
+    /// `src` only once the broker confirmed it, so a failure never loses a message.
+    /// Returns how many were moved.
+    pub async fn move_messages(
+        &self,
+        src: &str,
+        dst: &str,
+        limit: usize,
+    ) -> Result<usize, RabbitError> {
+        let channel = self.create_channel_for(ChannelPurpose::Admin).await?;
+        channel
+            .confirm_select(ConfirmSelectOptions::default())
+            .await?;
+        let mut moved = 0;
+        while moved < limit {
+            let Some(message) = channel
+                .basic_get(src, BasicGetOptions { no_ack: false })
+                .await?
+            else {
+                break;
+            };
+            let delivery = message.delivery;
+            let tag = delivery.delivery_tag;
+            let confirm = channel
+                .basic_publish(
+                    "",
+                    dst,
+                    BasicPublishOptions {
+                        mandatory: true,
+                        ..Default::default()
+                    },
+                    &delivery.data,
+                    delivery.properties,
+                )
+                .await?;
+            let receipt = PublishReceipt::new(confirm, String::new(), dst.to_owned());
+            if let Err(e) = receipt.await {
+                let requeue = BasicNackOptions {
+                    requeue: true,
+                    ..Default::default()
+                };
+                _ = channel.basic_nack(tag, requeue).await;
+                return Err(e);
+            }
+            channel.basic_ack(tag, BasicAckOptions::default()).await?;
+            moved += 1;
+        }
+        _ = channel.close(0, "messages moved").await;
+        info!(src, dst, moved, "messages moved");
+        Ok(moved)
+    }
+}
@@ -0,0 +1,138 @@
+use lapin::{
+    types::{AMQPValue, FieldArray, FieldTable},
+    BasicProperties,
+};
+
+use super::{headers, OutgoingMessage};
+
+const BATCH_COUNT: &str = "x-batch-count";
+const BATCH_OFFSETS: &str = "x-batch-offsets";
+
+/// Several small logical messages packed into one AMQP message.
+///
+/// Payloads are concatenated; the `x-batch-count` and `x-batch-offsets` headers
+/// locate them. All items share the routing and the properties of the envelope.
+/// Consumers unpack it with [`Unbatched`](super::consumer::Unbatched).
+#[derive(Clone, Debug, Default)]
+pub struct WireBatch {
+    payload: Vec<u8>,
+    offsets: Vec<u64>,
+}
+
+impl WireBatch {
+    pub fn new() -> Self {
+        WireBatch::default()
+    }
+
+    pub fn push(&mut self, item: &[u8]) {
+        self.offsets.push(self.payload.len() as u64);
+        self.payload.extend_from_slice(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Bytes of payload packed so far, for flushing by size.
+    pub fn size(&self) -> usize {
+        self.payload.len()
+    }
+
+    pub fn into_message(
+        self,
+        exchange: impl Into<String>,
+        routing_key: impl Into<String>,
+        properties: BasicProperties,
+    ) -> OutgoingMessage {
+        let mut table = headers::headers(&properties);
+        headers::set_u64(&mut table, BATCH_COUNT, self.offsets.len() as u64);
+        let offsets: Vec<AMQPValue> = self
+            .offsets
+            .iter()
+            .map(|&o| AMQPValue::LongLongInt(o as i64))
+            .collect();
+        table.insert(
+            BATCH_OFFSETS.into(),
+            AMQPValue::FieldArray(FieldArray::from(offsets)),
+        );
+        OutgoingMessage::new(exchange, routing_key, self.payload)
+            .with_properties(properties.with_headers(table))
+    }
+}
+
+/// Items of a batch envelope, or `None` when the message is not a well-formed batch.
+pub fn unpack<'a>(headers: &FieldTable, payload: &'a [u8]) -> Option<Vec<&'a [u8]>> {
+    let count = headers::get_u64(headers, BATCH_COUNT)? as usize;
+    let AMQPValue::FieldArray(offsets) = headers.inner().get(BATCH_OFFSETS)? else {
+        return None;
+    };
+    let offsets = offsets
+        .as_slice()
+        .iter()
+        .map(|o| headers::as_u64(o).map(|o| o as usize))
+        .collect::<Option<Vec<_>>>()?;
+    if offsets.len() != count {
+        return None;
+    }
+    let ends = offsets.iter().skip(1).copied().chain([payload.len()]);
+    offsets
+        .iter()
+        .zip(ends)
+        .map(|(&start, end)| payload.get(start..end))
+        .collect()
+}
+
+pub(crate) fn strip_headers(headers: &mut FieldTable) {
+    let mut inner = headers.inner().clone();
+    inner.remove(BATCH_COUNT);
+    inner.remove(BATCH_OFFSETS);
+    *headers = FieldTable::from(inner);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packed(items: &[&[u8]]) -> OutgoingMessage {
+        let mut batch = WireBatch::new();
+        for item in items {
+            batch.push(item);
+        }
+        batch.into_message("orders", "order.eu", BasicProperties::default())
+    }
+
+    #[test]
+    fn unpacks_what_was_packed() {
+        let message = packed(&[b"first", b"", b"third"]);
+        let table = headers::headers(&message.properties);
+        let items = unpack(&table, &message.payload).unwrap();
+        assert_eq!(items, [&b"first"[..], b"", b"third"]);
+    }
+
+    #[test]
+    fn rejects_malformed_batches() {
+        let message = packed(&[b"first", b"second"]);
+        let table = headers::headers(&message.properties);
+        assert!(unpack(&FieldTable::default(), &message.payload).is_none());
+
+        let mut miscounted = table.clone();
+        headers::set_u64(&mut miscounted, BATCH_COUNT, 3);
+        assert!(unpack(&miscounted, &message.payload).is_none());
+
+        assert!(unpack(&table, &message.payload[..3]).is_none());
+    }
+
+    #[test]
+    fn strips_the_batch_headers() {
+        let message = packed(&[b"first"]);
+        let mut table = headers::headers(&message.properties);
+        table.insert("x-other".into(), AMQPValue::Boolean(true));
+        strip_headers(&mut table);
+        assert!(unpack(&table, &message.payload).is_none());
+        assert!(table.inner().contains_key("x-other"));
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::watch;
+use tracing::{info, trace_span, Instrument, Span, warn, error };
+use actix::prelude::*;
+
+use super::{AmqpEndpoint, ConnectionEvent, ConnectionState, ConnectionOptions, EventLog};
+use crate::{
+    rabbit::{
+        metrics,
+        topology::{ObjectRef, Topology},
+        RabbitError,
+    },
+    shutdown::{ShutdownGuard, Stage},
+};
+
+enum State {
+    None,
+    Ready(Arc<lapin::Connection>, AmqpEndpoint),
+    Error(lapin::Error),
+    Closed,
+    TopologyFailed(String, lapin::Error),
+}
+
+impl Into<ConnectionState> for &State {
+    fn into(self) -> ConnectionState {
+        match (self) {
+            State::None => ConnectionState::None,
+            State::Ready(_, endpoint) => ConnectionState::Ready {
+                endpoint: endpoint.clone(),
+            },
+            State::Error(e) => ConnectionState::Error(e.clone()),
+            State::Closed => ConnectionState::Closed,
+            State::TopologyFailed(item, e) => ConnectionState::TopologyFailed {
+                item: item.clone(),
+                error: e.clone(),
+            },
+        }
+    }
+}
+
+
+
+/// Closes `connection`, dropping it when the broker does not answer within `timeout`.
+async fn close_bounded(
+    connection: Arc<lapin::Connection>,
+    code: u16,
+    text: &str,
+    timeout: Duration,
+) {
+    match tokio::time::timeout(timeout, connection.close(code, text)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => warn!(error = format!("{e}"), "close failed"),
+        Err(_) => warn!(
+            timeout_ms = timeout.as_millis() as u64,
+            "close timed out, connection dropped"
+        ),
+    }
+}
+
+/// Failed connect attempt.
+enum ConnectFailure {
+    Connect(lapin::Error),
+    /// Declaring the described topology item failed.
+    Topology(String, lapin::Error),
+}
+
+impl From<lapin::Error> for ConnectFailure {
+    fn from(e: lapin::Error) -> Self {
+        ConnectFailure::Connect(e)
+    }
+}
+
+/// Declares `topology` on a fresh connection, closing it when a declaration fails.
+async fn declare_topology(
+    connection: &lapin::Connection,
+    topology: &[Arc<dyn Topology>],
+) -> Result<(), ConnectFailure> {
+    if topology.is_empty() {
+        return Ok(());
+    }
+    let channel = connection.create_channel().await?;
+    for (index, item) in topology.iter().enumerate() {
+        if let Err(e) = item.declare(&channel).await {
+            _ = connection.close(0, "topology declaration failed").await;
+            let item = ObjectRef::of(item.as_ref())
+                .map_or_else(|| format!("topology item {index}"), |object| object.to_string());
+            return Err(ConnectFailure::Topology(item, e));
+        }
+    }
+    _ = channel.close(0, "topology declared").await;
+    Ok(())
+}
+
+/// One period without a connection, from the first failure until connected again.
+struct Outage {
+    since: Instant,
+    cause: String,
+    attempts: u64,
+}
+
+pub struct ConnectionActor {
+    state: State,
+    attempt: u64,
+    /// Index of the endpoint in use or tried next.
+    endpoint: usize,
+    outage: Option<Outage>,
+    /// Connects in a row the topology failed on.
+    topology_failures: u32,
+    options: ConnectionOptions,
+    state_subject: watch::Sender<ConnectionState>,
+    events: Arc<EventLog>,
+    shutdown_guard: Option<ShutdownGuard>,
+}
+
+impl Drop for ConnectionActor {
+    fn drop(&mut self) {
+        let span = self.make_span();
+        let _e = span.enter();
+        info!("dropped");
+    }
+}
+
+impl ConnectionActor {
+    fn make_span(&self) -> Span {
+        trace_span!("rabbit", name = self.options.name, endpoint = %self.endpoint())
+    }
+
+    fn endpoint(&self) -> &AmqpEndpoint {
+        &self.options.endpoints.as_slice()[self.endpoint]
+    }
+
+    fn set_state(&mut self, state: State) {
+        let span = self.make_span();
+        let _e = span.enter();
+        //let _st = span.enter();
+
+        let old_state: ConnectionState = (&self.state).into();
+        if old_state != (&state).into() {
+            match &state {
+                State::None => {}
+                State::Error(e) => error!(error = format!("{e}"), "connection error"),
+                State::Ready(..) => warn!("connected"),
+                State::Closed => info!("closed"),
+                State::TopologyFailed(item, e) => error!(
+                    error = format!("{e}"),
+                    item,
+                    "topology failed, connection parked"
+                ),
+            };
+            match &state {
+                State::Error(e) => self.events.record(ConnectionEvent::Error(e.clone())),
+                State::Closed => self.events.record(ConnectionEvent::Closed),
+                State::TopologyFailed(item, e) => {
+                    self.events.record(ConnectionEvent::TopologyFailed {
+                        item: item.clone(),
+                        error: e.clone(),
+                    })
+                }
+                _ => {}
+            }
+            let new_state = (&state).into();
+            metrics::connection_state(&self.options.name, &new_state);
+            self.state_subject.send_replace(new_state);
+        }
+
+        self.state = state;
+    }
+
+    fn begin_outage(&mut self, cause: &lapin::Error) {
+        if self.outage.is_none() {
+            self.outage = Some(Outage {
+                since: Instant::now(),
+                cause: format!("{cause}"),
+                attempts: 0,
+            });
+        }
+    }
+
+    pub fn new(options: ConnectionOptions, events: Arc<EventLog>) -> Self {
+        let (tx, _) = watch::channel(ConnectionState::None);
+        let guard = options.shutdown.as_ref().map(|s| s.guard(Stage::Connections));
+        ConnectionActor {
+            state: State::None,
+            attempt: 0,
+            endpoint: 0,
+            outage: None,
+            topology_failures: 0,
+            options,
+            state_subject: tx,
+            events,
+            shutdown_guard: guard,
+        }
+    }
+}
+
+impl Actor for ConnectionActor {
+    type Context = Context<ConnectionActor>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(shutdown) = self.options.shutdown.clone() {
+            ctx.spawn(
+                async move { shutdown.wait(Stage::Connections).await }
+                    .into_actor(self)
+                    .map(|_, _, ctx| ctx.stop()),
+            );
+        }
+        ctx.notify_later(Connect, crate::rabbit::jitter(self.options.startup_jitter));
+    }
+
+    fn stopping(&mut self, _: &mut Self::Context) -> Running {
+        let state = std::mem::replace(&mut self.state, State::None);
+        // the close outlives the actor context, the shutdown stage waits for it
+        let guard = self.shutdown_guard.take();
+        if let State::Ready(c, _) = state {
+            let timeout = self.options.close_timeout;
+            tokio::spawn(
+                async move {
+                    close_bounded(c, 0, "connection closed", timeout).await;
+                    drop(guard);
+                }
+                .instrument(self.make_span()),
+            );
+        }
+        Running::Stop
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Connect;
+
+impl Handler<Connect> for ConnectionActor {
+    type Result = ResponseActFuture<Self, ()>;
+    fn handle(&mut self, msg: Connect, ctx: &mut Self::Context) -> Self::Result {
+        match &self.state {
+            State::Ready(..) | State::Closed | State::TopologyFailed(..) => {
+                Box::pin(async {}.into_actor(self).map(|_, _, _| ()))
+            }
+            _ => {
+                self.attempt += 1;
+                let endpoint = self.endpoint().clone();
+                let span = trace_span!(
+                    "connect",
+                    name = self.options.name,
+                    endpoint = %endpoint,
+                    attempt = self.attempt
+                );
+                if let Some(outage) = &mut self.outage {
+                    outage.attempts += 1;
+                    let _e = span.enter();
+                    info!(
+                        cause = outage.cause,
+                        downtime_ms = outage.since.elapsed().as_millis() as u64,
+                        "reconnecting"
+                    );
+                    self.events.record(ConnectionEvent::Reconnecting {
+                        attempt: outage.attempts,
+                    });
+                }
+                self.events.record(ConnectionEvent::Connecting {
+                    endpoint: endpoint.clone(),
+                });
+                let uri = endpoint.amqp_uri();
+                let props = (&self.options).into();
+                let topology = self.options.topology.clone();
+                let (events, connected) = (self.events.clone(), endpoint.clone());
+                Box::pin(
+                    async move {
+                        let c = lapin::Connection::connect_uri(uri, props).await?;
+                        events.record(ConnectionEvent::Connected { endpoint: connected });
+                        declare_topology(&c, &topology).await?;
+                        events.record(ConnectionEvent::TopologyApplied);
+                        Ok(c)
+                    }
+                        .instrument(span.clone())
+                        .into_actor(self)
+                        .map(move |res, act, ctx| {
+                            let _e = span.enter();
+                            match res {
+                                Ok(c) => {
+                                    act.topology_failures = 0;
+                                    let this = ctx.address();
+                                    c.on_error(move |e| {
+                                        this.do_send(Disconnected(e));
+                                    });
+                                    if let Some(outage) = act.outage.take() {
+                                        metrics::reconnected(&act.options.name);
+                                        info!(
+                                            cause = outage.cause,
+                                            attempts = outage.attempts,
+                                            downtime_ms = outage.since.elapsed().as_millis() as u64,
+                                            "reconnected"
+                                        );
+                                    }
+                                    act.set_state(State::Ready(Arc::new(c), endpoint));
+                                }
+                                Err(failure) => {
+                                    let e = match failure {
+                                        ConnectFailure::Connect(e) => e,
+                                        ConnectFailure::Topology(item, e) => {
+                                            act.topology_failures += 1;
+                                            let limit = act.options.topology_attempts;
+                                            if limit.is_some_and(|n| act.topology_failures >= n) {
+                                                act.set_state(State::TopologyFailed(item, e));
+                                                return;
+                                            }
+                                            warn!(item, "topology declaration failed");
+                                            e
+                                        }
+                                    };
+                                    act.begin_outage(&e);
+                                    let wait = act.options.reconnect;
+                                    warn!(
+                                        error = format!("{e}"),
+                                        backoff_ms = wait.as_millis() as u64,
+                                        "connect attempt failed"
+                                    );
+                                    act.set_state(State::Error(e));
+                                    act.endpoint = act.options.failover.next(
+                                        act.endpoint,
+                                        act.options.endpoints.len(),
+                                    );
+                                    let this = ctx.address();
+                                    tokio::spawn(async move {
+                                        tokio::time::sleep(wait).await;
+                                        this.do_send(Connect);
+                                    });
+                                }
+                            };
+                        }),
+                )
+            }
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct Disconnected(lapin::Error);
+
+impl Handler<Disconnected> for ConnectionActor {
+    type Result = ();
+    fn handle(&mut self, msg: Disconnected, ctx: &mut Self::Context) -> Self::Result {
+        if let State::Closed = self.state {
+            return;
+        }
+        self.begin_outage(&msg.0);
+        self.set_state(State::Error(msg.0));
+        ctx.address().do_send(Connect);
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "watch::Receiver<ConnectionState>")]
+pub struct GetStateWatch;
+
+impl Handler<GetStateWatch> for ConnectionActor {
+    type Result = MessageResult<GetStateWatch>;
+    fn handle(&mut self, _: GetStateWatch, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.state_subject.subscribe())
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Result<lapin::Channel, RabbitError>")]
+pub struct CreateChannel;
+
+impl Handler<CreateChannel> for ConnectionActor {
+    type Result = ResponseFuture<Result<lapin::Channel, RabbitError>>;
+    fn handle(&mut self, _: CreateChannel, _: &mut Self::Context) -> Self::Result {
+        match &self.state {
+            State::Ready(c, _) => {
+                let c = c.clone();
+                Box::pin(async move { Ok(c.create_channel().await?) })
+            }
+            _ => Box::pin(async { Err(RabbitError::NotConnected) }),
+        }
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CloseConnection {
+    pub code: u16,
+    pub text: String,
+    pub timeout: Duration,
+}
+
+impl Handler<CloseConnection> for ConnectionActor {
+    type Result = ResponseActFuture<Self, ()>;
+    fn handle(&mut self, msg: CloseConnection, _: &mut Self::Context) -> Self::Result {
+        let state = std::mem::replace(&mut self.state, State::None);
+        self.set_state(State::Closed);
+        let span = self.make_span();
+        Box::pin(
+            async move {
+                if let State::Ready(c, _) = state {
+                    close_bounded(c, msg.code, &msg.text, msg.timeout).await;
+                }
+            }
+            .instrument(span)
+            .into_actor(self)
+            .map(|_, _, ctx| ctx.stop()),
+        )
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{ChannelPurpose, PurposeGauge};
+use crate::rabbit::RabbitError;
+
+/// What `create_channel` does once the channel budget of a connection is spent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetPolicy {
+    /// Wait until another channel of the connection is dropped.
+    Wait,
+    /// Fail with [`RabbitError::ChannelBudgetExceeded`].
+    Fail,
+}
+
+pub(crate) struct ChannelBudget {
+    open: AtomicUsize,
+    limit: Option<(usize, Arc<Semaphore>)>,
+    policy: BudgetPolicy,
+}
+
+impl ChannelBudget {
+    pub(crate) fn new(limit: Option<usize>, policy: BudgetPolicy) -> Arc<Self> {
+        Arc::new(ChannelBudget {
+            open: AtomicUsize::new(0),
+            limit: limit.map(|l| (l, Arc::new(Semaphore::new(l)))),
+            policy,
+        })
+    }
+
+    pub(crate) fn open(&self) -> usize {
+        self.open.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn exhausted(&self) -> bool {
+        matches!(&self.limit, Some((_, s)) if s.available_permits() == 0)
+    }
+
+    pub(crate) async fn acquire(self: &Arc<Self>) -> Result<ChannelLease, RabbitError> {
+        let permit = match &self.limit {
+            None => None,
+            Some((limit, semaphore)) => Some(match self.policy {
+                BudgetPolicy::Wait => semaphore
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("channel budget semaphore is never closed"),
+                BudgetPolicy::Fail => semaphore
+                    .clone()
+                    .try_acquire_owned()
+                    .map_err(|_| RabbitError::ChannelBudgetExceeded(*limit))?,
+            }),
+        };
+        self.open.fetch_add(1, Ordering::Relaxed);
+        Ok(ChannelLease {
+            budget: self.clone(),
+            _permit: permit,
+        })
+    }
+}
+
+pub(crate) struct ChannelLease {
+    budget: Arc<ChannelBudget>,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Drop for ChannelLease {
+    fn drop(&mut self) {
+        self.budget.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// AMQP channel counted against the budget of its connection until the last clone is dropped.
+#[derive(Clone)]
+pub struct Channel {
+    inner: lapin::Channel,
+    lease: Arc<ChannelLease>,
+    purpose: Option<Arc<PurposeGauge>>,
+}
+
+impl Channel {
+    pub(crate) fn new(inner: lapin::Channel, lease: ChannelLease) -> Self {
+        Channel {
+            inner,
+            lease: Arc::new(lease),
+            purpose: None,
+        }
+    }
+
+    pub(crate) fn with_purpose(mut self, connection: String, purpose: ChannelPurpose) -> Self {
+        self.purpose = Some(Arc::new(PurposeGauge::new(connection, purpose)));
+        self
+    }
+
+    /// Purpose the channel was opened for, `None` for plain `create_channel`.
+    pub fn purpose(&self) -> Option<ChannelPurpose> {
+        self.purpose.as_ref().map(|p| p.purpose())
+    }
+
+    /// Whether the channel is counted against `budget`, i.e. was opened on its connection.
+    pub(crate) fn counted_by(&self, budget: &Arc<ChannelBudget>) -> bool {
+        Arc::ptr_eq(&self.lease.budget, budget)
+    }
+}
+
+impl Deref for Channel {
+    type Target = lapin::Channel;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
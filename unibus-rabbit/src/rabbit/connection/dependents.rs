@@ -0,0 +1,42 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Users of a connection that a close would break, e.g. consumers of exclusive queues.
+#[derive(Debug, Default)]
+pub(crate) struct Dependents {
+    next: AtomicU64,
+    entries: Mutex<BTreeMap<u64, String>>,
+}
+
+impl Dependents {
+    pub fn add(self: &Arc<Self>, who: String) -> DependentGuard {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        self.entries.lock().unwrap().insert(id, who);
+        DependentGuard {
+            dependents: self.clone(),
+            id,
+        }
+    }
+
+    pub fn list(&self) -> Vec<String> {
+        self.entries.lock().unwrap().values().cloned().collect()
+    }
+}
+
+/// Registration of a connection dependent, removed on drop.
+#[derive(Debug)]
+pub struct DependentGuard {
+    dependents: Arc<Dependents>,
+    id: u64,
+}
+
+impl Drop for DependentGuard {
+    fn drop(&mut self) {
+        self.dependents.entries.lock().unwrap().remove(&self.id);
+    }
+}
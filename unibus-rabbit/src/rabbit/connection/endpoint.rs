@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use lapin::uri::{AMQPScheme, AMQPUri};
+
+use crate::rabbit::RabbitError;
+
+/// Parsed AMQP URI. Displays and debugs without the password, so it is safe to log.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AmqpEndpoint {
+    uri: AMQPUri,
+    query: BTreeMap<String, String>,
+}
+
+impl AmqpEndpoint {
+    pub fn parse(uri: &str) -> Result<Self, RabbitError> {
+        let parsed = uri.parse::<AMQPUri>().map_err(RabbitError::InvalidUri)?;
+        let query = uri
+            .split_once('?')
+            .map(|(_, query)| {
+                query
+                    .split('&')
+                    .filter(|pair| !pair.is_empty())
+                    .map(|pair| match pair.split_once('=') {
+                        Some((k, v)) => (k.to_owned(), v.to_owned()),
+                        None => (pair.to_owned(), String::new()),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(AmqpEndpoint { uri: parsed, query })
+    }
+
+    pub fn host(&self) -> &str {
+        &self.uri.authority.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.uri.authority.port
+    }
+
+    pub fn vhost(&self) -> &str {
+        &self.uri.vhost
+    }
+
+    pub fn user(&self) -> &str {
+        &self.uri.authority.userinfo.username
+    }
+
+    pub fn tls(&self) -> bool {
+        self.uri.scheme == AMQPScheme::AMQPS
+    }
+
+    /// Raw query parameters, e.g. `heartbeat` or `connection_timeout`.
+    pub fn query(&self) -> &BTreeMap<String, String> {
+        &self.query
+    }
+
+    /// The full URI, credentials included, for connecting.
+    pub(crate) fn amqp_uri(&self) -> AMQPUri {
+        self.uri.clone()
+    }
+}
+
+impl FromStr for AmqpEndpoint {
+    type Err = RabbitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for AmqpEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls() { "amqps" } else { "amqp" };
+        write!(
+            f,
+            "{scheme}://{}@{}:{}/{}",
+            self.user(),
+            self.host(),
+            self.port(),
+            self.vhost().replace('/', "%2f")
+        )?;
+        let mut sep = '?';
+        for (k, v) in &self.query {
+            write!(f, "{sep}{k}={v}")?;
+            sep = '&';
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for AmqpEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AmqpEndpoint({self})")
+    }
+}
+
+/// Brokers of one logical connection, tried in turn on failure; parses from a comma
+/// separated list of URIs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoints(Vec<AmqpEndpoint>);
+
+impl Endpoints {
+    pub fn as_slice(&self) -> &[AmqpEndpoint] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Always false, there is at least one endpoint.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<AmqpEndpoint> for Endpoints {
+    fn from(endpoint: AmqpEndpoint) -> Self {
+        Endpoints(vec![endpoint])
+    }
+}
+
+impl TryFrom<Vec<AmqpEndpoint>> for Endpoints {
+    type Error = RabbitError;
+
+    /// Fails on an empty list.
+    fn try_from(endpoints: Vec<AmqpEndpoint>) -> Result<Self, Self::Error> {
+        if endpoints.is_empty() {
+            return Err(RabbitError::InvalidUri("no broker endpoint given".to_owned()));
+        }
+        Ok(Endpoints(endpoints))
+    }
+}
+
+impl TryFrom<&str> for Endpoints {
+    type Error = RabbitError;
+
+    fn try_from(uris: &str) -> Result<Self, Self::Error> {
+        uris.parse()
+    }
+}
+
+impl FromStr for Endpoints {
+    type Err = RabbitError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|uri| !uri.is_empty())
+            .map(AmqpEndpoint::parse)
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+    }
+}
+
+impl fmt::Display for Endpoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, endpoint) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{endpoint}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Order endpoints are tried in after a failed connect. Either way the connection sticks
+/// to an endpoint while it works, and after losing it tries the same endpoint first.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Failover {
+    /// The next endpoint of the list, wrapping around.
+    #[default]
+    RoundRobin,
+    /// Another endpoint picked at random.
+    Random,
+}
+
+impl Failover {
+    /// Index of the endpoint to try after `current` failed, out of `len`.
+    pub(crate) fn next(self, current: usize, len: usize) -> usize {
+        match self {
+            _ if len <= 1 => 0,
+            Failover::RoundRobin => (current + 1) % len,
+            Failover::Random => (current + 1 + rand::random::<usize>() % (len - 1)) % len,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use tokio::sync::broadcast;
+
+use super::AmqpEndpoint;
+
+/// Step in the life of a connection, see [`Connection::events`](super::Connection::events).
+#[derive(Clone, Debug)]
+pub enum ConnectionEvent {
+    /// A connect attempt to `endpoint` started.
+    Connecting {
+        endpoint: AmqpEndpoint,
+    },
+    /// The broker accepted the connection; the topology is declared next.
+    Connected {
+        endpoint: AmqpEndpoint,
+    },
+    /// The topology of the options is declared, the connection turns ready.
+    TopologyApplied,
+    Error(lapin::Error),
+    /// Attempt `attempt` to restore a lost connection, counted per outage.
+    Reconnecting {
+        attempt: u64,
+    },
+    /// Closed on request.
+    Closed,
+    /// Parked after the topology failed too often, see
+    /// [`ConnectionState::TopologyFailed`](super::ConnectionState::TopologyFailed).
+    TopologyFailed {
+        item: String,
+        error: lapin::Error,
+    },
+}
+
+/// [`ConnectionEvent`] with the time it happened.
+#[derive(Clone, Debug)]
+pub struct TimedEvent {
+    pub at: SystemTime,
+    pub event: ConnectionEvent,
+}
+
+/// Last events of a connection, shared by its actor and handles.
+pub(crate) struct EventLog {
+    history: Mutex<VecDeque<TimedEvent>>,
+    capacity: usize,
+    sender: broadcast::Sender<TimedEvent>,
+}
+
+impl EventLog {
+    pub(crate) fn new(capacity: usize) -> Arc<Self> {
+        let capacity = capacity.max(1);
+        Arc::new(EventLog {
+            history: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            sender: broadcast::channel(capacity).0,
+        })
+    }
+
+    pub(crate) fn record(&self, event: ConnectionEvent) {
+        let event = TimedEvent {
+            at: SystemTime::now(),
+            event,
+        };
+        let mut history = self.history.lock().unwrap();
+        if history.len() == self.capacity {
+            history.pop_front();
+        }
+        history.push_back(event.clone());
+        // no subscribers is fine
+        _ = self.sender.send(event);
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<TimedEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Up to `n` most recent events, oldest first.
+    pub(crate) fn last(&self, n: usize) -> Vec<TimedEvent> {
+        let history = self.history.lock().unwrap();
+        history
+            .iter()
+            .skip(history.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
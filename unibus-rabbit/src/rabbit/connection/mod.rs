@@ -0,0 +1,295 @@
+mod actor;
+mod budget;
+mod dependents;
+mod endpoint;
+mod history;
+mod options;
+mod pool;
+mod purpose;
+mod stability;
+mod state;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use actix::{Addr, MailboxError};
+pub(super) use actor::{CloseConnection, ConnectionActor, CreateChannel, GetStateWatch};
+pub(crate) use budget::ChannelBudget;
+pub use budget::{BudgetPolicy, Channel};
+use dependents::Dependents;
+pub use dependents::DependentGuard;
+pub use endpoint::{AmqpEndpoint, Endpoints, Failover};
+pub(crate) use history::EventLog;
+pub use history::{ConnectionEvent, TimedEvent};
+pub use options::*;
+#[cfg(test)]
+pub(crate) use pool::Unreachable;
+pub use pool::{ChannelSource, ConnectionPool};
+pub(crate) use purpose::PurposeGauge;
+pub use purpose::{ChannelPreset, ChannelPurpose};
+pub use stability::StabilityFilter;
+pub use state::*;
+use tokio::sync::{broadcast, watch};
+use tracing::warn;
+
+use super::{
+    topology::{warn_audit, Definitions, Topology, TopologyRegistry},
+    RabbitError,
+};
+use crate::shutdown::{Shutdown, Stage};
+
+
+#[derive(Clone)]
+pub struct Connection {
+    addr: Addr<ConnectionActor>,
+    name: String,
+    budget: Arc<ChannelBudget>,
+    registry: Arc<RwLock<TopologyRegistry>>,
+    strict: bool,
+    close_timeout: Duration,
+    dependents: Arc<Dependents>,
+    shutdown: Shutdown,
+    events: Arc<EventLog>,
+    prefetch: BTreeMap<ChannelPurpose, u16>,
+}
+
+impl Connection {
+    pub(super) fn new(
+        addr: Addr<ConnectionActor>,
+        events: Arc<EventLog>,
+        options: &ConnectionOptions,
+    ) -> Self {
+        let mut registry = TopologyRegistry::default();
+        let mut definitions = Definitions::default();
+        for item in &options.topology {
+            item.register(&mut registry);
+            item.define(&mut definitions);
+        }
+        warn_audit(&definitions);
+        Connection {
+            addr,
+            name: options.name.clone(),
+            budget: ChannelBudget::new(options.channel_budget, options.budget_policy),
+            registry: Arc::new(RwLock::new(registry)),
+            strict: options.strict,
+            close_timeout: options.close_timeout,
+            dependents: Default::default(),
+            shutdown: options.shutdown.clone().unwrap_or_default(),
+            events,
+            prefetch: options.channel_prefetch.clone(),
+        }
+    }
+
+    /// Name from the options, e.g. for logs and metrics.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub async fn state_watcher(&self) -> Result<watch::Receiver<ConnectionState>, MailboxError> {
+        self.addr.send(GetStateWatch).await
+    }
+
+    /// Events from now on; a receiver lagging more than the history size of the
+    /// options misses the oldest ones.
+    pub fn events(&self) -> broadcast::Receiver<TimedEvent> {
+        self.events.subscribe()
+    }
+
+    /// Up to `n` most recent events, oldest first, bounded by the history size.
+    pub fn state_history(&self, n: usize) -> Vec<TimedEvent> {
+        self.events.last(n)
+    }
+
+    /// Waits until the connection is ready, e.g. after a reconnect.
+    /// Fails once it is closed or parked for good.
+    pub async fn ready(&self) -> Result<(), RabbitError> {
+        let mut state = self.state_watcher().await?;
+        ready_or_final(&mut state).await
+    }
+
+    pub async fn create_channel(&self) -> Result<Channel, RabbitError> {
+        let lease = self.budget.acquire().await?;
+        let channel = self.addr.send(CreateChannel).await??;
+        Ok(Channel::new(channel, lease))
+    }
+
+    /// Preset of `purpose` with the prefetch override of the options, if any.
+    pub fn preset(&self, purpose: ChannelPurpose) -> ChannelPreset {
+        let preset = purpose.preset();
+        match self.prefetch.get(&purpose) {
+            Some(&prefetch) => preset.with_prefetch(prefetch),
+            None => preset,
+        }
+    }
+
+    /// Opens a channel set up by the [`preset`](Connection::preset) of `purpose`.
+    pub async fn create_channel_for(
+        &self,
+        purpose: ChannelPurpose,
+    ) -> Result<Channel, RabbitError> {
+        ChannelSource::create_channel_with(self, purpose, self.preset(purpose)).await
+    }
+
+    /// Registers `who` as depending on this connection, until the guard is dropped.
+    /// Consumers of exclusive queues register themselves.
+    pub fn depend(&self, who: impl Into<String>) -> DependentGuard {
+        self.dependents.add(who.into())
+    }
+
+    /// Descriptions of the registered dependents.
+    pub fn dependents(&self) -> Vec<String> {
+        self.dependents.list()
+    }
+
+    /// Whether `channel` was opened on this connection.
+    pub(crate) fn owns(&self, channel: &Channel) -> bool {
+        channel.counted_by(&self.budget)
+    }
+
+    /// Dependent registration for a consumer of `queue`, if it was declared exclusive here.
+    pub(crate) fn hold_exclusive(&self, queue: &str, who: &str) -> Option<DependentGuard> {
+        let exclusive = self.registry.read().unwrap().is_exclusive(queue);
+        exclusive.then(|| self.depend(format!("{who} of exclusive queue {queue}")))
+    }
+
+    /// Closes the connection for good with the close timeout from the options.
+    pub async fn close(&self, code: u16, text: &str) -> Result<(), RabbitError> {
+        self.close_with_timeout(code, text, self.close_timeout).await
+    }
+
+    /// Closes the connection for good, waiting at most `timeout` for the broker to
+    /// answer before dropping the connection; the state turns `Closed` either way.
+    /// Refuses with [`RabbitError::InUse`] while dependents are registered.
+    pub async fn close_with_timeout(
+        &self,
+        code: u16,
+        text: &str,
+        timeout: Duration,
+    ) -> Result<(), RabbitError> {
+        let dependents = self.dependents();
+        if !dependents.is_empty() {
+            return Err(RabbitError::InUse(dependents));
+        }
+        self.close_forced(code, text, timeout).await
+    }
+
+    /// [`Connection::close_with_timeout`] that only warns about registered dependents.
+    pub async fn close_forced(
+        &self,
+        code: u16,
+        text: &str,
+        timeout: Duration,
+    ) -> Result<(), RabbitError> {
+        let dependents = self.dependents();
+        if !dependents.is_empty() {
+            warn!(dependents = dependents.join(", "), "closing a connection still in use");
+        }
+        self.addr
+            .send(CloseConnection {
+                code,
+                text: text.to_owned(),
+                timeout,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Drains and closes the connection: consumers cancel their subscriptions and finish
+    /// the handlers in flight, then publishers settle pending confirms, within `grace`
+    /// altogether; then the connection closes for good, dependents or not.
+    ///
+    /// Consumers and publishers follow the shutdown token of the options, advanced here,
+    /// or one of the connection's own when the options have none.
+    pub async fn shutdown(&self, grace: Duration) -> Result<(), RabbitError> {
+        let drained = tokio::time::timeout(grace, async {
+            self.shutdown.advance(Stage::Consumers).await;
+            self.shutdown.advance(Stage::Publishers).await;
+        })
+        .await;
+        if drained.is_err() {
+            warn!(?grace, "grace period elapsed before consumers and publishers drained");
+        }
+        self.close_forced(0, "shutdown", self.close_timeout).await
+    }
+
+    /// Shutdown token consumers and publishers on this connection follow by default.
+    pub fn shutdown_token(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Number of channels created through this connection and still alive.
+    pub fn channels_open(&self) -> usize {
+        self.budget.open()
+    }
+
+    /// Whether the channel budget is spent, so the next `create_channel` has to wait or fail.
+    pub fn channels_exhausted(&self) -> bool {
+        self.budget.exhausted()
+    }
+
+    /// Declares `topology`, logging the [`audit`](super::topology::audit) warnings first.
+    pub async fn declare(&self, topology: &[Box<dyn Topology>]) -> Result<(), RabbitError> {
+        let mut definitions = Definitions::default();
+        for item in topology {
+            item.define(&mut definitions);
+        }
+        warn_audit(&definitions);
+        let channel = self.create_channel_for(ChannelPurpose::Admin).await?;
+        for item in topology {
+            item.declare(&channel).await?;
+            item.register(&mut self.registry.write().unwrap());
+        }
+        _ = channel.close(0, "topology declared").await;
+        Ok(())
+    }
+
+    /// Records `item` without declaring it, e.g. when it was declared on a channel
+    /// directly, so the strict mode checks know about it.
+    pub fn register(&self, item: &dyn Topology) {
+        item.register(&mut self.registry.write().unwrap());
+    }
+
+    /// Names declared through this connection so far.
+    pub fn registry(&self) -> TopologyRegistry {
+        self.registry.read().unwrap().clone()
+    }
+
+    /// In strict mode, fails for exchanges missing from the declared topology.
+    pub fn ensure_exchange(&self, exchange: &str) -> Result<(), RabbitError> {
+        match self.strict {
+            true => self.registry.read().unwrap().ensure_exchange(exchange),
+            false => Ok(()),
+        }
+    }
+
+    /// In strict mode, fails for queues without a declared binding; consumers check it
+    /// before subscribing. A queue reached through the default exchange is registered
+    /// with a binding to `""`.
+    pub fn ensure_bound(&self, queue: &str) -> Result<(), RabbitError> {
+        match self.strict {
+            true => self.registry.read().unwrap().ensure_bound(queue),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Waits until `state` is ready or final, failing with the topology error of a
+/// `TopologyFailed` state and [`RabbitError::NotConnected`] otherwise.
+pub(crate) async fn ready_or_final(
+    state: &mut watch::Receiver<ConnectionState>,
+) -> Result<(), RabbitError> {
+    let state = state
+        .wait_for(|s| s.is_ready() || s.is_final())
+        .await
+        .map_err(|_| RabbitError::NotConnected)?;
+    match &*state {
+        ConnectionState::Ready { .. } => Ok(()),
+        ConnectionState::TopologyFailed { item, error } => Err(RabbitError::TopologyFailed {
+            item: item.clone(),
+            source: error.clone(),
+        }),
+        _ => Err(RabbitError::NotConnected),
+    }
+}
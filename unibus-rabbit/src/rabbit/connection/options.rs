@@ -0,0 +1,168 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use lapin::types::FieldTable;
+
+use super::{BudgetPolicy, ChannelPurpose, Endpoints, Failover};
+use crate::rabbit::{topology::Topology, RabbitError};
+use crate::shutdown::Shutdown;
+
+#[derive(Clone)]
+pub struct ConnectionOptions {
+    pub endpoints: Endpoints,
+    pub failover: Failover,
+    pub name: String,
+    pub reconnect: Duration,
+    pub topology: Vec<Arc<dyn Topology>>,
+    pub locale: String,
+    pub properties: FieldTable,
+    pub channel_budget: Option<usize>,
+    pub budget_policy: BudgetPolicy,
+    pub strict: bool,
+    pub shutdown: Option<Shutdown>,
+    pub startup_jitter: Duration,
+    pub close_timeout: Duration,
+    /// Number of events kept for [`Connection::state_history`](super::Connection::state_history).
+    pub history: usize,
+    /// Connects in a row the topology may fail on before the connection is parked.
+    pub topology_attempts: Option<u32>,
+    /// Prefetch overriding the preset of a channel purpose.
+    pub channel_prefetch: BTreeMap<ChannelPurpose, u16>,
+}
+
+impl Into<lapin::ConnectionProperties> for &ConnectionOptions {
+    #[cfg(target_family = "unix")]
+    fn into(self) -> lapin::ConnectionProperties {
+        use std::sync::Arc;
+
+        use lapin::ConnectionProperties;
+
+        ConnectionProperties {
+            locale: self.locale.clone(),
+            client_properties: self.properties.clone(),
+            executor: Some(Arc::new(tokio_executor_trait::Tokio::current())),
+            reactor: Some(Arc::new(tokio_reactor_trait::Tokio)),
+        }
+    }
+
+    #[cfg(target_family = "windows")]
+    fn into(self) -> ConnectionProperties {
+        ConnectionProperties {
+            locale: self.locale.clone(),
+            client_properties: self.properties.clone(),
+            executor: Some(Arc(tokio_executor_trait::Tokio::current())),
+            reactor: None,
+        }
+    }
+}
+
+impl ConnectionOptions {
+    /// Options for one broker, or several to fail over between, e.g. given as
+    /// `"amqp://a:5672,amqp://b:5672"`; fails on an invalid or empty list.
+    pub fn new<E>(
+        endpoints: impl TryInto<Endpoints, Error = E>,
+        name: impl Into<String>,
+    ) -> Result<Self, RabbitError>
+    where
+        RabbitError: From<E>,
+    {
+        Ok(ConnectionOptions {
+            endpoints: endpoints.try_into()?,
+            failover: Failover::default(),
+            name: name.into(),
+            reconnect: Duration::from_secs(3),
+            topology: Default::default(),
+            locale: "en-US".to_owned(),
+            properties: Default::default(),
+            channel_budget: None,
+            budget_policy: BudgetPolicy::Wait,
+            strict: false,
+            shutdown: None,
+            startup_jitter: Duration::ZERO,
+            close_timeout: Duration::from_secs(5),
+            history: 64,
+            topology_attempts: None,
+            channel_prefetch: BTreeMap::new(),
+        })
+    }
+
+    /// Order the endpoints are tried in after a failed connect.
+    pub fn with_failover(mut self, failover: Failover) -> Self {
+        self.failover = failover;
+        self
+    }
+
+    pub fn with_reconnect(mut self, reconnect: Duration) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Delays the first connect by a random duration up to `max`.
+    pub fn with_startup_jitter(mut self, max: Duration) -> Self {
+        self.startup_jitter = max;
+        self
+    }
+
+    /// Bounds the close handshake when the actor stops; past it the connection is dropped.
+    pub fn with_close_timeout(mut self, timeout: Duration) -> Self {
+        self.close_timeout = timeout;
+        self
+    }
+
+    pub fn with_history(mut self, history: usize) -> Self {
+        self.history = history;
+        self
+    }
+
+    pub fn with_locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = locale.into();
+        self
+    }
+
+    pub fn with_props(mut self, props: FieldTable) -> Self {
+        self.properties = props;
+        self
+    }
+
+    /// Rejects publishing to undeclared exchanges and consuming from unbound queues.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Closes the connection and stops reconnecting at [`Stage::Connections`](crate::shutdown::Stage).
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    pub fn with_channel_budget(mut self, limit: usize, policy: BudgetPolicy) -> Self {
+        self.channel_budget = Some(limit);
+        self.budget_policy = policy;
+        self
+    }
+
+    /// Prefetch of channels opened for `purpose`; confirm mode stays fixed by the purpose.
+    pub fn with_channel_prefetch(mut self, purpose: ChannelPurpose, prefetch: u16) -> Self {
+        self.channel_prefetch.insert(purpose, prefetch);
+        self
+    }
+
+    /// Declared on every (re)connect before the connection reports `Ready`.
+    pub fn with_topology(mut self, topology: Vec<Box<dyn Topology>>) -> Self {
+        self.topology = topology.into_iter().map(Arc::from).collect();
+        self
+    }
+
+    /// Parks the connection in [`ConnectionState::TopologyFailed`](super::ConnectionState)
+    /// once declaring the topology failed on `attempts` connects in a row, instead of
+    /// reconnecting forever, e.g. on `PRECONDITION_FAILED` for a changed queue.
+    pub fn with_topology_attempts(mut self, attempts: u32) -> Self {
+        self.topology_attempts = Some(attempts.max(1));
+        self
+    }
+
+    pub fn add_topology(mut self, topology: impl Topology + 'static) -> Self {
+        self.topology.push(Arc::new(topology));
+        self
+    }
+}
\ No newline at end of file
@@ -0,0 +1,365 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use futures::future::select_all;
+use tokio::sync::watch;
+
+use super::{
+    ready_or_final, Channel, ChannelPreset, ChannelPurpose, Connection, ConnectionState,
+    DependentGuard,
+};
+use crate::{
+    rabbit::{topology::Topology, RabbitError},
+    shutdown::Shutdown,
+};
+
+/// Something channels can be opened on: a single connection or a pool of them.
+#[async_trait]
+pub trait ChannelSource: Send + Sync {
+    async fn create_channel(&self) -> Result<Channel, RabbitError>;
+
+    /// Opens a channel for `purpose` set up by `preset`, counted in the channel
+    /// metrics under the purpose.
+    async fn create_channel_with(
+        &self,
+        purpose: ChannelPurpose,
+        preset: ChannelPreset,
+    ) -> Result<Channel, RabbitError> {
+        let channel = self.create_channel().await?;
+        preset.apply(&channel).await?;
+        Ok(channel.with_purpose(self.name(), purpose))
+    }
+
+    /// [`ChannelSource::create_channel_with`] the preset of the source for `purpose`.
+    async fn create_channel_for(&self, purpose: ChannelPurpose) -> Result<Channel, RabbitError> {
+        self.create_channel_with(purpose, self.preset(purpose)).await
+    }
+
+    /// Preset of `purpose`, with the prefetch
+    /// [override](super::ConnectionOptions::with_channel_prefetch) of the options.
+    fn preset(&self, purpose: ChannelPurpose) -> ChannelPreset {
+        purpose.preset()
+    }
+
+    /// Resolves once channels can be opened, e.g. after a reconnect;
+    /// fails when the source is closed for good.
+    async fn ready(&self) -> Result<(), RabbitError>;
+
+    /// Name of the connection, or of the first pooled one, labeling metrics.
+    fn name(&self) -> String {
+        String::new()
+    }
+
+    /// Owned handle on the same source, for tasks outliving the caller's borrow.
+    fn share(&self) -> Arc<dyn ChannelSource>;
+
+    /// Shutdown token of the source, followed by consumers and publishers created on it
+    /// without one of their own, see [`Connection::shutdown`].
+    fn shutdown(&self) -> Option<Shutdown> {
+        None
+    }
+
+    /// Strict mode check of a publish target, see [`Connection::ensure_exchange`].
+    fn ensure_exchange(&self, _exchange: &str) -> Result<(), RabbitError> {
+        Ok(())
+    }
+
+    /// Strict mode check of a consumed queue, see [`Connection::ensure_bound`].
+    fn ensure_bound(&self, _queue: &str) -> Result<(), RabbitError> {
+        Ok(())
+    }
+
+    /// Records `item`, declared on a channel of the source directly, for the strict
+    /// mode checks.
+    fn register(&self, _item: &dyn Topology) {}
+
+    /// Registers `who`, consuming on `channel`, as a dependent of the connection the
+    /// channel was opened on when `queue` is exclusive to it, see [`Connection::depend`].
+    fn hold_exclusive(
+        &self,
+        _channel: &Channel,
+        _queue: &str,
+        _who: &str,
+    ) -> Option<DependentGuard> {
+        None
+    }
+}
+
+#[async_trait]
+impl ChannelSource for Connection {
+    async fn create_channel(&self) -> Result<Channel, RabbitError> {
+        Connection::create_channel(self).await
+    }
+
+    fn preset(&self, purpose: ChannelPurpose) -> ChannelPreset {
+        Connection::preset(self, purpose)
+    }
+
+    async fn ready(&self) -> Result<(), RabbitError> {
+        Connection::ready(self).await
+    }
+
+    fn name(&self) -> String {
+        Connection::name(self).to_owned()
+    }
+
+    fn share(&self) -> Arc<dyn ChannelSource> {
+        Arc::new(self.clone())
+    }
+
+    fn shutdown(&self) -> Option<Shutdown> {
+        Some(self.shutdown_token())
+    }
+
+    fn ensure_exchange(&self, exchange: &str) -> Result<(), RabbitError> {
+        Connection::ensure_exchange(self, exchange)
+    }
+
+    fn ensure_bound(&self, queue: &str) -> Result<(), RabbitError> {
+        Connection::ensure_bound(self, queue)
+    }
+
+    fn register(&self, item: &dyn Topology) {
+        Connection::register(self, item)
+    }
+
+    fn hold_exclusive(&self, _channel: &Channel, queue: &str, who: &str) -> Option<DependentGuard> {
+        Connection::hold_exclusive(self, queue, who)
+    }
+}
+
+/// Several connections with the same options; channels are opened on the least loaded one.
+///
+/// Publishers and consumers created on the pool reopen their channel through it after a
+/// failure, so they move to a healthy connection. Nothing moves back once the failed
+/// connection recovers: it takes new channels first, evening out the load only as
+/// channels are opened anew.
+#[derive(Clone)]
+pub struct ConnectionPool {
+    members: Vec<(Connection, watch::Receiver<ConnectionState>)>,
+}
+
+impl ConnectionPool {
+    pub(crate) async fn new(connections: Vec<Connection>) -> Result<Self, RabbitError> {
+        let mut members = Vec::with_capacity(connections.len());
+        for connection in connections {
+            let state = connection.state_watcher().await?;
+            members.push((connection, state));
+        }
+        Ok(ConnectionPool { members })
+    }
+
+    pub fn connections(&self) -> impl Iterator<Item = &Connection> {
+        self.members.iter().map(|(c, _)| c)
+    }
+
+    /// Ready connection with spare channel budget and the fewest open channels.
+    pub fn least_loaded(&self) -> Option<&Connection> {
+        self.members
+            .iter()
+            .filter(|(c, state)| state.borrow().is_ready() && !c.channels_exhausted())
+            .map(|(c, _)| c)
+            .min_by_key(|c| c.channels_open())
+    }
+}
+
+#[async_trait]
+impl ChannelSource for ConnectionPool {
+    async fn create_channel(&self) -> Result<Channel, RabbitError> {
+        match self.least_loaded() {
+            Some(connection) => connection.create_channel().await,
+            None => Err(RabbitError::NotConnected),
+        }
+    }
+
+    /// Labeled with the connection the channel is opened on.
+    async fn create_channel_with(
+        &self,
+        purpose: ChannelPurpose,
+        preset: ChannelPreset,
+    ) -> Result<Channel, RabbitError> {
+        match self.least_loaded() {
+            Some(connection) => connection.create_channel_with(purpose, preset).await,
+            None => Err(RabbitError::NotConnected),
+        }
+    }
+
+    /// Preset of the first pooled connection; pooled connections share their options.
+    fn preset(&self, purpose: ChannelPurpose) -> ChannelPreset {
+        self.connections()
+            .next()
+            .map_or_else(|| purpose.preset(), |c| c.preset(purpose))
+    }
+
+    /// Resolves once any member connection is ready; fails with the first error once
+    /// all of them are closed or parked for good.
+    async fn ready(&self) -> Result<(), RabbitError> {
+        let waits = self.members.iter().map(|(_, state)| {
+            let mut state = state.clone();
+            Box::pin(async move { ready_or_final(&mut state).await })
+        });
+        let mut pending: Vec<_> = waits.collect();
+        let mut failure = None;
+        while !pending.is_empty() {
+            let (res, _, rest) = select_all(pending).await;
+            match res {
+                Ok(()) => return Ok(()),
+                Err(error) => {
+                    failure.get_or_insert(error);
+                }
+            }
+            pending = rest;
+        }
+        Err(failure.unwrap_or(RabbitError::NotConnected))
+    }
+
+    fn share(&self) -> Arc<dyn ChannelSource> {
+        Arc::new(self.clone())
+    }
+
+    fn name(&self) -> String {
+        self.connections()
+            .next()
+            .map(|c| c.name().to_owned())
+            .unwrap_or_default()
+    }
+
+    /// Shutdown token shared by the pooled connections, see
+    /// [`RabbitClient::connect_pool`](crate::rabbit::RabbitClient::connect_pool).
+    fn shutdown(&self) -> Option<Shutdown> {
+        self.connections().next().map(Connection::shutdown_token)
+    }
+
+    fn ensure_exchange(&self, exchange: &str) -> Result<(), RabbitError> {
+        self.connections()
+            .try_for_each(|c| c.ensure_exchange(exchange))
+    }
+
+    fn ensure_bound(&self, queue: &str) -> Result<(), RabbitError> {
+        self.connections().try_for_each(|c| c.ensure_bound(queue))
+    }
+
+    fn register(&self, item: &dyn Topology) {
+        self.connections().for_each(|c| c.register(item))
+    }
+
+    fn hold_exclusive(&self, channel: &Channel, queue: &str, who: &str) -> Option<DependentGuard> {
+        self.connections()
+            .find(|c| c.owns(channel))
+            .and_then(|c| c.hold_exclusive(queue, who))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use actix::Actor;
+
+    use super::{super::actor::ConnectionActor, *};
+    use crate::rabbit::{connection::EventLog, AmqpEndpoint, BudgetPolicy, ConnectionOptions};
+
+    /// Connection never connecting, with a state the test drives.
+    fn member(name: &str, budget: usize) -> (Connection, watch::Sender<ConnectionState>) {
+        let options = ConnectionOptions::new("amqp://127.0.0.1:1/%2f", name)
+            .unwrap()
+            .with_startup_jitter(Duration::from_secs(86_400))
+            .with_channel_budget(budget, BudgetPolicy::Fail);
+        let events = EventLog::new(16);
+        let addr = ConnectionActor::new(options.clone(), events.clone()).start();
+        let (state, _) = watch::channel(ConnectionState::None);
+        (Connection::new(addr, events, &options), state)
+    }
+
+    fn pool(members: &[&(Connection, watch::Sender<ConnectionState>)]) -> ConnectionPool {
+        let members = members
+            .iter()
+            .map(|(connection, state)| (connection.clone(), state.subscribe()))
+            .collect();
+        ConnectionPool { members }
+    }
+
+    fn ready() -> ConnectionState {
+        ConnectionState::Ready {
+            endpoint: "amqp://127.0.0.1:1/%2f".parse::<AmqpEndpoint>().unwrap(),
+        }
+    }
+
+    #[actix::test]
+    async fn picks_the_ready_member_with_the_fewest_channels() {
+        let a = member("a", 2);
+        let b = member("b", 1);
+        let c = member("c", 2);
+        let pool = pool(&[&b, &a, &c]);
+        assert!(pool.least_loaded().is_none());
+
+        a.1.send_replace(ready());
+        b.1.send_replace(ready());
+        let _a = a.0.budget.acquire().await.unwrap();
+        assert_eq!(pool.least_loaded().unwrap().name(), "b");
+
+        // as loaded as `a`, but out of budget
+        let _b = b.0.budget.acquire().await.unwrap();
+        assert_eq!(pool.least_loaded().unwrap().name(), "a");
+
+        c.1.send_replace(ready());
+        assert_eq!(pool.least_loaded().unwrap().name(), "c");
+    }
+
+    #[actix::test]
+    async fn ready_once_any_member_is() {
+        let a = member("a", 2);
+        let b = member("b", 2);
+        let pool = pool(&[&a, &b]);
+        let waiting = tokio::spawn({
+            let pool = pool.clone();
+            async move { ChannelSource::ready(&pool).await }
+        });
+        a.1.send_replace(ConnectionState::Closed);
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        b.1.send_replace(ready());
+        assert!(waiting.await.unwrap().is_ok());
+    }
+
+    #[actix::test]
+    async fn fails_once_every_member_is_final() {
+        let a = member("a", 2);
+        let b = member("b", 2);
+        let pool = pool(&[&a, &b]);
+        a.1.send_replace(ConnectionState::Closed);
+        b.1.send_replace(ConnectionState::Closed);
+        assert!(ChannelSource::ready(&pool).await.is_err());
+    }
+}
+
+/// Source whose channels never open, counting the attempts; each attempt yields to the
+/// scheduler once before failing, as a real one would.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct Unreachable(Arc<std::sync::atomic::AtomicU32>);
+
+#[cfg(test)]
+impl Unreachable {
+    pub(crate) fn attempts(&self) -> u32 {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl ChannelSource for Unreachable {
+    async fn create_channel(&self) -> Result<Channel, RabbitError> {
+        self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        tokio::task::yield_now().await;
+        Err(RabbitError::NotConnected)
+    }
+
+    async fn ready(&self) -> Result<(), RabbitError> {
+        Ok(())
+    }
+
+    fn share(&self) -> Arc<dyn ChannelSource> {
+        Arc::new(self.clone())
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use lapin::options::{BasicQosOptions, ConfirmSelectOptions};
+
+use crate::rabbit::{metrics, RabbitError};
+
+/// What a channel is opened for. Each purpose comes with preset options and labels
+/// the channel metrics, see [`ChannelSource::create_channel_for`](super::ChannelSource).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChannelPurpose {
+    /// Publishing with broker confirms.
+    PublishConfirm,
+    /// Consuming with a prefetch limit.
+    Consume,
+    /// Declarations, purges and other one-off operations.
+    Admin,
+}
+
+impl ChannelPurpose {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChannelPurpose::PublishConfirm => "publish-confirm",
+            ChannelPurpose::Consume => "consume",
+            ChannelPurpose::Admin => "admin",
+        }
+    }
+
+    /// Options channels of this purpose are opened with unless the connection options
+    /// override the prefetch.
+    pub fn preset(self) -> ChannelPreset {
+        match self {
+            ChannelPurpose::PublishConfirm => ChannelPreset {
+                confirm: true,
+                prefetch: None,
+            },
+            ChannelPurpose::Consume => ChannelPreset {
+                confirm: false,
+                prefetch: Some(10),
+            },
+            ChannelPurpose::Admin => ChannelPreset {
+                confirm: false,
+                prefetch: None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ChannelPurpose {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Modes set on a channel right after it is opened.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelPreset {
+    /// Puts the channel in confirm mode.
+    pub confirm: bool,
+    /// Per-consumer prefetch count, left to the broker default when `None`.
+    pub prefetch: Option<u16>,
+}
+
+impl ChannelPreset {
+    pub fn with_prefetch(mut self, prefetch: u16) -> Self {
+        self.prefetch = Some(prefetch);
+        self
+    }
+
+    pub(crate) async fn apply(self, channel: &lapin::Channel) -> Result<(), RabbitError> {
+        if self.confirm {
+            channel
+                .confirm_select(ConfirmSelectOptions::default())
+                .await?;
+        }
+        if let Some(prefetch) = self.prefetch {
+            channel
+                .basic_qos(prefetch, BasicQosOptions::default())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts a channel as open for its purpose until dropped.
+pub(crate) struct PurposeGauge {
+    connection: String,
+    purpose: ChannelPurpose,
+}
+
+impl PurposeGauge {
+    pub(crate) fn new(connection: String, purpose: ChannelPurpose) -> Self {
+        metrics::channel_opened(&connection, purpose.as_str());
+        PurposeGauge {
+            connection,
+            purpose,
+        }
+    }
+
+    pub(crate) fn purpose(&self) -> ChannelPurpose {
+        self.purpose
+    }
+}
+
+impl Drop for PurposeGauge {
+    fn drop(&mut self) {
+        metrics::channel_closed(&self.connection, self.purpose.as_str());
+    }
+}
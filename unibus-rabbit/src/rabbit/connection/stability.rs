@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use tokio::{sync::watch, time::Instant};
+
+use super::{Connection, ConnectionState};
+use crate::rabbit::RabbitError;
+
+/// Debounced readiness over the state watcher of a connection, for readiness probes
+/// that should not flap during brief broker hiccups.
+///
+/// Reports ready only once the connection stayed ready for `up_after`, and not ready
+/// only once it stayed down for `down_after`; a connection closed for good is reported
+/// not ready at once.
+#[derive(Clone, Copy, Debug)]
+pub struct StabilityFilter {
+    pub up_after: Duration,
+    pub down_after: Duration,
+}
+
+impl Default for StabilityFilter {
+    /// Ready after 5s up, not ready after 10s down.
+    fn default() -> Self {
+        StabilityFilter {
+            up_after: Duration::from_secs(5),
+            down_after: Duration::from_secs(10),
+        }
+    }
+}
+
+impl StabilityFilter {
+    pub fn new(up_after: Duration, down_after: Duration) -> Self {
+        StabilityFilter {
+            up_after,
+            down_after,
+        }
+    }
+
+    /// Filtered readiness of `states`, starting not ready; the filter runs until every
+    /// receiver is dropped or the states end.
+    pub fn watch(self, mut states: watch::Receiver<ConnectionState>) -> watch::Receiver<bool> {
+        let (ready, receiver) = watch::channel(false);
+        tokio::spawn(async move {
+            loop {
+                let (target, closed) = {
+                    let state = states.borrow_and_update();
+                    (state.is_ready(), state.is_final())
+                };
+                if closed {
+                    ready.send_replace(false);
+                    return;
+                }
+                if target != *ready.borrow() {
+                    let delay = if target {
+                        self.up_after
+                    } else {
+                        self.down_after
+                    };
+                    match self
+                        .settle(&mut states, target, Instant::now() + delay)
+                        .await
+                    {
+                        Settle::Held => {
+                            ready.send_replace(target);
+                        }
+                        Settle::Changed => continue,
+                        Settle::Ended => return,
+                    }
+                }
+                tokio::select! {
+                    changed = states.changed() => if changed.is_err() { return },
+                    _ = ready.closed() => return,
+                }
+            }
+        });
+        receiver
+    }
+
+    /// Waits until `deadline` for the readiness to change away from `target`.
+    async fn settle(
+        &self,
+        states: &mut watch::Receiver<ConnectionState>,
+        target: bool,
+        deadline: Instant,
+    ) -> Settle {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => return Settle::Held,
+                changed = states.changed() => {
+                    if changed.is_err() {
+                        return Settle::Ended;
+                    }
+                    let state = states.borrow();
+                    if state.is_final() || state.is_ready() != target {
+                        return Settle::Changed;
+                    }
+                }
+            }
+        }
+    }
+}
+
+enum Settle {
+    Held,
+    Changed,
+    Ended,
+}
+
+impl Connection {
+    /// Readiness of the connection filtered by `filter`, see [`StabilityFilter`].
+    pub async fn stable_readiness(
+        &self,
+        filter: StabilityFilter,
+    ) -> Result<watch::Receiver<bool>, RabbitError> {
+        Ok(filter.watch(self.state_watcher().await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rabbit::AmqpEndpoint;
+
+    fn ready() -> ConnectionState {
+        ConnectionState::Ready {
+            endpoint: AmqpEndpoint::parse("amqp://localhost:5672/%2f").unwrap(),
+        }
+    }
+
+    fn filtered() -> (watch::Sender<ConnectionState>, watch::Receiver<bool>) {
+        let (states, receiver) = watch::channel(ConnectionState::None);
+        let filter = StabilityFilter::new(Duration::from_secs(5), Duration::from_secs(10));
+        (states, filter.watch(receiver))
+    }
+
+    async fn after(secs: u64) {
+        tokio::time::sleep(Duration::from_secs(secs)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_ready_once_up_long_enough() {
+        let (states, readiness) = filtered();
+        states.send_replace(ready());
+        after(4).await;
+        assert!(!*readiness.borrow());
+        after(2).await;
+        assert!(*readiness.borrow());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ignores_hiccups_shorter_than_the_delays() {
+        let (states, readiness) = filtered();
+        states.send_replace(ready());
+        after(3).await;
+        states.send_replace(ConnectionState::None);
+        after(1).await;
+        states.send_replace(ready());
+        after(4).await;
+        assert!(!*readiness.borrow(), "the up delay restarts after a drop");
+        after(2).await;
+        assert!(*readiness.borrow());
+
+        states.send_replace(ConnectionState::None);
+        after(9).await;
+        states.send_replace(ready());
+        after(30).await;
+        assert!(*readiness.borrow());
+
+        states.send_replace(ConnectionState::None);
+        after(11).await;
+        assert!(!*readiness.borrow());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_a_closed_connection_at_once() {
+        let (states, mut readiness) = filtered();
+        states.send_replace(ready());
+        after(6).await;
+        assert!(*readiness.borrow_and_update());
+        let closed = Instant::now();
+        states.send_replace(ConnectionState::Closed);
+        readiness.changed().await.unwrap();
+        assert!(!*readiness.borrow());
+        assert_eq!(closed.elapsed(), Duration::ZERO);
+    }
+}
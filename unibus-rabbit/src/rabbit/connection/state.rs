@@ -0,0 +1,65 @@
+use super::AmqpEndpoint;
+use crate::rabbit::lapin_error_eq;
+
+#[derive(Clone, Debug)]
+pub enum ConnectionState {
+    None,
+    /// Connected to `endpoint`, one of the endpoints of the options.
+    Ready { endpoint: AmqpEndpoint },
+    Error(lapin::Error),
+    /// Closed on request; the connection does not reconnect any more.
+    Closed,
+    /// Declaring `item` of the topology failed on as many connects in a row as the
+    /// options allow; the connection is parked and does not reconnect any more.
+    TopologyFailed { item: String, error: lapin::Error },
+}
+
+impl ConnectionState {
+    pub fn is_ready(&self) -> bool {
+        matches!(self, ConnectionState::Ready { .. })
+    }
+
+    /// Closed or parked: the state does not change any more.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            ConnectionState::Closed | ConnectionState::TopologyFailed { .. }
+        )
+    }
+}
+
+impl PartialEq for ConnectionState {
+    fn eq(&self, other: &Self) -> bool {
+        match self {
+            ConnectionState::None => {
+                if let ConnectionState::None = other {
+                    true
+                } else {
+                    false
+                }
+            }
+            ConnectionState::Ready { endpoint: e1 } => {
+                if let ConnectionState::Ready { endpoint: e2 } = other {
+                    e1 == e2
+                } else {
+                    false
+                }
+            }
+            ConnectionState::Closed => matches!(other, ConnectionState::Closed),
+            ConnectionState::Error(e1) => {
+                if let ConnectionState::Error(e2) = other {
+                    lapin_error_eq(e1, e2)
+                } else {
+                    false
+                }
+            }
+            ConnectionState::TopologyFailed { item, error } => {
+                if let ConnectionState::TopologyFailed { item: i2, error: e2 } = other {
+                    item == i2 && lapin_error_eq(error, e2)
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use lapin::{
+    acker::Acker,
+    options::{BasicAckOptions, BasicNackOptions, BasicRejectOptions},
+};
+use tracing::warn;
+
+use super::{Ack, Delivery, DeliveryHandler, Nack};
+use crate::rabbit::{metrics, RabbitError};
+
+/// Owned right to settle one delivery, movable apart from its payload.
+///
+/// Dropping the token leaves the delivery unacknowledged until its channel closes,
+/// when the broker requeues it.
+#[derive(Debug)]
+#[must_use = "an unsettled delivery is redelivered only after its channel closes"]
+pub struct AckToken {
+    acker: Acker,
+}
+
+impl AckToken {
+    pub(crate) fn new(acker: Acker) -> Self {
+        AckToken { acker }
+    }
+
+    pub async fn ack(self) -> Result<(), RabbitError> {
+        Ok(self.acker.ack(BasicAckOptions::default()).await?)
+    }
+
+    pub async fn nack(self, requeue: bool) -> Result<(), RabbitError> {
+        Ok(self
+            .acker
+            .nack(BasicNackOptions {
+                requeue,
+                ..Default::default()
+            })
+            .await?)
+    }
+
+    /// Rejects without requeue, so the delivery dead-letters if configured.
+    pub async fn reject(self) -> Result<(), RabbitError> {
+        Ok(self
+            .acker
+            .reject(BasicRejectOptions { requeue: false })
+            .await?)
+    }
+}
+
+/// Right to settle one delivery, handed to [`ManualAck`](super::ManualAck) handlers that
+/// settle deliveries themselves rather than by their result.
+///
+/// As with [`AckToken`], dropping the handle leaves the delivery unacknowledged until
+/// its channel closes, e.g. for the deliveries covered by a later
+/// [`AckHandle::ack_multiple`].
+#[derive(Debug)]
+#[must_use = "an unsettled delivery is redelivered only after its channel closes"]
+pub struct AckHandle {
+    delivery_tag: u64,
+    target: Target,
+}
+
+#[derive(Debug)]
+enum Target {
+    Broker {
+        acker: Acker,
+        connection: String,
+        queue: String,
+    },
+    /// Settlement taken by a handler wrapper, see [`Settler`].
+    Captured(Arc<Mutex<Capture>>),
+}
+
+#[derive(Debug)]
+enum Capture {
+    Open,
+    Settled(Settlement),
+    /// The wrapper is done; the handler kept the handle and settles for real.
+    Released(AckHandle),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Settlement {
+    Ack { multiple: bool },
+    Nack { requeue: bool },
+    Reject,
+}
+
+impl Settlement {
+    fn outcome(self) -> Result<Ack, Nack> {
+        match self {
+            Settlement::Ack { .. } => Ok(Ack),
+            Settlement::Nack { requeue } => Err(Nack { requeue }),
+            Settlement::Reject => Err(Nack { requeue: false }),
+        }
+    }
+}
+
+impl AckHandle {
+    pub(crate) fn new(acker: Acker, delivery_tag: u64, connection: String, queue: String) -> Self {
+        AckHandle {
+            delivery_tag,
+            target: Target::Broker {
+                acker,
+                connection,
+                queue,
+            },
+        }
+    }
+
+    pub fn delivery_tag(&self) -> u64 {
+        self.delivery_tag
+    }
+
+    pub async fn ack(self) -> Result<(), RabbitError> {
+        self.settle_as(Settlement::Ack { multiple: false }).await
+    }
+
+    /// Acks this delivery and every earlier one of the channel not settled yet, e.g. the
+    /// last of a batch. With a concurrency above one that includes deliveries other
+    /// handlers are still working on.
+    pub async fn ack_multiple(self) -> Result<(), RabbitError> {
+        self.settle_as(Settlement::Ack { multiple: true }).await
+    }
+
+    pub async fn nack(self, requeue: bool) -> Result<(), RabbitError> {
+        self.settle_as(Settlement::Nack { requeue }).await
+    }
+
+    /// Rejects without requeue, so the delivery dead-letters if configured.
+    pub async fn reject(self) -> Result<(), RabbitError> {
+        self.settle_as(Settlement::Reject).await
+    }
+
+    /// Acks or nacks by a handler result.
+    pub async fn settle(self, outcome: Result<Ack, Nack>) -> Result<(), RabbitError> {
+        match outcome {
+            Ok(Ack) => self.ack().await,
+            Err(Nack { requeue }) => self.nack(requeue).await,
+        }
+    }
+
+    /// [`AckHandle::settle`] logging a failure.
+    pub(crate) async fn settle_or_warn(self, outcome: Result<Ack, Nack>) {
+        if let Err(e) = self.settle(outcome).await {
+            warn!(error = format!("{e}"), "acknowledge failed");
+        }
+    }
+
+    async fn settle_as(self, settlement: Settlement) -> Result<(), RabbitError> {
+        let (acker, connection, queue) = match self.target {
+            Target::Broker {
+                acker,
+                connection,
+                queue,
+            } => (acker, connection, queue),
+            Target::Captured(capture) => {
+                let state =
+                    mem::replace(&mut *capture.lock().unwrap(), Capture::Settled(settlement));
+                return match state {
+                    Capture::Released(handle) => Box::pin(handle.settle_as(settlement)).await,
+                    _ => Ok(()),
+                };
+            }
+        };
+        let settled = match settlement {
+            Settlement::Ack { multiple } => {
+                acker.ack(BasicAckOptions { multiple }).await?;
+                "unibus_acked_total"
+            }
+            Settlement::Nack { requeue } => {
+                acker
+                    .nack(BasicNackOptions {
+                        requeue,
+                        ..Default::default()
+                    })
+                    .await?;
+                match requeue {
+                    true => "unibus_requeued_total",
+                    false => "unibus_rejected_total",
+                }
+            }
+            Settlement::Reject => {
+                acker.reject(BasicRejectOptions { requeue: false }).await?;
+                "unibus_rejected_total"
+            }
+        };
+        metrics::delivery(settled, &connection, &queue);
+        Ok(())
+    }
+}
+
+enum Slot {
+    Fresh(AckHandle),
+    Captured(Settlement, AckHandle),
+    Gone,
+}
+
+/// Ack handle of a delivery shared between a handler wrapper and the handler it wraps,
+/// so the wrapper acts on how the wrapped handler settled the delivery as on its result,
+/// and [`ManualAck`](super::ManualAck) handlers keep working behind wrappers.
+pub(crate) struct Settler {
+    slot: Mutex<Slot>,
+}
+
+impl Settler {
+    pub(crate) fn new(ack: AckHandle) -> Self {
+        Settler {
+            slot: Mutex::new(Slot::Fresh(ack)),
+        }
+    }
+
+    /// Runs `handler` through [`DeliveryHandler::handle_with`] with a handle capturing
+    /// how it settles `delivery`, returned as its result. A handler keeping the handle
+    /// settles the delivery for good whenever it does; its run and any later one count
+    /// as acked.
+    pub(crate) async fn run<H: DeliveryHandler + ?Sized>(
+        &self,
+        handler: &H,
+        delivery: Delivery,
+    ) -> Result<Ack, Nack> {
+        let handle = match mem::replace(&mut *self.slot.lock().unwrap(), Slot::Gone) {
+            Slot::Fresh(handle) | Slot::Captured(_, handle) => handle,
+            Slot::Gone => return Ok(Ack),
+        };
+        let capture = Arc::new(Mutex::new(Capture::Open));
+        let captured = AckHandle {
+            delivery_tag: handle.delivery_tag,
+            target: Target::Captured(capture.clone()),
+        };
+        handler.handle_with(delivery, captured).await;
+        let mut state = capture.lock().unwrap();
+        match mem::replace(&mut *state, Capture::Open) {
+            Capture::Settled(settlement) => {
+                *self.slot.lock().unwrap() = Slot::Captured(settlement, handle);
+                settlement.outcome()
+            }
+            _ => {
+                *state = Capture::Released(handle);
+                Ok(Ack)
+            }
+        }
+    }
+
+    /// Settles the delivery with the wrapper's `outcome`, the way the wrapped handler did
+    /// when the outcome is unchanged, e.g. keeping an [`AckHandle::ack_multiple`].
+    pub(crate) async fn finish(&self, outcome: Result<Ack, Nack>) {
+        let slot = mem::replace(&mut *self.slot.lock().unwrap(), Slot::Gone);
+        let res = match slot {
+            Slot::Captured(settlement, handle) if settlement.outcome() == outcome => {
+                handle.settle_as(settlement).await
+            }
+            Slot::Fresh(handle) | Slot::Captured(_, handle) => handle.settle(outcome).await,
+            Slot::Gone => Ok(()),
+        };
+        if let Err(e) = res {
+            warn!(error = format!("{e}"), "acknowledge failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lapin::types::FieldTable;
+
+    use super::*;
+    use crate::rabbit::consumer::ManualAck;
+
+    fn handle() -> (AckHandle, Acker) {
+        let acker = Acker::default();
+        let handle = AckHandle::new(acker.clone(), 1, String::new(), String::new());
+        (handle, acker)
+    }
+
+    fn delivery() -> Delivery {
+        Delivery::test(FieldTable::default(), "{}")
+    }
+
+    #[tokio::test]
+    async fn wrapper_settles_by_its_own_outcome() {
+        let (ack, acker) = handle();
+        let settler = Settler::new(ack);
+        let handler = ManualAck::new(|_: Delivery, ack: AckHandle| async move {
+            ack.reject().await.unwrap();
+        });
+        let outcome = settler.run(&handler, delivery()).await;
+        assert_eq!(outcome, Err(Nack { requeue: false }));
+        assert!(!acker.used());
+        settler.finish(Ok(Ack)).await;
+        assert!(acker.used());
+    }
+
+    #[tokio::test]
+    async fn kept_handle_settles_after_the_wrapper() {
+        let (ack, acker) = handle();
+        let settler = Settler::new(ack);
+        let kept = Arc::new(Mutex::new(None));
+        let keep = kept.clone();
+        let handler = ManualAck::new(move |_: Delivery, ack: AckHandle| {
+            let keep = keep.clone();
+            async move { *keep.lock().unwrap() = Some(ack) }
+        });
+        assert_eq!(settler.run(&handler, delivery()).await, Ok(Ack));
+        settler.finish(Err(Nack { requeue: true })).await;
+        assert!(!acker.used());
+        let ack = kept.lock().unwrap().take().unwrap();
+        ack.ack().await.unwrap();
+        assert!(acker.used());
+    }
+}
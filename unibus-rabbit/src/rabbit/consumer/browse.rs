@@ -0,0 +1,39 @@
+use lapin::options::{BasicGetOptions, BasicNackOptions};
+
+use super::Delivery;
+use crate::rabbit::{ChannelPurpose, Connection, RabbitError};
+
+impl Connection {
+    /// Peeks at up to `max` messages from the head of `queue` without consuming them.
+    ///
+    /// The messages are fetched with `basic_get` and requeued together at the end, in
+    /// their original order; the broker marks them redelivered. Use [`Delivery::json`]
+    /// to decode the payloads.
+    pub async fn browse(&self, queue: &str, max: usize) -> Result<Vec<Delivery>, RabbitError> {
+        let channel = self.create_channel_for(ChannelPurpose::Admin).await?;
+        let mut browsed = Vec::new();
+        while browsed.len() < max {
+            let Some(message) = channel
+                .basic_get(queue, BasicGetOptions { no_ack: false })
+                .await?
+            else {
+                break;
+            };
+            let (delivery, _) = Delivery::from_lapin(message.delivery);
+            browsed.push(delivery);
+        }
+        if let Some(last) = browsed.last() {
+            channel
+                .basic_nack(
+                    last.delivery_tag,
+                    BasicNackOptions {
+                        multiple: true,
+                        requeue: true,
+                    },
+                )
+                .await?;
+        }
+        _ = channel.close(0, "browse finished").await;
+        Ok(browsed)
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    future::Future,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use lapin::types::FieldTable;
+
+use super::{Ack, Delivery, DeliveryHandler, Nack};
+
+/// Typed map of shared dependencies (pools, clients, config) handed to handlers.
+#[derive(Default)]
+pub struct Extensions {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Extensions::default()
+    }
+
+    /// Stores `value`, returning the previous value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok().map(|old| *old))
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+}
+
+/// Delivery together with the extensions of the consumer it arrived on.
+pub struct DeliveryContext {
+    pub delivery: Delivery,
+    extensions: Arc<Extensions>,
+}
+
+impl DeliveryContext {
+    pub fn new(delivery: Delivery, extensions: Arc<Extensions>) -> Self {
+        DeliveryContext {
+            delivery,
+            extensions,
+        }
+    }
+
+    /// Context of a delivery carrying `headers` and `payload`, without extensions, for
+    /// handler tests without a broker.
+    pub fn test(headers: FieldTable, payload: impl Into<Vec<u8>>) -> Self {
+        DeliveryContext::new(Delivery::test(headers, payload), Default::default())
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// The extension of type `T`; panics when it was never registered, a wiring bug.
+    pub fn get<T: Send + Sync + 'static>(&self) -> &T {
+        self.extensions
+            .get()
+            .unwrap_or_else(|| panic!("extension {} is not registered", std::any::type_name::<T>()))
+    }
+}
+
+/// Closure handler receiving a [`DeliveryContext`] with `extensions`.
+pub struct WithContext<F> {
+    extensions: Arc<Extensions>,
+    handler: F,
+}
+
+impl<F, Fut> WithContext<F>
+where
+    F: Fn(DeliveryContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    pub fn new(extensions: Arc<Extensions>, handler: F) -> Self {
+        WithContext {
+            extensions,
+            handler,
+        }
+    }
+}
+
+#[async_trait]
+impl<F, Fut> DeliveryHandler for WithContext<F>
+where
+    F: Fn(DeliveryContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        (self.handler)(DeliveryContext::new(delivery, self.extensions.clone())).await
+    }
+}
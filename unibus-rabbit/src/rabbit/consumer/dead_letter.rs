@@ -0,0 +1,213 @@
+use std::{collections::BTreeMap, future::Future};
+
+use async_trait::async_trait;
+use lapin::types::{AMQPValue, FieldTable};
+use tracing::warn;
+
+use super::{Ack, Consumer, ConsumerOptions, Delivery, DeliveryHandler, Nack};
+use crate::rabbit::{
+    headers,
+    topology::{Queue, Topology},
+    ChannelPurpose, ChannelSource, OutgoingMessage, Publisher, RabbitError,
+};
+
+/// One entry of the broker's `x-death` header: a queue the message was dead-lettered
+/// from, and how often.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Death {
+    pub queue: String,
+    /// `rejected`, `expired`, `maxlen` or `delivery_limit`.
+    pub reason: String,
+    /// Exchange the message was published to before it entered `queue`.
+    pub exchange: String,
+    pub routing_keys: Vec<String>,
+    pub count: u64,
+    /// Seconds since the epoch of the latest of these deaths.
+    pub time: Option<u64>,
+}
+
+impl Death {
+    /// Entries of the `x-death` header, the most recent first. The one parser of the
+    /// header, also behind [`FaultInfo`](crate::bus::FaultInfo) and
+    /// [`RetryTopology::attempt`](crate::rabbit::topology::RetryTopology::attempt).
+    pub fn from_headers(table: &FieldTable) -> Vec<Death> {
+        let Some(AMQPValue::FieldArray(deaths)) = table.inner().get("x-death") else {
+            return Vec::new();
+        };
+        deaths
+            .as_slice()
+            .iter()
+            .filter_map(|death| match death {
+                AMQPValue::FieldTable(death) => Some(Death::from_entry(death)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn from_entry(death: &FieldTable) -> Death {
+        let routing_keys = match death.inner().get("routing-keys") {
+            Some(AMQPValue::FieldArray(keys)) => keys
+                .as_slice()
+                .iter()
+                .filter_map(|key| match key {
+                    AMQPValue::LongString(key) => Some(key.to_string()),
+                    AMQPValue::ShortString(key) => Some(key.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let time = match death.inner().get("time") {
+            Some(AMQPValue::Timestamp(time)) => Some(*time),
+            _ => None,
+        };
+        Death {
+            queue: headers::get_str(death, "queue").unwrap_or_default(),
+            reason: headers::get_str(death, "reason").unwrap_or_default(),
+            exchange: headers::get_str(death, "exchange").unwrap_or_default(),
+            routing_keys,
+            count: headers::get_u64(death, "count").unwrap_or_default(),
+            time,
+        }
+    }
+}
+
+/// A message taken from a dead letter queue, settled by [`DeadLetter::replay`] or
+/// [`DeadLetter::park`].
+pub struct DeadLetter {
+    delivery: Delivery,
+    deaths: Vec<Death>,
+    publisher: Publisher,
+    parking_queue: String,
+}
+
+impl DeadLetter {
+    pub fn delivery(&self) -> &Delivery {
+        &self.delivery
+    }
+
+    /// The parsed `x-death` header, the most recent death first.
+    pub fn deaths(&self) -> &[Death] {
+        &self.deaths
+    }
+
+    /// The first death, whose exchange and routing key the message was published with.
+    pub fn origin(&self) -> Option<&Death> {
+        self.deaths.last()
+    }
+
+    /// Republishes the message to the exchange and routing key it was first
+    /// dead-lettered from, without the death headers; acked once confirmed.
+    pub async fn replay(self) -> Result<Ack, Nack> {
+        let Some(origin) = self.origin() else {
+            warn!("dead letter without x-death header, not replayed");
+            return Err(Nack { requeue: true });
+        };
+        let exchange = origin.exchange.clone();
+        let routing_key = origin.routing_keys.first().cloned().unwrap_or_default();
+        let table: FieldTable = headers::headers(&self.delivery.properties)
+            .inner()
+            .iter()
+            .filter(|(k, _)| k.as_str() != "x-death" && !k.as_str().starts_with("x-first-death-"))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect::<BTreeMap<_, _>>()
+            .into();
+        let message = OutgoingMessage::new(&exchange, &routing_key, self.delivery.data)
+            .with_properties(self.delivery.properties.with_headers(table));
+        match self.publisher.publish(message).await {
+            Ok(()) => Ok(Ack),
+            Err(e) => {
+                warn!(error = format!("{e}"), "dead letter replay failed");
+                Err(Nack { requeue: true })
+            }
+        }
+    }
+
+    /// Moves the message with all its headers to the parking queue, where it stays
+    /// until dealt with by hand; acked once confirmed.
+    pub async fn park(self) -> Result<Ack, Nack> {
+        let message = OutgoingMessage::new("", &self.parking_queue, self.delivery.data)
+            .with_properties(self.delivery.properties);
+        match self.publisher.publish(message).await {
+            Ok(()) => Ok(Ack),
+            Err(e) => {
+                warn!(error = format!("{e}"), "dead letter not parked");
+                Err(Nack { requeue: true })
+            }
+        }
+    }
+}
+
+/// Consumer of a dead letter queue handing each message with its parsed `x-death`
+/// header to a handler, which replays it to where it came from or parks it.
+///
+/// The parking queue defaults to `<queue>.parking-lot` and is declared durable on
+/// start.
+pub struct DeadLetterConsumer<F> {
+    publisher: Publisher,
+    handler: F,
+    parking_queue: Option<String>,
+}
+
+impl<F, Fut> DeadLetterConsumer<F>
+where
+    F: Fn(DeadLetter) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    pub fn new(publisher: Publisher, handler: F) -> Self {
+        DeadLetterConsumer {
+            publisher,
+            handler,
+            parking_queue: None,
+        }
+    }
+
+    pub fn with_parking_queue(mut self, queue: impl Into<String>) -> Self {
+        self.parking_queue = Some(queue.into());
+        self
+    }
+
+    /// Declares the parking queue and consumes `options.queue`.
+    pub async fn start(
+        self,
+        source: &dyn ChannelSource,
+        options: ConsumerOptions,
+    ) -> Result<Consumer, RabbitError> {
+        let parking_queue = self
+            .parking_queue
+            .unwrap_or_else(|| format!("{}.parking-lot", options.queue));
+        let channel = source.create_channel_for(ChannelPurpose::Admin).await?;
+        Queue::new(&parking_queue).declare(&channel).await?;
+        _ = channel.close(200, "OK").await;
+        let handler = Parking {
+            publisher: self.publisher,
+            handler: self.handler,
+            parking_queue,
+        };
+        Consumer::start(source, options, handler).await
+    }
+}
+
+struct Parking<F> {
+    publisher: Publisher,
+    handler: F,
+    parking_queue: String,
+}
+
+#[async_trait]
+impl<F, Fut> DeliveryHandler for Parking<F>
+where
+    F: Fn(DeadLetter) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        let deaths = Death::from_headers(&headers::headers(&delivery.properties));
+        let dead_letter = DeadLetter {
+            delivery,
+            deaths,
+            publisher: self.publisher.clone(),
+            parking_queue: self.parking_queue.clone(),
+        };
+        (self.handler)(dead_letter).await
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+
+use super::Delivery;
+
+/// Payload format guessed from its first bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadKind {
+    Empty,
+    Json,
+    Gzip,
+    Zstd,
+    /// Starts like a protobuf field tag; a guess, many binary formats do.
+    Protobuf,
+    Text,
+    Binary,
+}
+
+impl fmt::Display for PayloadKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PayloadKind::Empty => "empty",
+            PayloadKind::Json => "json",
+            PayloadKind::Gzip => "gzip",
+            PayloadKind::Zstd => "zstd",
+            PayloadKind::Protobuf => "protobuf-like",
+            PayloadKind::Text => "text",
+            PayloadKind::Binary => "binary",
+        })
+    }
+}
+
+/// Guesses the format of `data` from magic bytes and the leading character.
+pub fn sniff(data: &[u8]) -> PayloadKind {
+    match data {
+        [] => PayloadKind::Empty,
+        [0x1f, 0x8b, ..] => PayloadKind::Gzip,
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => PayloadKind::Zstd,
+        _ => match data.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{' | b'[' | b'"') => PayloadKind::Json,
+            _ if std::str::from_utf8(data).is_ok() => PayloadKind::Text,
+            // a field tag: field number from 1, wire type varint, 64-bit, length or 32-bit
+            Some(&tag) if tag >> 3 > 0 && matches!(tag & 7, 0 | 1 | 2 | 5) => PayloadKind::Protobuf,
+            _ => PayloadKind::Binary,
+        },
+    }
+}
+
+/// Payload that does not decode as JSON, with what the delivery declared and what
+/// the payload looks like.
+#[derive(Debug)]
+pub struct DecodeError {
+    pub source: serde_json::Error,
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    pub sniffed: PayloadKind,
+}
+
+impl DecodeError {
+    /// What most likely went wrong, from the declaration and the payload.
+    pub fn hint(&self) -> String {
+        let encoding = self.content_encoding.as_deref();
+        let content_type = self.content_type.as_deref();
+        match (self.sniffed, content_type) {
+            (PayloadKind::Gzip | PayloadKind::Zstd, _) => match encoding {
+                Some(encoding) => format!("payload is {encoding} encoded, decompress it first"),
+                None => format!(
+                    "payload is {} compressed but declares no content encoding",
+                    self.sniffed
+                ),
+            },
+            (PayloadKind::Empty, _) => "payload is empty".into(),
+            (PayloadKind::Json, Some(ct)) if !is_json(ct) => {
+                format!("payload looks like json but declares content type {ct}")
+            }
+            (PayloadKind::Json, _) => "payload is json of another shape than expected".into(),
+            (sniffed, Some(ct)) if is_json(ct) => {
+                format!("declares content type {ct} but the payload is {sniffed}")
+            }
+            (_, Some(ct)) => format!("declares content type {ct}, not json"),
+            (sniffed, None) => format!("payload is {sniffed} and declares no content type"),
+        }
+    }
+}
+
+fn is_json(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    essence == "application/json" || essence.ends_with("+json")
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "can not decode {} payload as json: {} ({})",
+            self.sniffed,
+            self.source,
+            self.hint()
+        )
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl Delivery {
+    /// Decodes the payload as JSON; failures describe the payload against its headers.
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, DecodeError> {
+        serde_json::from_slice(&self.data).map_err(|source| DecodeError {
+            source,
+            content_type: self
+                .properties
+                .content_type()
+                .as_ref()
+                .map(|s| s.to_string()),
+            content_encoding: self
+                .properties
+                .content_encoding()
+                .as_ref()
+                .map(|s| s.to_string()),
+            sniffed: sniff(&self.data),
+        })
+    }
+}
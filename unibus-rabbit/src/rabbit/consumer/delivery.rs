@@ -0,0 +1,69 @@
+use lapin::{acker::Acker, types::FieldTable, BasicProperties};
+use unibus_core::Envelope;
+
+use crate::rabbit::headers;
+
+/// Message received by a consumer; acknowledged by the consumer runtime from the handler result,
+/// or by a [`ManualAck`](super::ManualAck) handler itself.
+#[derive(Clone, Debug)]
+pub struct Delivery {
+    pub delivery_tag: u64,
+    pub exchange: String,
+    pub routing_key: String,
+    pub redelivered: bool,
+    pub properties: BasicProperties,
+    pub data: Vec<u8>,
+}
+
+impl Delivery {
+    pub(crate) fn from_lapin(delivery: lapin::message::Delivery) -> (Self, Acker) {
+        let lapin::message::Delivery {
+            delivery_tag,
+            exchange,
+            routing_key,
+            redelivered,
+            properties,
+            data,
+            acker,
+        } = delivery;
+        let delivery = Delivery {
+            delivery_tag,
+            exchange: exchange.to_string(),
+            routing_key: routing_key.to_string(),
+            redelivered,
+            properties,
+            data,
+        };
+        (delivery, acker)
+    }
+
+    /// Delivery of `payload` with `headers` on tag 1, for handler tests without a broker.
+    pub fn test(headers: FieldTable, payload: impl Into<Vec<u8>>) -> Self {
+        Delivery {
+            delivery_tag: 1,
+            exchange: String::new(),
+            routing_key: String::new(),
+            redelivered: false,
+            properties: BasicProperties::default().with_headers(headers),
+            data: payload.into(),
+        }
+    }
+}
+
+/// Headers of the delivery become envelope headers, its exchange the destination.
+impl From<Delivery> for Envelope {
+    fn from(delivery: Delivery) -> Self {
+        let properties = &delivery.properties;
+        let content_type = properties.content_type().as_ref().map(|s| s.to_string());
+        let message_type = properties.kind().as_ref().map(|s| s.to_string());
+        let headers = headers::to_map(&headers::headers(properties));
+        Envelope {
+            destination: delivery.exchange,
+            routing_key: delivery.routing_key,
+            payload: delivery.data,
+            content_type: content_type.unwrap_or_default(),
+            message_type: message_type.unwrap_or_default(),
+            headers,
+        }
+    }
+}
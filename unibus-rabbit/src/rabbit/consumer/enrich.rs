@@ -0,0 +1,159 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use lapin::{types::FieldTable, BasicProperties};
+use tracing::warn;
+
+use super::{Ack, AckHandle, Delivery, DeliveryHandler, Nack, Validation};
+use crate::rabbit::headers;
+
+/// Failure of an [`Enricher`].
+pub type EnrichError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Asynchronous lookup annotating a delivery before it is handled, e.g. the
+/// configuration of its tenant or a resolved reference.
+#[async_trait]
+pub trait Enricher: Send + Sync + 'static {
+    /// Key the annotations of `delivery` are cached under, e.g. its tenant id;
+    /// `None` looks them up every time.
+    fn key(&self, _delivery: &Delivery) -> Option<String> {
+        None
+    }
+
+    /// Headers added to `delivery`, replacing headers of the same name.
+    async fn annotate(&self, delivery: &Delivery) -> Result<FieldTable, EnrichError>;
+}
+
+/// What [`Enriched`] does when the enricher fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnrichFailure {
+    /// Hands the delivery over without annotations.
+    Skip,
+    /// Nacks the delivery without handling it, the default with requeue.
+    Fail { requeue: bool },
+}
+
+struct Cache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, (Instant, FieldTable)>>,
+}
+
+impl Cache {
+    fn get(&self, key: &str) -> Option<FieldTable> {
+        let entries = self.entries.lock().unwrap();
+        let (at, annotations) = entries.get(key)?;
+        (at.elapsed() < self.ttl).then(|| annotations.clone())
+    }
+
+    fn insert(&self, key: String, annotations: FieldTable) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries.retain(|_, (at, _)| at.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (at, _))| *at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (Instant::now(), annotations));
+    }
+}
+
+/// Runs an [`Enricher`] before the inner handler, which sees the annotations as
+/// headers, e.g. through [`WithHeaders`](super::WithHeaders). Annotations replace
+/// headers of the same name, so publishers can not forge them.
+pub struct Enriched<E, H> {
+    enricher: E,
+    handler: H,
+    failure: EnrichFailure,
+    cache: Option<Cache>,
+}
+
+impl<E: Enricher, H: DeliveryHandler> Enriched<E, H> {
+    pub fn new(enricher: E, handler: H) -> Self {
+        Enriched {
+            enricher,
+            handler,
+            failure: EnrichFailure::Fail { requeue: true },
+            cache: None,
+        }
+    }
+
+    pub fn with_failure(mut self, failure: EnrichFailure) -> Self {
+        self.failure = failure;
+        self
+    }
+
+    /// Reuses the annotations of a key for `ttl`, keeping at most `capacity` keys.
+    pub fn with_cache(mut self, ttl: Duration, capacity: usize) -> Self {
+        self.cache = Some(Cache {
+            ttl,
+            capacity: capacity.max(1),
+            entries: Default::default(),
+        });
+        self
+    }
+
+    async fn annotations(&self, delivery: &Delivery) -> Result<FieldTable, EnrichError> {
+        let cached = self
+            .cache
+            .as_ref()
+            .and_then(|cache| Some((cache, self.enricher.key(delivery)?)));
+        let Some((cache, key)) = cached else {
+            return self.enricher.annotate(delivery).await;
+        };
+        if let Some(annotations) = cache.get(&key) {
+            return Ok(annotations);
+        }
+        let annotations = self.enricher.annotate(delivery).await?;
+        cache.insert(key, annotations.clone());
+        Ok(annotations)
+    }
+
+    async fn enrich(&self, delivery: &mut Delivery) -> Result<(), Nack> {
+        match self.annotations(delivery).await {
+            Ok(annotations) => {
+                let mut table = headers::headers(&delivery.properties);
+                for (key, value) in annotations.inner() {
+                    table.insert(key.clone(), value.clone());
+                }
+                delivery.properties = delivery.properties.clone().with_headers(table);
+            }
+            Err(e) => {
+                warn!(error = format!("{e}"), "enrichment failed");
+                if let EnrichFailure::Fail { requeue } = self.failure {
+                    return Err(Nack { requeue });
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<E: Enricher, H: DeliveryHandler> DeliveryHandler for Enriched<E, H> {
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        self.handler.validate(properties)
+    }
+
+    async fn handle(&self, mut delivery: Delivery) -> Result<Ack, Nack> {
+        self.enrich(&mut delivery).await?;
+        self.handler.handle(delivery).await
+    }
+
+    async fn handle_with(&self, mut delivery: Delivery, ack: AckHandle) {
+        match self.enrich(&mut delivery).await {
+            Ok(()) => self.handler.handle_with(delivery, ack).await,
+            Err(nack) => ack.settle_or_warn(Err(nack)).await,
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+};
+
+use crate::shutdown::{Shutdown, ShutdownGuard, Stage};
+
+#[derive(Default)]
+struct Inner {
+    connections: BTreeSet<String>,
+    /// Consumers stage guards on the shutdowns of publishers fed with another token.
+    held: Vec<(Shutdown, ShutdownGuard)>,
+    drained: bool,
+}
+
+/// Publishers the handlers of a consumer published through, set as the context of
+/// every handler task.
+///
+/// Confirms of those publishes hold the consumer's shutdown in [`Stage::Publishers`],
+/// so it closes the connection only once they settled. A publisher following another
+/// shutdown is held in [`Stage::Consumers`] until the consumer drained, so it keeps
+/// accepting the publishes of the handlers still running.
+#[derive(Clone)]
+pub(crate) struct Feeds {
+    shutdown: Shutdown,
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Feeds {
+    pub(crate) fn new(shutdown: Shutdown) -> Self {
+        Feeds {
+            shutdown,
+            inner: Default::default(),
+        }
+    }
+
+    /// Records a publish on `connection` by a publisher following `publisher`; the
+    /// guard holds the consumer's shutdown until its confirm settles, `None` when the
+    /// publisher follows the same shutdown and guards it already.
+    pub(crate) fn publish(
+        &self,
+        connection: &str,
+        publisher: Option<&Shutdown>,
+    ) -> Option<ShutdownGuard> {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.connections.contains(connection) {
+            inner.connections.insert(connection.to_owned());
+        }
+        if let Some(publisher) = publisher {
+            if publisher.is_same(&self.shutdown) {
+                return None;
+            }
+            let held = inner.held.iter().any(|(s, _)| s.is_same(publisher));
+            if !inner.drained && !held {
+                let guard = publisher.guard(Stage::Consumers);
+                inner.held.push((publisher.clone(), guard));
+            }
+        }
+        Some(self.shutdown.guard(Stage::Publishers))
+    }
+
+    /// Connections the handlers published on so far.
+    pub(crate) fn connections(&self) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        inner.connections.iter().cloned().collect()
+    }
+
+    /// Releases the publishers held in [`Stage::Consumers`], once no handler runs.
+    pub(crate) fn drained(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.drained = true;
+        inner.held.clear();
+    }
+}
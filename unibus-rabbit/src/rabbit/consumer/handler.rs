@@ -0,0 +1,187 @@
+use std::{future::Future, marker::PhantomData};
+
+use async_trait::async_trait;
+use lapin::BasicProperties;
+use tracing::warn;
+pub use unibus_core::transport::{Ack, Nack};
+
+use super::{AckHandle, Delivery, Settler};
+use crate::rabbit::headers::{self, FromHeaders};
+
+/// Verdict of the cheap validation phase, taken before the payload is handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Validation {
+    /// Handle the delivery.
+    Accept,
+    /// Irrelevant for this consumer: ack without handling.
+    Drop,
+    /// Malformed: reject without requeue, so it dead-letters if configured.
+    Reject,
+}
+
+#[async_trait]
+pub trait DeliveryHandler: Send + Sync + 'static {
+    /// Synchronous check on the message properties, run before a concurrency slot is taken.
+    fn validate(&self, _properties: &BasicProperties) -> Validation {
+        Validation::Accept
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack>;
+
+    /// Handles `delivery` and settles it through `ack`, by default by the result of
+    /// [`DeliveryHandler::handle`]; [`ManualAck`] hands `ack` to its closure instead.
+    /// Handlers wrapping another one forward to its `handle_with`.
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        let outcome = self.handle(delivery).await;
+        ack.settle_or_warn(outcome).await
+    }
+}
+
+/// Handler wrapped by another one, run by its result or, when the wrapper was handed the
+/// ack handle of the delivery, through the [`Settler`] of that handle.
+pub(crate) struct Inner<'a, H: ?Sized> {
+    handler: &'a H,
+    settler: Option<&'a Settler>,
+}
+
+impl<'a, H: DeliveryHandler + ?Sized> Inner<'a, H> {
+    pub(crate) fn new(handler: &'a H, settler: Option<&'a Settler>) -> Self {
+        Inner { handler, settler }
+    }
+
+    pub(crate) async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        match self.settler {
+            Some(settler) => settler.run(self.handler, delivery).await,
+            None => self.handler.handle(delivery).await,
+        }
+    }
+}
+
+#[async_trait]
+impl<F, Fut> DeliveryHandler for F
+where
+    F: Fn(Delivery) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        self(delivery).await
+    }
+}
+
+/// Closure handler settling each delivery itself through its [`AckHandle`], e.g. to
+/// ack a batch at once or to requeue only some failures.
+///
+/// Behind a wrapper acting on results, e.g. [`Retrying`](crate::rabbit::topology::Retrying),
+/// the way the closure settles is taken as its result.
+pub struct ManualAck<F> {
+    handler: F,
+}
+
+impl<F, Fut> ManualAck<F>
+where
+    F: Fn(Delivery, AckHandle) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    pub fn new(handler: F) -> Self {
+        ManualAck { handler }
+    }
+}
+
+#[async_trait]
+impl<F, Fut> DeliveryHandler for ManualAck<F>
+where
+    F: Fn(Delivery, AckHandle) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    async fn handle(&self, _delivery: Delivery) -> Result<Ack, Nack> {
+        warn!("manual ack handler called without its ack handle, requeueing");
+        Err(Nack { requeue: true })
+    }
+
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        (self.handler)(delivery, ack).await
+    }
+}
+
+/// Closure handler with a separate validation phase.
+pub struct TwoPhase<V, H> {
+    validate: V,
+    handler: H,
+}
+
+impl<V, H> TwoPhase<V, H>
+where
+    V: Fn(&BasicProperties) -> Validation + Send + Sync + 'static,
+    H: DeliveryHandler,
+{
+    pub fn new(validate: V, handler: H) -> Self {
+        TwoPhase { validate, handler }
+    }
+}
+
+#[async_trait]
+impl<V, H> DeliveryHandler for TwoPhase<V, H>
+where
+    V: Fn(&BasicProperties) -> Validation + Send + Sync + 'static,
+    H: DeliveryHandler,
+{
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        (self.validate)(properties)
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        self.handler.handle(delivery).await
+    }
+
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        self.handler.handle_with(delivery, ack).await
+    }
+}
+
+/// Closure handler receiving the headers decoded into a context struct `C`.
+/// Deliveries whose headers do not decode are rejected in the validation phase.
+pub struct WithHeaders<C, F> {
+    handler: F,
+    _context: PhantomData<fn() -> C>,
+}
+
+impl<C, F, Fut> WithHeaders<C, F>
+where
+    C: FromHeaders + Send + 'static,
+    F: Fn(Delivery, C) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    pub fn new(handler: F) -> Self {
+        WithHeaders {
+            handler,
+            _context: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<C, F, Fut> DeliveryHandler for WithHeaders<C, F>
+where
+    C: FromHeaders + Send + 'static,
+    F: Fn(Delivery, C) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Ack, Nack>> + Send,
+{
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        match C::from_headers(&headers::headers(properties)) {
+            Ok(_) => Validation::Accept,
+            Err(e) => {
+                warn!(
+                    error = format!("{e}"),
+                    "headers do not match the handler context"
+                );
+                Validation::Reject
+            }
+        }
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        let context = C::from_headers(&headers::headers(&delivery.properties))
+            .map_err(|_| Nack { requeue: false })?;
+        (self.handler)(delivery, context).await
+    }
+}
@@ -0,0 +1,34 @@
+use std::{future::Future, sync::Arc};
+
+use futures::future::BoxFuture;
+
+use crate::shutdown::{Shutdown, Stage};
+
+/// Failure of a lifecycle hook.
+pub type HookError = Box<dyn std::error::Error + Send + Sync>;
+
+pub(crate) type Hook =
+    Arc<dyn Fn(HookContext) -> BoxFuture<'static, Result<(), HookError>> + Send + Sync>;
+
+/// What a lifecycle hook of a consumer gets to see.
+#[derive(Clone)]
+pub struct HookContext {
+    pub queue: String,
+    /// Shutdown token of the consumer, a detached one when it has none.
+    pub shutdown: Shutdown,
+}
+
+impl HookContext {
+    /// Whether the consumer is stopping because of a shutdown rather than a cancel.
+    pub fn shutting_down(&self) -> bool {
+        self.shutdown.reached(Stage::Consumers)
+    }
+}
+
+pub(crate) fn hook<F, Fut>(hook: F) -> Hook
+where
+    F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), HookError>> + Send + 'static,
+{
+    Arc::new(move |context| Box::pin(hook(context)))
+}
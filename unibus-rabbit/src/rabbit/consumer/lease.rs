@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use lapin::BasicProperties;
+use serde::Serialize;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, warn, Instrument};
+
+use super::{Ack, AckHandle, Delivery, DeliveryHandler, Inner, Nack, Settler, Validation};
+use crate::rabbit::{OutgoingMessage, Publisher};
+
+type Outcome = Option<Result<Ack, Nack>>;
+
+#[derive(Serialize)]
+struct Heartbeat<'a> {
+    message_id: &'a str,
+    elapsed_ms: u64,
+}
+
+struct HeartbeatTarget {
+    publisher: Publisher,
+    exchange: String,
+    routing_key: String,
+}
+
+/// Lease mode for handlers that legitimately outlive a sensible unacked window.
+///
+/// While the handler runs, a progress heartbeat is published every `interval`, in the
+/// background so a slow publish never holds up the handler; a tick is skipped while
+/// the previous heartbeat is still in flight.
+/// When the connection drops, the broker redelivers the message to a new consumer;
+/// a redelivered copy whose message id is still running here waits for the original
+/// and settles with its outcome instead of being handled twice. Clones share the
+/// running set, so pass a clone to the consumer started after a reconnect.
+pub struct Leased<H> {
+    handler: Arc<H>,
+    interval: Duration,
+    heartbeat: Option<Arc<HeartbeatTarget>>,
+    running: Arc<Mutex<HashMap<String, watch::Receiver<Outcome>>>>,
+}
+
+impl<H> Clone for Leased<H> {
+    fn clone(&self) -> Self {
+        Leased {
+            handler: self.handler.clone(),
+            interval: self.interval,
+            heartbeat: self.heartbeat.clone(),
+            running: self.running.clone(),
+        }
+    }
+}
+
+impl<H: DeliveryHandler> Leased<H> {
+    pub fn new(handler: H, interval: Duration) -> Self {
+        Leased {
+            handler: Arc::new(handler),
+            interval,
+            heartbeat: None,
+            running: Default::default(),
+        }
+    }
+
+    /// Publishes `{message_id, elapsed_ms}` heartbeats to `exchange` with `routing_key`.
+    pub fn with_heartbeat(
+        mut self,
+        publisher: Publisher,
+        exchange: impl Into<String>,
+        routing_key: impl Into<String>,
+    ) -> Self {
+        self.heartbeat = Some(Arc::new(HeartbeatTarget {
+            publisher,
+            exchange: exchange.into(),
+            routing_key: routing_key.into(),
+        }));
+        self
+    }
+
+    fn beat(&self, message_id: &str, started: Instant) -> Option<JoinHandle<()>> {
+        let target = self.heartbeat.clone()?;
+        let body = Heartbeat {
+            message_id,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        };
+        let message = OutgoingMessage::json(&target.exchange, &target.routing_key, &body);
+        let message_id = message_id.to_owned();
+        let beat = async move {
+            let res = match message {
+                Ok(message) => target.publisher.publish(message).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = res {
+                warn!(error = format!("{e}"), message_id, "lease heartbeat failed");
+            }
+        };
+        Some(tokio::spawn(beat.in_current_span()))
+    }
+
+    async fn run(
+        &self,
+        delivery: Delivery,
+        message_id: &str,
+        inner: &Inner<'_, H>,
+    ) -> Result<Ack, Nack> {
+        let started = Instant::now();
+        let handle = inner.handle(delivery);
+        tokio::pin!(handle);
+        let mut ticks =
+            tokio::time::interval_at(tokio::time::Instant::now() + self.interval, self.interval);
+        let mut beating: Option<JoinHandle<()>> = None;
+        loop {
+            tokio::select! {
+                outcome = &mut handle => return outcome,
+                _ = ticks.tick() => {
+                    if beating.as_ref().is_none_or(JoinHandle::is_finished) {
+                        beating = self.beat(message_id, started);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn lease(&self, delivery: Delivery, inner: Inner<'_, H>) -> Result<Ack, Nack> {
+        let Some(message_id) = delivery
+            .properties
+            .message_id()
+            .as_ref()
+            .map(|id| id.to_string())
+        else {
+            return self.run(delivery, "", &inner).await;
+        };
+        let tx = {
+            let mut running = self.running.lock().unwrap();
+            match running.get(&message_id) {
+                Some(original) => Err(original.clone()),
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    running.insert(message_id.clone(), rx);
+                    Ok(tx)
+                }
+            }
+        };
+        let tx = match tx {
+            Ok(tx) => tx,
+            Err(mut original) => {
+                debug!(message_id, "redelivered while the original still runs");
+                return match original.wait_for(Option::is_some).await {
+                    Ok(outcome) => outcome.expect("waited for some"),
+                    Err(_) => Err(Nack { requeue: true }),
+                };
+            }
+        };
+        let _entry = Entry {
+            running: &self.running,
+            message_id: &message_id,
+        };
+        let outcome = self.run(delivery, &message_id, &inner).await;
+        _ = tx.send(Some(outcome));
+        outcome
+    }
+}
+
+// removes the running entry even if the handler panics
+struct Entry<'a> {
+    running: &'a Mutex<HashMap<String, watch::Receiver<Outcome>>>,
+    message_id: &'a str,
+}
+
+impl Drop for Entry<'_> {
+    fn drop(&mut self) {
+        self.running.lock().unwrap().remove(self.message_id);
+    }
+}
+
+#[async_trait]
+impl<H: DeliveryHandler> DeliveryHandler for Leased<H> {
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        self.handler.validate(properties)
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        self.lease(delivery, Inner::new(&*self.handler, None)).await
+    }
+
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        let settler = Settler::new(ack);
+        let outcome = self
+            .lease(delivery, Inner::new(&*self.handler, Some(&settler)))
+            .await;
+        settler.finish(outcome).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use lapin::types::FieldTable;
+    use tokio::sync::Notify;
+
+    use super::*;
+    use crate::rabbit::{Channel, ChannelSource, RabbitError};
+
+    /// Source whose channels never finish opening.
+    #[derive(Clone)]
+    struct Stalled;
+
+    #[async_trait]
+    impl ChannelSource for Stalled {
+        async fn create_channel(&self) -> Result<Channel, RabbitError> {
+            std::future::pending().await
+        }
+
+        async fn ready(&self) -> Result<(), RabbitError> {
+            Ok(())
+        }
+
+        fn share(&self) -> Arc<dyn ChannelSource> {
+            Arc::new(self.clone())
+        }
+    }
+
+    fn delivery(message_id: &str) -> Delivery {
+        let mut delivery = Delivery::test(FieldTable::default(), "{}");
+        delivery.properties = delivery.properties.with_message_id(message_id.into());
+        delivery
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn heartbeats_do_not_hold_up_the_handler() {
+        let handler = |_| async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(Ack)
+        };
+        let leased = Leased::new(handler, Duration::from_millis(10)).with_heartbeat(
+            Publisher::new(Stalled),
+            "progress",
+            "lease",
+        );
+        let outcome = tokio::time::timeout(Duration::from_millis(60), leased.handle(delivery("m")));
+        assert_eq!(outcome.await, Ok(Ok(Ack)));
+    }
+
+    #[tokio::test]
+    async fn redeliveries_settle_with_the_running_original() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let release = Arc::new(Notify::new());
+        let handler = {
+            let calls = calls.clone();
+            let release = release.clone();
+            move |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let release = release.clone();
+                async move {
+                    release.notified().await;
+                    Err(Nack { requeue: false })
+                }
+            }
+        };
+        let leased = Leased::new(handler, Duration::from_secs(60));
+        let original = tokio::spawn({
+            let leased = leased.clone();
+            async move { leased.handle(delivery("m")).await }
+        });
+        while calls.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        let redelivered = tokio::spawn({
+            let leased = leased.clone();
+            async move { leased.handle(delivery("m")).await }
+        });
+        tokio::task::yield_now().await;
+        release.notify_one();
+        assert_eq!(original.await.unwrap(), Err(Nack { requeue: false }));
+        assert_eq!(redelivered.await.unwrap(), Err(Nack { requeue: false }));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(leased.running.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn settled_message_ids_are_handled_again() {
+        let calls = Arc::new(AtomicU32::new(0));
+        let handler = {
+            let calls = calls.clone();
+            move |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Ok(Ack) }
+            }
+        };
+        let leased = Leased::new(handler, Duration::from_secs(60));
+        assert_eq!(leased.handle(delivery("m")).await, Ok(Ack));
+        assert_eq!(leased.handle(delivery("m")).await, Ok(Ack));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Byte budget for payloads held by running handlers, shareable between consumers.
+///
+/// A consumer stops taking deliveries off its stream while the budget is spent,
+/// so memory stays bounded even with generous prefetch counts.
+/// A payload larger than the whole budget waits until it can take all of it.
+#[derive(Clone, Debug)]
+pub struct MemoryBudget {
+    bytes: u32,
+    available: Arc<Semaphore>,
+}
+
+impl MemoryBudget {
+    pub fn new(bytes: u32) -> Self {
+        MemoryBudget {
+            bytes,
+            available: Arc::new(Semaphore::new(bytes as usize)),
+        }
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.bytes
+    }
+
+    pub fn in_use(&self) -> usize {
+        self.bytes as usize - self.available.available_permits()
+    }
+
+    pub(crate) async fn reserve(&self, len: usize) -> OwnedSemaphorePermit {
+        let len = u32::try_from(len).unwrap_or(u32::MAX).min(self.bytes);
+        self.available
+            .clone()
+            .acquire_many_owned(len)
+            .await
+            .expect("memory budget semaphore is never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn caps_payloads_larger_than_the_budget() {
+        let budget = MemoryBudget::new(100);
+        let permit = budget.reserve(4096).await;
+        assert_eq!(permit.num_permits(), 100);
+        assert_eq!(budget.in_use(), 100);
+
+        let waiting = tokio::spawn({
+            let budget = budget.clone();
+            async move { budget.reserve(1).await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        drop(permit);
+        let permit = waiting.await.unwrap();
+        assert_eq!(budget.in_use(), 1);
+        drop(permit);
+        assert_eq!(budget.in_use(), 0);
+    }
+
+    #[tokio::test]
+    async fn releases_the_payload_on_drop() {
+        let budget = MemoryBudget::new(100);
+        let first = budget.reserve(30).await;
+        let second = budget.reserve(50).await;
+        assert_eq!(budget.in_use(), 80);
+
+        drop(first);
+        assert_eq!(budget.in_use(), 50);
+        drop(second);
+        assert_eq!(budget.in_use(), 0);
+    }
+}
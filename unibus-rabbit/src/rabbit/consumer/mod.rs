@@ -0,0 +1,473 @@
+mod ack;
+mod browse;
+mod context;
+mod dead_letter;
+mod decode;
+mod delivery;
+mod enrich;
+mod feeds;
+mod handler;
+mod hooks;
+mod lease;
+mod memory;
+mod options;
+mod relay;
+mod router;
+mod sink;
+mod slow_start;
+mod standby;
+mod status;
+mod stream;
+mod unbatch;
+
+use std::{
+    panic::AssertUnwindSafe,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{FutureExt, StreamExt};
+use lapin::options::{
+    BasicAckOptions, BasicCancelOptions, BasicConsumeOptions, BasicRejectOptions,
+};
+use tokio::{sync::Semaphore, task::JoinHandle};
+use tracing::{debug, error, field::Empty, info, trace_span, warn, Instrument};
+
+pub(crate) use ack::Settler;
+pub use ack::{AckHandle, AckToken};
+pub use context::{DeliveryContext, Extensions, WithContext};
+pub use dead_letter::{DeadLetter, DeadLetterConsumer, Death};
+pub use decode::{sniff, DecodeError, PayloadKind};
+pub use delivery::*;
+pub use enrich::{EnrichError, EnrichFailure, Enriched, Enricher};
+pub(crate) use feeds::Feeds;
+pub(crate) use handler::Inner;
+pub use handler::*;
+pub use hooks::{HookContext, HookError};
+pub use lease::Leased;
+pub use memory::MemoryBudget;
+pub use options::*;
+pub use relay::{process_and_publish, ProcessAndPublish};
+pub use router::Router;
+pub use sink::{BatchSink, FlushError};
+pub use slow_start::SlowStart;
+pub use standby::Standby;
+pub(crate) use status::ConsumerProbe;
+pub use status::ConsumerStatus;
+pub use stream::*;
+pub use unbatch::Unbatched;
+
+use super::{
+    headers, metrics, otel,
+    propagation::{self, Correlation},
+    telemetry, Channel, ChannelPurpose, ChannelSource, Connection, DependentGuard, RabbitError,
+};
+use crate::shutdown::Stage;
+
+const RESUBSCRIBE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Channel and tag of the current subscription, replaced when it is re-created.
+struct Live {
+    channel: Channel,
+    tag: String,
+}
+
+/// Running subscription of a handler to a queue.
+///
+/// When the channel or connection fails the subscription is re-created on a new
+/// channel as soon as the source is ready again, until cancelled or shut down.
+pub struct Consumer {
+    live: Arc<Mutex<Live>>,
+    cancelled: Arc<AtomicBool>,
+    probe: ConsumerProbe,
+    task: JoinHandle<()>,
+    _dependent: Option<DependentGuard>,
+}
+
+impl Consumer {
+    /// Subscribes `handler` to `options.queue`; on a strict source the queue must have
+    /// a declared binding.
+    pub async fn start(
+        source: &dyn ChannelSource,
+        options: ConsumerOptions,
+        handler: impl DeliveryHandler,
+    ) -> Result<Self, RabbitError> {
+        source.ensure_bound(&options.queue)?;
+        tokio::time::sleep(super::jitter(options.startup_jitter)).await;
+        let channel = Self::open(source, &options).await?;
+        Self::consume(source, channel, options, Arc::new(handler)).await
+    }
+
+    pub(crate) async fn open(
+        source: &dyn ChannelSource,
+        options: &ConsumerOptions,
+    ) -> Result<Channel, RabbitError> {
+        let preset = source
+            .preset(ChannelPurpose::Consume)
+            .with_prefetch(options.prefetch);
+        source
+            .create_channel_with(ChannelPurpose::Consume, preset)
+            .await
+    }
+
+    async fn subscribe(
+        channel: &Channel,
+        options: &ConsumerOptions,
+    ) -> Result<lapin::Consumer, RabbitError> {
+        Ok(channel
+            .basic_consume(
+                &options.queue,
+                "",
+                BasicConsumeOptions {
+                    exclusive: options.exclusive,
+                    ..Default::default()
+                },
+                options.arguments.clone(),
+            )
+            .await?)
+    }
+
+    pub(crate) async fn consume<H: DeliveryHandler>(
+        source: &dyn ChannelSource,
+        channel: Channel,
+        mut options: ConsumerOptions,
+        handler: Arc<H>,
+    ) -> Result<Self, RabbitError> {
+        source.ensure_bound(&options.queue)?;
+        if options.shutdown.is_none() {
+            options.shutdown = source.shutdown();
+        }
+        if let Some(on_start) = &options.on_start {
+            on_start(options.hook_context())
+                .await
+                .map_err(RabbitError::Hook)?;
+        }
+        let consumer = Self::subscribe(&channel, &options).await?;
+        let tag = consumer.tag().to_string();
+        let dependent = source.hold_exclusive(&channel, &options.queue, &format!("consumer {tag}"));
+        let live = Arc::new(Mutex::new(Live { channel, tag }));
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let probe = ConsumerProbe::new(
+            options.queue.clone(),
+            options.prefetch,
+            options.concurrency.max(1),
+            live.clone(),
+            cancelled.clone(),
+            Feeds::new(options.shutdown.clone().unwrap_or_default()),
+        );
+        let span = trace_span!("consumer", queue = options.queue);
+        let supervisor = Supervisor {
+            source: source.share(),
+            live: live.clone(),
+            cancelled: cancelled.clone(),
+            probe: probe.clone(),
+        };
+        let task = tokio::spawn(supervisor.run(consumer, handler, options).instrument(span));
+        Ok(Consumer {
+            live,
+            cancelled,
+            probe,
+            task,
+            _dependent: dependent,
+        })
+    }
+
+    /// Tag of the current subscription; it changes when the subscription is re-created.
+    pub fn tag(&self) -> String {
+        self.live.lock().unwrap().tag.clone()
+    }
+
+    /// Prefetch, handlers in flight and the current subscription.
+    pub fn status(&self) -> ConsumerStatus {
+        self.probe.status()
+    }
+
+    pub(crate) fn probe(&self) -> ConsumerProbe {
+        self.probe.clone()
+    }
+
+    /// Stops handling deliveries once the handlers in flight finish; up to `prefetch`
+    /// deliveries wait unacknowledged until [`Consumer::resume`].
+    pub async fn pause(&self) {
+        self.probe.pause().await
+    }
+
+    pub fn resume(&self) {
+        self.probe.resume()
+    }
+
+    /// Stops receiving new deliveries; handlers already running complete on their own.
+    pub async fn cancel(self) -> Result<(), RabbitError> {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.probe.resume();
+        let (channel, tag) = {
+            let live = self.live.lock().unwrap();
+            (live.channel.clone(), live.tag.clone())
+        };
+        let res = channel
+            .basic_cancel(&tag, BasicCancelOptions::default())
+            .await;
+        _ = self.task.await;
+        // a subscription already lost with its channel is cancelled as well
+        match res {
+            Err(_) if !channel.status().connected() => Ok(()),
+            res => Ok(res?),
+        }
+    }
+}
+
+impl Connection {
+    /// Consumes `queue` on a channel of its own, acking or nacking each delivery from the
+    /// result of `handler`, e.g. an `async` closure `Fn(Delivery) -> Result<Ack, Nack>`.
+    /// See [`Consumer::start`] with [`ConsumerOptions`] for the other settings.
+    pub async fn consume(
+        &self,
+        queue: impl Into<String>,
+        prefetch: u16,
+        handler: impl DeliveryHandler,
+    ) -> Result<Consumer, RabbitError> {
+        let options = ConsumerOptions::new(queue).with_prefetch(prefetch);
+        Consumer::start(self, options, handler).await
+    }
+}
+
+struct Supervisor {
+    source: Arc<dyn ChannelSource>,
+    live: Arc<Mutex<Live>>,
+    cancelled: Arc<AtomicBool>,
+    probe: ConsumerProbe,
+}
+
+impl Supervisor {
+    async fn run<H: DeliveryHandler>(
+        self,
+        mut consumer: lapin::Consumer,
+        handler: Arc<H>,
+        options: ConsumerOptions,
+    ) {
+        let concurrency = options.concurrency.max(1);
+        let slots = self.probe.slots.clone();
+        let feeds = self.probe.feeds.clone();
+        let shutdown = options.shutdown.clone().unwrap_or_default();
+        let _guard = shutdown.guard(Stage::Consumers);
+        let connection = self.source.name();
+        let mut ramp: Option<JoinHandle<()>> = None;
+        loop {
+            let lost = tokio::select! {
+                _ = shutdown.wait(Stage::Consumers) => false,
+                lost = deliver(&mut consumer, &handler, &options, &slots, &feeds, &connection) => {
+                    lost
+                }
+            };
+            self.stop_ramp(ramp.take());
+            if !lost || self.cancelled.load(Ordering::SeqCst) {
+                break;
+            }
+            warn!("subscription lost, re-creating it once the connection is back");
+            let resubscribed = tokio::select! {
+                _ = shutdown.wait(Stage::Consumers) => None,
+                next = self.resubscribe(&options) => next,
+            };
+            match resubscribed {
+                Some(next) => consumer = next,
+                None => break,
+            }
+            if let Some(slow_start) = options.slow_start {
+                let channel = self.live.lock().unwrap().channel.clone();
+                ramp = Some(tokio::spawn(
+                    slow_start
+                        .ramp(channel, self.probe.clone())
+                        .in_current_span(),
+                ));
+            }
+            if self.cancelled.load(Ordering::SeqCst) {
+                let (channel, tag) = {
+                    let live = self.live.lock().unwrap();
+                    (live.channel.clone(), live.tag.clone())
+                };
+                _ = channel
+                    .basic_cancel(&tag, BasicCancelOptions::default())
+                    .await;
+                break;
+            }
+        }
+        if shutdown.reached(Stage::Consumers) && !self.cancelled.load(Ordering::SeqCst) {
+            // stop the broker from pushing; unhandled prefetched deliveries are requeued
+            let (channel, tag) = {
+                let live = self.live.lock().unwrap();
+                (live.channel.clone(), live.tag.clone())
+            };
+            _ = channel
+                .basic_cancel(&tag, BasicCancelOptions::default())
+                .await;
+        }
+        // wait for in-flight handlers before releasing the shutdown guard
+        self.probe.resume();
+        _ = slots.acquire_many(concurrency as u32).await;
+        feeds.drained();
+        if let Some(on_drain) = &options.on_drain {
+            if let Err(e) = on_drain(options.hook_context()).await {
+                warn!(error = format!("{e}"), "drain hook failed");
+            }
+        }
+    }
+
+    /// Channel and subscription, limited to the initial prefetch of the slow start if any.
+    async fn reopen(
+        &self,
+        options: &ConsumerOptions,
+    ) -> Result<(Channel, lapin::Consumer), RabbitError> {
+        let channel = Consumer::open(self.source.as_ref(), options).await?;
+        if let Some(slow_start) = &options.slow_start {
+            slow_start.begin(&channel, options.prefetch).await?;
+        }
+        let consumer = Consumer::subscribe(&channel, options).await?;
+        Ok((channel, consumer))
+    }
+
+    fn stop_ramp(&self, ramp: Option<JoinHandle<()>>) {
+        if let Some(ramp) = ramp {
+            ramp.abort();
+            self.probe.unthrottle(0);
+        }
+    }
+
+    /// New subscription on a new channel; `None` when the source is closed for good.
+    async fn resubscribe(&self, options: &ConsumerOptions) -> Option<lapin::Consumer> {
+        loop {
+            if let Err(e) = self.source.ready().await {
+                warn!(error = format!("{e}"), "consumer source gone, giving up");
+                return None;
+            }
+            match self.reopen(options).await {
+                Ok((channel, consumer)) => {
+                    let tag = consumer.tag().to_string();
+                    info!(tag, "subscription re-created");
+                    *self.live.lock().unwrap() = Live { channel, tag };
+                    return Some(consumer);
+                }
+                Err(e) => {
+                    warn!(error = format!("{e}"), "re-subscribe failed");
+                    tokio::time::sleep(RESUBSCRIBE_BACKOFF).await;
+                }
+            }
+        }
+    }
+}
+
+/// Hands deliveries to `handler` until the subscription ends; `true` when it was lost
+/// or cancelled rather than shut down.
+async fn deliver<H: DeliveryHandler>(
+    consumer: &mut lapin::Consumer,
+    handler: &Arc<H>,
+    options: &ConsumerOptions,
+    slots: &Arc<Semaphore>,
+    feeds: &Feeds,
+    connection: &str,
+) -> bool {
+    loop {
+        let delivery = match consumer.next().await {
+            Some(Ok(delivery)) => delivery,
+            Some(Err(e)) => {
+                error!(error = format!("{e}"), "consumer failed");
+                return true;
+            }
+            None => return true,
+        };
+        let (delivery, acker) = Delivery::from_lapin(delivery);
+        metrics::delivery("unibus_consumed_total", connection, &options.queue);
+        let oversized = options
+            .size_limit
+            .and_then(|limit| limit.check(delivery.data.len(), &options.queue).err());
+        let validation = match oversized {
+            Some(e) => {
+                warn!(error = format!("{e}"), "delivery quarantined");
+                Validation::Reject
+            }
+            None if !options.accepts(&delivery.properties) => Validation::Drop,
+            None => match options.stale(&delivery.properties) {
+                Some(age) => {
+                    debug!(age = format!("{age:?}"), "stale delivery discarded");
+                    metrics::delivery("unibus_stale_discarded_total", connection, &options.queue);
+                    if let Some(on_stale) = &options.on_stale {
+                        on_stale(&delivery, age);
+                    }
+                    Validation::Drop
+                }
+                None => handler.validate(&delivery.properties),
+            },
+        };
+        let early = match validation {
+            Validation::Accept => None,
+            Validation::Drop => Some(acker.ack(BasicAckOptions::default()).await),
+            Validation::Reject => Some(acker.reject(BasicRejectOptions { requeue: false }).await),
+        };
+        if let Some(res) = early {
+            if let Err(e) = res {
+                warn!(error = format!("{e}"), "acknowledge failed");
+            }
+            continue;
+        }
+        let reserved = match &options.memory_budget {
+            Some(budget) => Some(budget.reserve(delivery.data.len()).await),
+            None => None,
+        };
+        let permit = slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("consumer slots are never closed");
+        let handler = handler.clone();
+        let (connection, queue) = (connection.to_owned(), options.queue.clone());
+        let on_panic = Nack {
+            requeue: options.requeue_on_error,
+        };
+        let feeds = feeds.clone();
+        let table = headers::headers(&delivery.properties);
+        let correlation = Correlation::from_headers(&table).unwrap_or_default();
+        let span = trace_span!(
+            "delivery",
+            trace_id = correlation.trace_id,
+            request_id = correlation.request_id,
+            messaging.system = Empty,
+            messaging.destination = Empty,
+            messaging.operation = Empty,
+            otel.kind = Empty,
+            payload = Empty,
+        );
+        otel::consume(&span, &table, &queue);
+        if let Some(payload) = telemetry::sample_payload(&delivery.data) {
+            span.record("payload", payload);
+        }
+        tokio::spawn(
+            propagation::scope(correlation, async move {
+                let _permit = (permit, reserved);
+                let started = Instant::now();
+                // settles the delivery when the handler panicked before it did
+                let backstop = acker.clone();
+                let ack = AckHandle::new(
+                    acker,
+                    delivery.delivery_tag,
+                    connection.clone(),
+                    queue.clone(),
+                );
+                let handled = propagation::scope(feeds, handler.handle_with(delivery, ack));
+                let panicked = AssertUnwindSafe(handled).catch_unwind().await.is_err();
+                metrics::handled(&connection, &queue, started.elapsed());
+                if panicked {
+                    error!(requeue = on_panic.requeue, "handler panicked");
+                }
+                if panicked && !backstop.used() {
+                    let ack = AckHandle::new(backstop, 0, connection, queue);
+                    if let Err(e) = ack.settle(Err(on_panic)).await {
+                        warn!(error = format!("{e}"), "acknowledge failed");
+                    }
+                }
+            })
+            .instrument(span),
+        );
+    }
+}
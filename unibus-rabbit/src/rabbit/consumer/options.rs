@@ -0,0 +1,284 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use lapin::{
+    types::{AMQPValue, FieldArray, FieldTable},
+    BasicProperties,
+};
+
+use super::{
+    hooks::{self, Hook, HookContext, HookError},
+    Delivery, MemoryBudget, SlowStart,
+};
+use crate::rabbit::{headers, SizeLimit};
+use crate::shutdown::Shutdown;
+
+/// Where a consumer of a stream queue starts reading.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamOffset {
+    /// The oldest message still retained.
+    First,
+    /// The last chunk of messages written.
+    Last,
+    /// Only messages written after subscribing, the default.
+    Next,
+    /// Messages written from this unix time in seconds on.
+    Timestamp(u64),
+    /// Messages from this offset on.
+    Offset(u64),
+}
+
+impl From<StreamOffset> for AMQPValue {
+    fn from(offset: StreamOffset) -> Self {
+        match offset {
+            StreamOffset::First => AMQPValue::LongString("first".into()),
+            StreamOffset::Last => AMQPValue::LongString("last".into()),
+            StreamOffset::Next => AMQPValue::LongString("next".into()),
+            StreamOffset::Timestamp(secs) => AMQPValue::Timestamp(secs),
+            StreamOffset::Offset(offset) => AMQPValue::LongLongInt(offset as i64),
+        }
+    }
+}
+
+/// Header a publisher sets for stream filtering, see
+/// [`OutgoingMessage::with_stream_filter_value`](crate::rabbit::OutgoingMessage::with_stream_filter_value).
+pub const STREAM_FILTER_VALUE: &str = "x-stream-filter-value";
+
+/// Filter values a stream consumer asks for (RabbitMQ 3.13+).
+///
+/// The broker skips only the chunks without any matching message, so the consumer
+/// still receives some messages of other values; [`StreamFilter::matches`] is the
+/// client-side step dropping them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StreamFilter {
+    pub values: Vec<String>,
+    /// Also deliver messages published without a filter value.
+    pub match_unfiltered: bool,
+}
+
+impl StreamFilter {
+    pub fn new(values: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        StreamFilter {
+            values: values.into_iter().map(Into::into).collect(),
+            match_unfiltered: false,
+        }
+    }
+
+    pub fn with_match_unfiltered(mut self, match_unfiltered: bool) -> Self {
+        self.match_unfiltered = match_unfiltered;
+        self
+    }
+
+    /// Whether a message with `properties` passes the filter.
+    pub fn matches(&self, properties: &BasicProperties) -> bool {
+        match headers::get_str(&headers::headers(properties), STREAM_FILTER_VALUE) {
+            Some(value) => self.values.contains(&value),
+            None => self.match_unfiltered,
+        }
+    }
+
+    fn arguments(&self, arguments: &mut FieldTable) {
+        let values = self
+            .values
+            .iter()
+            .map(|value| AMQPValue::LongString(value.as_str().into()))
+            .collect::<Vec<_>>();
+        arguments.insert(
+            "x-stream-filter".into(),
+            AMQPValue::FieldArray(FieldArray::from(values)),
+        );
+        arguments.insert(
+            "x-stream-match-unfiltered".into(),
+            AMQPValue::Boolean(self.match_unfiltered),
+        );
+    }
+}
+
+/// Callback of [`ConsumerOptions::with_on_stale`].
+pub(crate) type StaleHook = Arc<dyn Fn(&Delivery, Duration) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct ConsumerOptions {
+    pub queue: String,
+    pub prefetch: u16,
+    pub concurrency: usize,
+    /// Whether a delivery whose handler panicked is requeued rather than rejected.
+    pub requeue_on_error: bool,
+    /// Asks the broker for exclusive access: no other consumer may subscribe to the queue.
+    pub exclusive: bool,
+    pub shutdown: Option<Shutdown>,
+    pub startup_jitter: Duration,
+    pub memory_budget: Option<MemoryBudget>,
+    pub size_limit: Option<SizeLimit>,
+    pub stream_filter: Option<StreamFilter>,
+    pub slow_start: Option<SlowStart>,
+    /// Deliveries published longer ago are acked and dropped without reaching the handler.
+    pub max_age: Option<Duration>,
+    /// Arguments of `basic.consume`.
+    pub arguments: FieldTable,
+    pub(crate) on_start: Option<Hook>,
+    pub(crate) on_drain: Option<Hook>,
+    pub(crate) on_stale: Option<StaleHook>,
+}
+
+impl ConsumerOptions {
+    pub fn new(queue: impl Into<String>) -> Self {
+        ConsumerOptions {
+            queue: queue.into(),
+            prefetch: 10,
+            concurrency: 1,
+            requeue_on_error: false,
+            exclusive: false,
+            shutdown: None,
+            startup_jitter: Duration::ZERO,
+            memory_budget: None,
+            size_limit: None,
+            stream_filter: None,
+            slow_start: None,
+            max_age: None,
+            arguments: Default::default(),
+            on_start: None,
+            on_drain: None,
+            on_stale: None,
+        }
+    }
+
+    pub fn with_prefetch(mut self, prefetch: u16) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    /// Stops fetching at [`Stage::Consumers`](crate::shutdown::Stage) and drains in-flight handlers.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Delays subscribing by a random duration up to `max`.
+    pub fn with_startup_jitter(mut self, max: Duration) -> Self {
+        self.startup_jitter = max;
+        self
+    }
+
+    pub fn with_memory_budget(mut self, budget: MemoryBudget) -> Self {
+        self.memory_budget = Some(budget);
+        self
+    }
+
+    /// Quarantines deliveries past the reject threshold: they are rejected without
+    /// requeue, so they land in the dead letter exchange of the queue if it has one.
+    pub fn with_size_limit(mut self, limit: SizeLimit) -> Self {
+        self.size_limit = Some(limit);
+        self
+    }
+
+    /// Ramps prefetch and concurrency up by `slow_start` whenever the subscription is
+    /// re-created after a reconnect; the first subscription starts at full speed.
+    pub fn with_slow_start(mut self, slow_start: SlowStart) -> Self {
+        self.slow_start = Some(slow_start);
+        self
+    }
+
+    /// Acks and drops deliveries whose timestamp property is older than `age` when they
+    /// arrive, counted in `unibus_stale_discarded_total`; for telemetry-style data that is
+    /// useless late, so a backlog clears itself. Messages without a timestamp are kept.
+    pub fn with_max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    /// Runs `audit` with each delivery dropped by [`ConsumerOptions::with_max_age`] and
+    /// its age, e.g. to record an audit event.
+    pub fn with_on_stale(
+        mut self,
+        audit: impl Fn(&Delivery, Duration) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_stale = Some(Arc::new(audit));
+        self
+    }
+
+    /// Age of a delivery with `properties` when it is past the maximum age.
+    pub(crate) fn stale(&self, properties: &BasicProperties) -> Option<Duration> {
+        let max_age = self.max_age?;
+        let published = UNIX_EPOCH + Duration::from_secs((*properties.timestamp())?);
+        let age = SystemTime::now().duration_since(published).ok()?;
+        (age > max_age).then_some(age)
+    }
+
+    pub fn with_argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.arguments.insert(key.into(), value);
+        self
+    }
+
+    /// Starting point in a stream queue. A subscription re-created after a reconnect
+    /// starts from this offset again.
+    pub fn with_stream_offset(self, offset: StreamOffset) -> Self {
+        self.with_argument("x-stream-offset", offset.into())
+    }
+
+    /// Consumes only the messages of a stream queue matching `filter`; the others are
+    /// acknowledged without reaching the handler.
+    pub fn with_stream_filter(mut self, filter: StreamFilter) -> Self {
+        filter.arguments(&mut self.arguments);
+        self.stream_filter = Some(filter);
+        self
+    }
+
+    /// `false` for deliveries the stream filter drops.
+    pub(crate) fn accepts(&self, properties: &BasicProperties) -> bool {
+        self.stream_filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(properties))
+    }
+
+    /// Runs `hook` before subscribing, e.g. to prime caches; when it fails the
+    /// consumer does not start. Not run again when the subscription is re-created.
+    pub fn with_on_start<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), HookError>> + Send + 'static,
+    {
+        self.on_start = Some(hooks::hook(hook));
+        self
+    }
+
+    /// Runs `hook` once the consumer stopped and its in-flight handlers finished,
+    /// e.g. to flush batch writers; shutdown waits for it.
+    pub fn with_on_drain<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(HookContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), HookError>> + Send + 'static,
+    {
+        self.on_drain = Some(hooks::hook(hook));
+        self
+    }
+
+    pub(crate) fn hook_context(&self) -> HookContext {
+        HookContext {
+            queue: self.queue.clone(),
+            shutdown: self.shutdown.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Requeues deliveries whose handler panicked instead of rejecting them, which
+    /// dead-letters them if the queue is configured to.
+    pub fn with_requeue_on_error(mut self, requeue: bool) -> Self {
+        self.requeue_on_error = requeue;
+        self
+    }
+
+    /// Subscribes as the only consumer of the queue; fails with `ACCESS_REFUSED` while
+    /// another consumer holds it.
+    pub fn with_exclusive(mut self, exclusive: bool) -> Self {
+        self.exclusive = exclusive;
+        self
+    }
+}
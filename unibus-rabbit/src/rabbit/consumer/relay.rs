@@ -0,0 +1,135 @@
+use std::future::Future;
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use tracing::warn;
+
+use super::{Ack, Delivery, DeliveryHandler, Nack};
+use crate::rabbit::{OutgoingMessage, PublishGuarantee, Publisher, RabbitError};
+
+/// Handler for "consume M, publish N results, then ack M", see [`process_and_publish`].
+pub struct ProcessAndPublish<F> {
+    publisher: Publisher,
+    process: F,
+}
+
+/// Handles a delivery with `process` and publishes the messages it returns; the
+/// delivery is acked only once the broker confirmed every one of them.
+///
+/// Failed publishes are retried by the [`RetryPolicy`](crate::rabbit::RetryPolicy) of
+/// the publisher, if it has one; once it gives up, the delivery is requeued and
+/// processed again. Results without a message id get `<delivery message id>/<index>`,
+/// so consumers deduplicating by message id drop the copies published again; the
+/// deduplication window of the publisher itself is bypassed, so a result published
+/// again after a failure is never taken for a duplicate.
+pub fn process_and_publish<F, Fut>(publisher: Publisher, process: F) -> ProcessAndPublish<F>
+where
+    F: Fn(Delivery) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Vec<OutgoingMessage>, Nack>> + Send,
+{
+    ProcessAndPublish { publisher, process }
+}
+
+impl<F> ProcessAndPublish<F> {
+    async fn publish_all(&self, results: Vec<OutgoingMessage>) -> Result<(), RabbitError> {
+        let publishes = results.into_iter().map(|message| {
+            self.publisher
+                .resend_with(message, PublishGuarantee::Confirmed)
+        });
+        join_all(publishes).await.into_iter().collect()
+    }
+}
+
+#[async_trait]
+impl<F, Fut> DeliveryHandler for ProcessAndPublish<F>
+where
+    F: Fn(Delivery) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Vec<OutgoingMessage>, Nack>> + Send,
+{
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        let source_id = delivery
+            .properties
+            .message_id()
+            .as_ref()
+            .map(|id| id.to_string());
+        let mut results = (self.process)(delivery).await?;
+        if let Some(source_id) = &source_id {
+            for (index, message) in results.iter_mut().enumerate() {
+                if message.properties.message_id().is_none() {
+                    message.properties = std::mem::take(&mut message.properties)
+                        .with_message_id(format!("{source_id}/{index}").into());
+                }
+            }
+        }
+        match self.publish_all(results).await {
+            Ok(()) => Ok(Ack),
+            Err(e) => {
+                warn!(
+                    error = format!("{e}"),
+                    message_id = source_id,
+                    "results not confirmed, delivery requeued"
+                );
+                Err(Nack { requeue: true })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use lapin::types::FieldTable;
+
+    use super::*;
+    use crate::rabbit::{RetryPolicy, Unreachable};
+
+    fn delivery() -> Delivery {
+        let mut delivery = Delivery::test(FieldTable::default(), "{}");
+        delivery.properties = delivery.properties.with_message_id("m-1".into());
+        delivery
+    }
+
+    async fn results(_: Delivery) -> Result<Vec<OutgoingMessage>, Nack> {
+        Ok(vec![
+            OutgoingMessage::new("orders", "a", "{}"),
+            OutgoingMessage::new("orders", "b", "{}"),
+        ])
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_by_the_policy_of_the_publisher_then_requeues() {
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone()).with_retry(RetryPolicy::new(3));
+        let handler = process_and_publish(publisher, results);
+        assert_eq!(
+            handler.handle(delivery()).await,
+            Err(Nack { requeue: true })
+        );
+        assert_eq!(source.attempts(), 6);
+    }
+
+    #[tokio::test]
+    async fn redelivered_results_bypass_the_dedup_window() {
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone()).with_dedup(Duration::from_secs(60));
+        let handler = process_and_publish(publisher.clone(), results);
+        assert_eq!(
+            handler.handle(delivery()).await,
+            Err(Nack { requeue: true })
+        );
+        assert_eq!(
+            handler.handle(delivery()).await,
+            Err(Nack { requeue: true })
+        );
+        assert_eq!(source.attempts(), 4);
+        assert_eq!(publisher.suppressed_duplicates(), 0);
+    }
+
+    #[tokio::test]
+    async fn acks_without_results() {
+        let publisher = Publisher::new(Unreachable::default());
+        let handler = process_and_publish(publisher, |_| async { Ok(Vec::new()) });
+        assert_eq!(handler.handle(delivery()).await, Ok(Ack));
+    }
+}
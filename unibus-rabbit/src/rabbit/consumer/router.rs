@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use lapin::BasicProperties;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::{Ack, AckHandle, Delivery, DeliveryHandler, Nack, Validation};
+
+struct Route {
+    handler: Box<dyn DeliveryHandler>,
+    limit: Option<Semaphore>,
+}
+
+/// Dispatches deliveries of one queue to handlers by the `type` property.
+///
+/// Each message type may get its own concurrency limit; deliveries over the
+/// limit wait while holding a consumer slot, so the consumer concurrency should
+/// cover the sum of the limits. Unknown types are rejected.
+#[derive(Default)]
+pub struct Router {
+    routes: HashMap<String, Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    pub fn with_route(mut self, kind: impl Into<String>, handler: impl DeliveryHandler) -> Self {
+        self.routes.insert(
+            kind.into(),
+            Route {
+                handler: Box::new(handler),
+                limit: None,
+            },
+        );
+        self
+    }
+
+    /// Caps concurrent handling of `kind`; a no-op for types without a route.
+    pub fn with_limit(mut self, kind: &str, limit: usize) -> Self {
+        if let Some(route) = self.routes.get_mut(kind) {
+            route.limit = Some(Semaphore::new(limit.max(1)));
+        }
+        self
+    }
+
+    fn route(&self, properties: &BasicProperties) -> Option<&Route> {
+        properties
+            .kind()
+            .as_ref()
+            .and_then(|kind| self.routes.get(kind.as_str()))
+    }
+
+    /// Route of a delivery, with a permit of its limit if it has one.
+    async fn enter(
+        &self,
+        properties: &BasicProperties,
+    ) -> Option<(&Route, Option<SemaphorePermit<'_>>)> {
+        let route = self.route(properties)?;
+        let permit = match &route.limit {
+            Some(limit) => Some(limit.acquire().await.expect("route limit is never closed")),
+            None => None,
+        };
+        Some((route, permit))
+    }
+}
+
+#[async_trait]
+impl DeliveryHandler for Router {
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        match self.route(properties) {
+            Some(route) => route.handler.validate(properties),
+            None => Validation::Reject,
+        }
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        let Some((route, _permit)) = self.enter(&delivery.properties).await else {
+            return Err(Nack { requeue: false });
+        };
+        route.handler.handle(delivery).await
+    }
+
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        let Some((route, _permit)) = self.enter(&delivery.properties).await else {
+            return ack.settle_or_warn(Err(Nack { requeue: false })).await;
+        };
+        route.handler.handle_with(delivery, ack).await
+    }
+}
@@ -0,0 +1,177 @@
+use std::{fmt::Display, future::Future, sync::Arc, time::Duration};
+
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::Instant,
+};
+use tracing::warn;
+
+use super::{Ack, Delivery, DeliveryHandler, Nack};
+
+/// Why an item pushed to a [`BatchSink`] was not written.
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum FlushError {
+    #[error("batch flush failed: {0}")]
+    Failed(String),
+    #[error("batch sink stopped")]
+    Stopped,
+}
+
+type Pending<T> = (T, oneshot::Sender<Result<(), FlushError>>);
+
+/// Collects handler outputs and writes them in batches through a flush callback,
+/// once `max_items` are pending or the oldest waited `max_delay`.
+///
+/// [`BatchSink::push`] resolves with the outcome of the flush its item went out with,
+/// so a handler awaiting it acks its delivery only once the item is written, and a
+/// failed flush nacks every delivery of the batch. Handlers wait for the flush in
+/// their concurrency slot: give the consumer a concurrency and prefetch of at least
+/// `max_items`, or batches only ever fill up to the concurrency.
+/// Clones share the batch.
+pub struct BatchSink<T> {
+    items: mpsc::Sender<Pending<T>>,
+}
+
+impl<T> Clone for BatchSink<T> {
+    fn clone(&self) -> Self {
+        BatchSink {
+            items: self.items.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> BatchSink<T> {
+    pub fn new<F, Fut, E>(max_items: usize, max_delay: Duration, flush: F) -> Self
+    where
+        F: Fn(Vec<T>) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Display + 'static,
+    {
+        let max_items = max_items.max(1);
+        let (tx, rx) = mpsc::channel(max_items);
+        tokio::spawn(run(rx, max_items, max_delay, flush));
+        BatchSink { items: tx }
+    }
+
+    /// Adds `item` to the current batch and waits until that batch is flushed.
+    pub async fn push(&self, item: T) -> Result<(), FlushError> {
+        let (tx, rx) = oneshot::channel();
+        self.items
+            .send((item, tx))
+            .await
+            .map_err(|_| FlushError::Stopped)?;
+        rx.await.unwrap_or(Err(FlushError::Stopped))
+    }
+
+    /// Handler pushing what `map` makes of each delivery and acking once it is flushed;
+    /// a failed flush nacks with requeue, an error of `map` settles with its nack.
+    pub fn handler<M>(self, map: M) -> impl DeliveryHandler
+    where
+        M: Fn(&Delivery) -> Result<T, Nack> + Send + Sync + 'static,
+    {
+        let map = Arc::new(map);
+        move |delivery: Delivery| {
+            let sink = self.clone();
+            let map = map.clone();
+            async move {
+                let item = map(&delivery)?;
+                match sink.push(item).await {
+                    Ok(()) => Ok(Ack),
+                    Err(e) => {
+                        warn!(error = format!("{e}"), "batched delivery not written");
+                        Err(Nack { requeue: true })
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn run<T, F, Fut, E>(
+    mut rx: mpsc::Receiver<Pending<T>>,
+    max_items: usize,
+    max_delay: Duration,
+    flush: F,
+) where
+    F: Fn(Vec<T>) -> Fut,
+    Fut: Future<Output = Result<(), E>>,
+    E: Display,
+{
+    while let Some(first) = rx.recv().await {
+        let deadline = Instant::now() + max_delay;
+        let mut batch = vec![first];
+        while batch.len() < max_items {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(pending)) => batch.push(pending),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        let (items, waiters): (Vec<T>, Vec<_>) = batch.into_iter().unzip();
+        let outcome = flush(items)
+            .await
+            .map_err(|e| FlushError::Failed(format!("{e}")));
+        if let Err(e) = &outcome {
+            warn!(
+                error = format!("{e}"),
+                size = waiters.len(),
+                "batch flush failed"
+            );
+        }
+        for waiter in waiters {
+            _ = waiter.send(outcome.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::future::join_all;
+    use lapin::types::FieldTable;
+
+    use super::*;
+
+    type Batches = Arc<Mutex<Vec<Vec<u32>>>>;
+
+    fn recording(max_items: usize, max_delay: Duration) -> (BatchSink<u32>, Batches) {
+        let batches = Batches::default();
+        let flushed = batches.clone();
+        let sink = BatchSink::new(max_items, max_delay, move |items| {
+            flushed.lock().unwrap().push(items);
+            async { Ok::<_, String>(()) }
+        });
+        (sink, batches)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flushes_once_max_items_are_pending() {
+        let (sink, batches) = recording(2, Duration::from_secs(3600));
+        let started = Instant::now();
+        let (first, second) = tokio::join!(sink.push(1), sink.push(2));
+        assert!(first.is_ok() && second.is_ok());
+        assert!(started.elapsed() < Duration::from_secs(3600));
+        assert_eq!(*batches.lock().unwrap(), [vec![1, 2]]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn flushes_a_partial_batch_after_max_delay() {
+        let (sink, batches) = recording(10, Duration::from_millis(50));
+        let started = Instant::now();
+        sink.push(1).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(*batches.lock().unwrap(), [vec![1]]);
+    }
+
+    #[tokio::test]
+    async fn a_failed_flush_nacks_every_delivery_of_the_batch() {
+        let sink = BatchSink::new(3, Duration::from_secs(3600), |_: Vec<Vec<u8>>| async {
+            Err("disk full")
+        });
+        let handler = sink.handler(|delivery| Ok(delivery.data.clone()));
+        let outcomes =
+            join_all((0..3).map(|_| handler.handle(Delivery::test(FieldTable::default(), "{}"))))
+                .await;
+        assert_eq!(outcomes, vec![Err(Nack { requeue: true }); 3]);
+    }
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use lapin::options::BasicQosOptions;
+use tracing::{debug, warn};
+
+use super::ConsumerProbe;
+use crate::rabbit::{Channel, RabbitError};
+
+/// Gradual ramp of a consumer's prefetch and concurrency once its subscription was
+/// re-created after a reconnect, so the backlog of an outage is not pulled and handled
+/// all at once.
+///
+/// The prefetch is ramped with a channel-wide limit below the consumer's own one, the
+/// concurrency by holding back handler slots. Pausing the consumer ends the ramp of the
+/// concurrency.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SlowStart {
+    /// Share of the prefetch and concurrency to start from, at least one of each.
+    pub initial: f64,
+    /// Number of equal increments up to the full values.
+    pub steps: u32,
+    /// Time between increments.
+    pub interval: Duration,
+}
+
+impl SlowStart {
+    /// Starts from a tenth and reaches the full values in five steps of `interval`.
+    pub fn new(interval: Duration) -> Self {
+        SlowStart {
+            initial: 0.1,
+            steps: 5,
+            interval,
+        }
+    }
+
+    pub fn with_initial(mut self, share: f64) -> Self {
+        self.initial = share;
+        self
+    }
+
+    pub fn with_steps(mut self, steps: u32) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    /// Share of `full` at `step`, from the initial share at 0 up to `full` at `steps`.
+    fn at(&self, step: u32, full: usize) -> usize {
+        let initial = self.initial.clamp(0.0, 1.0);
+        let share = initial + (1.0 - initial) * f64::from(step) / f64::from(self.steps.max(1));
+        ((full as f64 * share).ceil() as usize).clamp(1, full.max(1))
+    }
+
+    /// Limits the new `channel` to the initial prefetch, before it subscribes.
+    pub(super) async fn begin(&self, channel: &Channel, prefetch: u16) -> Result<(), RabbitError> {
+        if prefetch > 0 {
+            let initial = self.at(0, prefetch.into()) as u16;
+            channel
+                .basic_qos(initial, BasicQosOptions { global: true })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Holds back handler slots for the initial concurrency, then raises both limits
+    /// step by step and lifts the channel-wide one at the end.
+    pub(super) async fn ramp(self, channel: Channel, probe: ConsumerProbe) {
+        let held = probe.concurrency - self.at(0, probe.concurrency);
+        probe.throttle(held);
+        for step in 1..=self.steps.max(1) {
+            tokio::time::sleep(self.interval).await;
+            probe.unthrottle(probe.concurrency - self.at(step, probe.concurrency));
+            if probe.prefetch == 0 {
+                continue;
+            }
+            let prefetch = match step >= self.steps {
+                true => 0,
+                false => self.at(step, probe.prefetch.into()) as u16,
+            };
+            debug!(step, prefetch, "slow start");
+            let res = channel
+                .basic_qos(prefetch, BasicQosOptions { global: true })
+                .await;
+            if let Err(e) = res {
+                warn!(error = format!("{e}"), "slow start stopped");
+                break;
+            }
+        }
+        probe.unthrottle(0);
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+use tracing::info;
+
+use super::{Consumer, ConsumerOptions, DeliveryHandler};
+use crate::rabbit::{topology::Topology, Channel, ChannelSource, RabbitError};
+
+/// Warm-standby consumer: topology declared and channel open, but not consuming
+/// until promoted, for active/passive worker deployments.
+pub struct Standby<H> {
+    source: Arc<dyn ChannelSource>,
+    channel: Channel,
+    options: ConsumerOptions,
+    handler: Arc<H>,
+}
+
+impl<H: DeliveryHandler> Standby<H> {
+    /// Declares `topology` and opens the consumer channel.
+    pub async fn prepare(
+        source: impl ChannelSource + 'static,
+        topology: &[Box<dyn Topology>],
+        options: ConsumerOptions,
+        handler: H,
+    ) -> Result<Self, RabbitError> {
+        let channel = Consumer::open(&source, &options).await?;
+        for item in topology {
+            item.declare(&channel).await?;
+        }
+        Ok(Standby {
+            source: Arc::new(source),
+            channel,
+            options,
+            handler: Arc::new(handler),
+        })
+    }
+
+    /// Starts consuming, reopening the channel if it was lost while on standby.
+    pub async fn promote(self) -> Result<Consumer, RabbitError> {
+        let channel = match self.channel.status().connected() {
+            true => self.channel,
+            false => Consumer::open(self.source.as_ref(), &self.options).await?,
+        };
+        info!(queue = self.options.queue, "standby consumer promoted");
+        Consumer::consume(self.source.as_ref(), channel, self.options, self.handler).await
+    }
+
+    /// Promotes once `signal` turns true, e.g. a leadership watch;
+    /// fails with [`RabbitError::ShuttingDown`] when the signal goes away first.
+    pub async fn promote_when(
+        self,
+        mut signal: watch::Receiver<bool>,
+    ) -> Result<Consumer, RabbitError> {
+        if signal.wait_for(|active| *active).await.is_err() {
+            return Err(RabbitError::ShuttingDown);
+        }
+        self.promote().await
+    }
+}
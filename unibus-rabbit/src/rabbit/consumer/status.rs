@@ -0,0 +1,133 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use serde::Serialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::{Feeds, Live};
+
+/// Snapshot of a running [`Consumer`](super::Consumer).
+#[derive(Clone, Debug, Serialize)]
+pub struct ConsumerStatus {
+    pub queue: String,
+    /// Tag of the current subscription.
+    pub tag: String,
+    pub prefetch: u16,
+    pub concurrency: usize,
+    /// Deliveries being handled right now.
+    pub in_flight: usize,
+    pub paused: bool,
+    pub cancelled: bool,
+    /// Connections the handlers published on; their shutdown waits for this consumer.
+    pub feeds: Vec<String>,
+}
+
+/// Handle reading the status of a consumer without owning it.
+#[derive(Clone)]
+pub(crate) struct ConsumerProbe {
+    pub(super) queue: String,
+    pub(super) prefetch: u16,
+    pub(super) concurrency: usize,
+    pub(super) slots: Arc<Semaphore>,
+    pub(super) live: Arc<Mutex<Live>>,
+    pub(super) cancelled: Arc<AtomicBool>,
+    pub(super) paused: Arc<AtomicBool>,
+    /// Every handler slot while paused.
+    pub(super) hold: Arc<Mutex<Option<OwnedSemaphorePermit>>>,
+    /// Handler slots held back by a [`SlowStart`](super::SlowStart).
+    pub(super) throttled: Arc<Mutex<Vec<OwnedSemaphorePermit>>>,
+    pub(super) feeds: Feeds,
+}
+
+impl ConsumerProbe {
+    pub(super) fn new(
+        queue: String,
+        prefetch: u16,
+        concurrency: usize,
+        live: Arc<Mutex<Live>>,
+        cancelled: Arc<AtomicBool>,
+        feeds: Feeds,
+    ) -> Self {
+        ConsumerProbe {
+            queue,
+            prefetch,
+            concurrency,
+            slots: Arc::new(Semaphore::new(concurrency)),
+            live,
+            cancelled,
+            paused: Default::default(),
+            hold: Default::default(),
+            throttled: Default::default(),
+            feeds,
+        }
+    }
+
+    pub(crate) fn queue(&self) -> &str {
+        &self.queue
+    }
+
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Takes every handler slot once the handlers in flight finish, so no delivery is
+    /// handled until [`ConsumerProbe::resume`].
+    pub(crate) async fn pause(&self) {
+        if self.paused.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.unthrottle(0);
+        let hold = self
+            .slots
+            .clone()
+            .acquire_many_owned(self.concurrency as u32)
+            .await
+            .expect("consumer slots are never closed");
+        // resumed while waiting for the handlers in flight
+        if self.paused.load(Ordering::SeqCst) {
+            *self.hold.lock().unwrap() = Some(hold);
+        }
+    }
+
+    pub(crate) fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.hold.lock().unwrap().take();
+    }
+
+    /// Holds back up to `slots` free handler slots.
+    pub(super) fn throttle(&self, slots: usize) {
+        let mut throttled = self.throttled.lock().unwrap();
+        while throttled.len() < slots {
+            match self.slots.clone().try_acquire_owned() {
+                Ok(permit) => throttled.push(permit),
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Releases held back handler slots down to `slots`.
+    pub(super) fn unthrottle(&self, slots: usize) {
+        self.throttled.lock().unwrap().truncate(slots);
+    }
+
+    pub(crate) fn status(&self) -> ConsumerStatus {
+        let held = match self.hold.lock().unwrap().is_some() {
+            true => self.concurrency,
+            false => self.throttled.lock().unwrap().len(),
+        };
+        ConsumerStatus {
+            queue: self.queue.clone(),
+            tag: self.live.lock().unwrap().tag.clone(),
+            prefetch: self.prefetch,
+            concurrency: self.concurrency,
+            in_flight: self
+                .concurrency
+                .saturating_sub(self.slots.available_permits() + held),
+            paused: self.paused.load(Ordering::SeqCst),
+            cancelled: self.cancelled.load(Ordering::SeqCst),
+            feeds: self.feeds.connections(),
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Stream, StreamExt};
+use lapin::options::{BasicCancelOptions, BasicConsumeOptions, BasicQosOptions};
+use serde::de::DeserializeOwned;
+
+use super::{AckToken, ConsumerOptions, DecodeError, Delivery, StreamFilter};
+use crate::rabbit::{Channel, ChannelPreset, ChannelPurpose, ChannelSource, RabbitError};
+
+/// Decoded delivery handed out by [`MessageStream`]; the caller acknowledges it.
+#[derive(Debug)]
+pub struct Message<T> {
+    pub body: T,
+    pub delivery: Delivery,
+    token: AckToken,
+}
+
+impl<T> Message<T> {
+    pub async fn ack(self) -> Result<(), RabbitError> {
+        self.token.ack().await
+    }
+
+    pub async fn nack(self, requeue: bool) -> Result<(), RabbitError> {
+        self.token.nack(requeue).await
+    }
+
+    /// Separates the body from the right to settle it, e.g. to ack after a
+    /// downstream task finished; copy what is needed from `delivery` first.
+    pub fn split(self) -> (T, AckToken) {
+        (self.body, self.token)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConsumeError {
+    #[error("consumer failed: {0}")]
+    Amqp(#[from] lapin::Error),
+    /// The payload is not a valid `T`; the undecoded message is left for the caller to settle.
+    #[error("{source}")]
+    Decode {
+        source: DecodeError,
+        message: Box<Message<()>>,
+    },
+}
+
+/// Queue subscription as a `futures::Stream` of JSON-decoded messages.
+///
+/// Polling pulls from the deliveries the broker has pushed; the broker stops
+/// pushing once `prefetch` messages are unacknowledged, so a slow pipeline backs
+/// up to the queue rather than into memory.
+pub struct MessageStream<T> {
+    channel: Channel,
+    consumer: lapin::Consumer,
+    filter: Option<StreamFilter>,
+    _body: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> MessageStream<T> {
+    /// Subscribes to `options.queue` with `options.prefetch`, `options.arguments` and
+    /// `options.stream_filter`; other options are ignored.
+    pub async fn open(
+        source: &dyn ChannelSource,
+        options: ConsumerOptions,
+    ) -> Result<Self, RabbitError> {
+        source.ensure_bound(&options.queue)?;
+        // the prefetch is set by `on_channel`
+        let preset = ChannelPreset {
+            prefetch: None,
+            ..source.preset(ChannelPurpose::Consume)
+        };
+        let channel = source
+            .create_channel_with(ChannelPurpose::Consume, preset)
+            .await?;
+        Self::on_channel(channel, options).await
+    }
+
+    pub(crate) async fn on_channel(
+        channel: Channel,
+        options: ConsumerOptions,
+    ) -> Result<Self, RabbitError> {
+        channel
+            .basic_qos(options.prefetch, BasicQosOptions::default())
+            .await?;
+        let consumer = channel
+            .basic_consume(
+                &options.queue,
+                "",
+                BasicConsumeOptions::default(),
+                options.arguments.clone(),
+            )
+            .await?;
+        Ok(MessageStream {
+            channel,
+            consumer,
+            filter: options.stream_filter,
+            _body: PhantomData,
+        })
+    }
+
+    /// Stops the broker from pushing; messages already received can still be settled.
+    pub async fn cancel(&self) -> Result<(), RabbitError> {
+        self.channel
+            .basic_cancel(self.consumer.tag().as_str(), BasicCancelOptions::default())
+            .await?;
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> Stream for MessageStream<T> {
+    type Item = Result<Message<T>, ConsumeError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (delivery, token) = loop {
+            let delivery = match self.consumer.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(delivery))) => delivery,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            let (delivery, acker) = Delivery::from_lapin(delivery);
+            let token = AckToken::new(acker);
+            match &self.filter {
+                Some(filter) if !filter.matches(&delivery.properties) => {
+                    tokio::spawn(token.ack());
+                }
+                _ => break (delivery, token),
+            }
+        };
+        let item = match delivery.json() {
+            Ok(body) => Ok(Message {
+                body,
+                delivery,
+                token,
+            }),
+            Err(source) => Err(ConsumeError::Decode {
+                source,
+                message: Box::new(Message {
+                    body: (),
+                    delivery,
+                    token,
+                }),
+            }),
+        };
+        Poll::Ready(Some(item))
+    }
+}
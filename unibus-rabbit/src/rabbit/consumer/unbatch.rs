@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use lapin::BasicProperties;
+
+use super::{Ack, AckHandle, Delivery, DeliveryHandler, Inner, Nack, Settler, Validation};
+use crate::rabbit::{batch, headers};
+
+/// Hands the items of [`WireBatch`](crate::rabbit::batch::WireBatch) envelopes to
+/// the inner handler one by one; other deliveries pass through unchanged.
+///
+/// The envelope is acked once every item is; the first nack stops the batch and
+/// nacks the envelope, so items handled before it are seen again on redelivery.
+pub struct Unbatched<H> {
+    handler: H,
+}
+
+impl<H: DeliveryHandler> Unbatched<H> {
+    pub fn new(handler: H) -> Self {
+        Unbatched { handler }
+    }
+
+    async fn unbatch(&self, delivery: Delivery, inner: Inner<'_, H>) -> Result<Ack, Nack> {
+        let mut table = headers::headers(&delivery.properties);
+        let Some(items) = batch::unpack(&table, &delivery.data) else {
+            return inner.handle(delivery).await;
+        };
+        batch::strip_headers(&mut table);
+        let template = Delivery {
+            delivery_tag: delivery.delivery_tag,
+            exchange: delivery.exchange.clone(),
+            routing_key: delivery.routing_key.clone(),
+            redelivered: delivery.redelivered,
+            properties: delivery.properties.clone().with_headers(table),
+            data: Vec::new(),
+        };
+        for item in items {
+            let item = Delivery {
+                data: item.to_vec(),
+                ..template.clone()
+            };
+            inner.handle(item).await?;
+        }
+        Ok(Ack)
+    }
+}
+
+#[async_trait]
+impl<H: DeliveryHandler> DeliveryHandler for Unbatched<H> {
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        self.handler.validate(properties)
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        self.unbatch(delivery, Inner::new(&self.handler, None))
+            .await
+    }
+
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        let settler = Settler::new(ack);
+        let outcome = self
+            .unbatch(delivery, Inner::new(&self.handler, Some(&settler)))
+            .await;
+        settler.finish(outcome).await
+    }
+}
@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use lapin::{options::QueueDeclareOptions, types::FieldTable};
+use tokio::time::Instant;
+use tracing::info;
+
+use super::{
+    topology::{Binding, Topology},
+    ChannelPurpose, Connection, RabbitError,
+};
+
+const DRAIN_POLL: Duration = Duration::from_millis(500);
+
+/// Blue/green switch of the bindings of a consumer group from the `old` queue to the
+/// `new` one, e.g. for a contract migration, with both groups running meanwhile.
+///
+/// The steps run in order through [`Cutover::run`] or one by one: bind `new`, unbind
+/// `old`, then wait for the consumers of `old` to drain what it still holds. From the
+/// unbind on, each message goes to one side only. Every step is idempotent, and
+/// [`Cutover::rollback`] switches back.
+pub struct Cutover {
+    connection: Connection,
+    old: String,
+    new: String,
+    bindings: Vec<(String, String)>,
+}
+
+impl Cutover {
+    /// Both queues are expected to exist, with their consumers started.
+    pub fn new(connection: Connection, old: impl Into<String>, new: impl Into<String>) -> Self {
+        Cutover {
+            connection,
+            old: old.into(),
+            new: new.into(),
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Moves the binding of `exchange` with `routing_key`.
+    pub fn with_binding(
+        mut self,
+        exchange: impl Into<String>,
+        routing_key: impl Into<String>,
+    ) -> Self {
+        self.bindings.push((exchange.into(), routing_key.into()));
+        self
+    }
+
+    /// Switches over and waits up to `drain_timeout` for `old` to empty.
+    pub async fn run(&self, drain_timeout: Duration) -> Result<(), RabbitError> {
+        self.bind_new().await?;
+        self.unbind_old().await?;
+        self.drain_old(drain_timeout).await
+    }
+
+    /// Step 1: `new` receives along with `old`, so both groups handle each message.
+    pub async fn bind_new(&self) -> Result<(), RabbitError> {
+        self.connection.declare(&self.topology(&self.new)).await?;
+        info!(queue = self.new, "cutover: new queue bound");
+        Ok(())
+    }
+
+    /// Step 2: `old` stops receiving.
+    pub async fn unbind_old(&self) -> Result<(), RabbitError> {
+        self.unbind(&self.old).await?;
+        info!(queue = self.old, "cutover: old queue unbound");
+        Ok(())
+    }
+
+    /// Step 3: waits until `old` has no ready messages left, polling its depth; fails
+    /// with [`RabbitError::NotDrained`] after `timeout`. Messages delivered and not yet
+    /// acknowledged are not counted, cancel the old consumers to wait for those.
+    pub async fn drain_old(&self, timeout: Duration) -> Result<(), RabbitError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = self.depth(&self.old).await?;
+            if remaining == 0 {
+                info!(queue = self.old, "cutover: old queue drained");
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(RabbitError::NotDrained {
+                    queue: self.old.clone(),
+                    remaining,
+                });
+            }
+            tokio::time::sleep(DRAIN_POLL).await;
+        }
+    }
+
+    /// Binds `old` again and unbinds `new`.
+    pub async fn rollback(&self) -> Result<(), RabbitError> {
+        self.connection.declare(&self.topology(&self.old)).await?;
+        self.unbind(&self.new).await?;
+        info!(queue = self.old, "cutover: rolled back");
+        Ok(())
+    }
+
+    fn bindings(&self, queue: &str) -> Vec<Binding> {
+        self.bindings
+            .iter()
+            .map(|(exchange, key)| Binding::new(queue, exchange, key))
+            .collect()
+    }
+
+    fn topology(&self, queue: &str) -> Vec<Box<dyn Topology>> {
+        self.bindings(queue)
+            .into_iter()
+            .map(|binding| Box::new(binding) as Box<dyn Topology>)
+            .collect()
+    }
+
+    async fn unbind(&self, queue: &str) -> Result<(), RabbitError> {
+        let channel = self.connection.create_channel_for(ChannelPurpose::Admin).await?;
+        for binding in self.bindings(queue) {
+            binding.unbind(&channel).await?;
+        }
+        _ = channel.close(0, "cutover unbound").await;
+        Ok(())
+    }
+
+    async fn depth(&self, queue: &str) -> Result<u32, RabbitError> {
+        let channel = self.connection.create_channel_for(ChannelPurpose::Admin).await?;
+        let declared = channel
+            .queue_declare(
+                queue,
+                QueueDeclareOptions {
+                    passive: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+        _ = channel.close(0, "cutover depth").await;
+        Ok(declared.message_count())
+    }
+}
@@ -0,0 +1,58 @@
+use actix::MailboxError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RabbitError {
+    #[error("connection is not ready")]
+    NotConnected,
+    #[error("shutting down")]
+    ShuttingDown,
+    #[error("invalid amqp uri: {0}")]
+    InvalidUri(String),
+    #[error("rabbit system failed: {0}")]
+    System(#[source] std::io::Error),
+    #[error("rabbit system unavailable: {0}")]
+    Mailbox(#[from] MailboxError),
+    #[error("amqp error: {0}")]
+    Amqp(#[from] lapin::Error),
+    #[error("connection still used by {}", .0.join(", "))]
+    InUse(Vec<String>),
+    #[error("channel budget of {0} exhausted")]
+    ChannelBudgetExceeded(usize),
+    #[error("exchange {0} is not in the declared topology")]
+    UndeclaredExchange(String),
+    #[error("queue {0} has no declared binding")]
+    UnboundQueue(String),
+    #[error("queue {0} does not exist")]
+    QueueMissing(String),
+    #[error("queue {queue} still holds {remaining} messages")]
+    NotDrained { queue: String, remaining: u32 },
+    #[error("no publisher confirm within {0:?}")]
+    ConfirmTimeout(std::time::Duration),
+    #[error("payload of {size} bytes exceeds the limit of {limit}")]
+    PayloadTooLarge { size: usize, limit: usize },
+    #[error("rpc failed with {0}")]
+    Rpc(#[from] super::RpcError),
+    #[error("no reply within {0:?}")]
+    RpcTimeout(std::time::Duration),
+    #[error("consumer hook failed: {0}")]
+    Hook(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("message nacked by broker")]
+    Nacked,
+    #[error("message to {exchange}/{routing_key} is unroutable")]
+    Unroutable { exchange: String, routing_key: String },
+    #[error("declaring {item} failed, connection parked: {source}")]
+    TopologyFailed { item: String, source: lapin::Error },
+    #[error("injected failure")]
+    Injected,
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// For conversions that cannot fail, e.g. an [`Endpoints`](super::Endpoints) given to
+/// [`ConnectionOptions::new`](super::ConnectionOptions::new).
+impl From<std::convert::Infallible> for RabbitError {
+    fn from(never: std::convert::Infallible) -> Self {
+        match never {}
+    }
+}
@@ -0,0 +1,140 @@
+use std::{future::Future, sync::Arc};
+
+use lapin::{
+    options::QueueDeclareOptions,
+    types::{AMQPValue, FieldTable},
+};
+
+use super::{
+    consumer::{Ack, Consumer, ConsumerOptions, Delivery},
+    headers,
+    topology::{Binding, Topology},
+    ChannelSource, RabbitError,
+};
+
+/// Topic exchange of the `rabbitmq_event_exchange` plugin.
+pub const EVENT_EXCHANGE: &str = "amq.rabbitmq.event";
+
+/// Broker-side event, parsed from the routing key and headers of the event exchange.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BrokerEvent {
+    QueueCreated {
+        name: String,
+        vhost: String,
+    },
+    QueueDeleted {
+        name: String,
+        vhost: String,
+    },
+    ConsumerCreated {
+        queue: String,
+        consumer_tag: String,
+    },
+    ConsumerDeleted {
+        queue: String,
+        consumer_tag: String,
+    },
+    ConnectionCreated {
+        name: String,
+        user: String,
+    },
+    ConnectionClosed {
+        name: String,
+        user: String,
+    },
+    /// Any other event, e.g. `user.authentication.failure` or `policy.set`.
+    Other {
+        key: String,
+        headers: FieldTable,
+    },
+}
+
+impl BrokerEvent {
+    pub fn parse(routing_key: &str, headers: &FieldTable) -> Self {
+        let field = |key| headers::get_str(headers, key).unwrap_or_default();
+        match routing_key {
+            "queue.created" => BrokerEvent::QueueCreated {
+                name: field("name"),
+                vhost: field("vhost"),
+            },
+            "queue.deleted" => BrokerEvent::QueueDeleted {
+                name: field("name"),
+                vhost: field("vhost"),
+            },
+            "consumer.created" => BrokerEvent::ConsumerCreated {
+                queue: field("queue"),
+                consumer_tag: field("consumer_tag"),
+            },
+            "consumer.deleted" => BrokerEvent::ConsumerDeleted {
+                queue: field("queue"),
+                consumer_tag: field("consumer_tag"),
+            },
+            "connection.created" => BrokerEvent::ConnectionCreated {
+                name: field("name"),
+                user: field("user"),
+            },
+            "connection.closed" => BrokerEvent::ConnectionClosed {
+                name: field("name"),
+                user: field("user"),
+            },
+            key => BrokerEvent::Other {
+                key: key.to_string(),
+                headers: headers.clone(),
+            },
+        }
+    }
+}
+
+/// Subscribes `handler` to broker events matching the topic `patterns`
+/// (e.g. `queue.*`, `consumer.deleted`); requires the event exchange plugin.
+///
+/// Events go through a private auto-deleted queue, so they are only seen while
+/// the consumer runs. Handler panics aside, every event is acked.
+pub async fn consume_events<F, Fut>(
+    source: &dyn ChannelSource,
+    patterns: &[&str],
+    handler: F,
+) -> Result<Consumer, RabbitError>
+where
+    F: Fn(BrokerEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let channel = source.create_channel().await?;
+    let mut arguments = FieldTable::default();
+    // drop the queue if the consumer never comes back after a reconnect
+    arguments.insert("x-expires".into(), AMQPValue::LongInt(60_000));
+    let queue = channel
+        .queue_declare(
+            &format!("unibus.events.{}", uuid::Uuid::new_v4().simple()),
+            QueueDeclareOptions {
+                auto_delete: true,
+                ..Default::default()
+            },
+            arguments,
+        )
+        .await?;
+    let queue = queue.name().to_string();
+    for pattern in patterns {
+        let binding = Binding::new(&queue, EVENT_EXCHANGE, *pattern);
+        binding.declare(&channel).await?;
+        source.register(&binding);
+    }
+    _ = channel.close(200, "OK").await;
+    let handler = Arc::new(handler);
+    Consumer::start(
+        source,
+        ConsumerOptions::new(queue),
+        move |delivery: Delivery| {
+            let handler = handler.clone();
+            async move {
+                let event = BrokerEvent::parse(
+                    &delivery.routing_key,
+                    &headers::headers(&delivery.properties),
+                );
+                handler(event).await;
+                Ok(Ack)
+            }
+        },
+    )
+    .await
+}
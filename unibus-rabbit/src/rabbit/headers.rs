@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+
+use lapin::{
+    types::{AMQPValue, FieldTable},
+    BasicProperties,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value};
+
+/// Typed view of message headers.
+///
+/// Implemented for every `Deserialize` type: derive `Deserialize` on a context struct
+/// and use `#[serde(default)]`, `#[serde(rename = "x-...")]` and `Option` fields to
+/// mark defaults, header names and optional headers; other fields are required.
+pub trait FromHeaders: Sized {
+    fn from_headers(headers: &FieldTable) -> Result<Self, serde_json::Error>;
+}
+
+impl<T: DeserializeOwned> FromHeaders for T {
+    fn from_headers(headers: &FieldTable) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(table_to_json(headers))
+    }
+}
+
+/// Headers as a JSON object; strings are decoded lossily, byte arrays become number arrays.
+pub fn table_to_json(headers: &FieldTable) -> Value {
+    let map: Map<String, Value> = headers
+        .inner()
+        .iter()
+        .map(|(k, v)| (k.to_string(), to_json(v)))
+        .collect();
+    Value::Object(map)
+}
+
+pub fn to_json(value: &AMQPValue) -> Value {
+    match value {
+        AMQPValue::Boolean(v) => Value::Bool(*v),
+        AMQPValue::ShortShortInt(v) => (*v).into(),
+        AMQPValue::ShortShortUInt(v) => (*v).into(),
+        AMQPValue::ShortInt(v) => (*v).into(),
+        AMQPValue::ShortUInt(v) => (*v).into(),
+        AMQPValue::LongInt(v) => (*v).into(),
+        AMQPValue::LongUInt(v) => (*v).into(),
+        AMQPValue::LongLongInt(v) => (*v).into(),
+        AMQPValue::Timestamp(v) => (*v).into(),
+        AMQPValue::Float(v) => float(f64::from(*v)),
+        AMQPValue::Double(v) => float(*v),
+        AMQPValue::DecimalValue(d) => float(f64::from(d.value) / 10f64.powi(d.scale.into())),
+        AMQPValue::ShortString(s) => Value::String(s.to_string()),
+        AMQPValue::LongString(s) => Value::String(String::from_utf8_lossy(s.as_bytes()).into()),
+        AMQPValue::FieldArray(a) => Value::Array(a.as_slice().iter().map(to_json).collect()),
+        AMQPValue::FieldTable(t) => table_to_json(t),
+        AMQPValue::ByteArray(b) => Value::Array(b.as_slice().iter().map(|&b| b.into()).collect()),
+        AMQPValue::Void => Value::Null,
+    }
+}
+
+/// Headers in the form of [`Envelope::headers`](unibus_core::Envelope::headers).
+pub fn to_map(headers: &FieldTable) -> BTreeMap<String, Value> {
+    headers
+        .inner()
+        .iter()
+        .map(|(k, v)| (k.to_string(), to_json(v)))
+        .collect()
+}
+
+/// Inverse of [`to_map`], see [`from_json`].
+pub fn from_map(headers: &BTreeMap<String, Value>) -> FieldTable {
+    let mut table = FieldTable::default();
+    for (key, value) in headers {
+        table.insert(key.as_str().into(), from_json(value));
+    }
+    table
+}
+
+/// Inverse of [`to_json`]: integers become long long ints, objects field tables.
+pub fn from_json(value: &Value) -> AMQPValue {
+    match value {
+        Value::Null => AMQPValue::Void,
+        Value::Bool(v) => AMQPValue::Boolean(*v),
+        Value::Number(n) => match n.as_i64() {
+            Some(v) => AMQPValue::LongLongInt(v),
+            None => AMQPValue::Double(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => AMQPValue::LongString(s.as_str().into()),
+        Value::Array(a) => {
+            AMQPValue::FieldArray(a.iter().map(from_json).collect::<Vec<_>>().into())
+        }
+        Value::Object(o) => {
+            let mut table = FieldTable::default();
+            for (key, value) in o {
+                table.insert(key.as_str().into(), from_json(value));
+            }
+            AMQPValue::FieldTable(table)
+        }
+    }
+}
+
+fn float(v: f64) -> Value {
+    Number::from_f64(v).map_or(Value::Null, Value::Number)
+}
+
+/// Headers of `properties`, empty when there are none.
+pub fn headers(properties: &BasicProperties) -> FieldTable {
+    properties.headers().clone().unwrap_or_default()
+}
+
+pub fn get_str(headers: &FieldTable, key: &str) -> Option<String> {
+    match headers.inner().get(key)? {
+        AMQPValue::LongString(s) => Some(s.to_string()),
+        AMQPValue::ShortString(s) => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+pub fn get_u64(headers: &FieldTable, key: &str) -> Option<u64> {
+    as_u64(headers.inner().get(key)?)
+}
+
+pub fn as_u64(value: &AMQPValue) -> Option<u64> {
+    match *value {
+        AMQPValue::ShortShortUInt(v) => Some(v.into()),
+        AMQPValue::ShortUInt(v) => Some(v.into()),
+        AMQPValue::LongUInt(v) => Some(v.into()),
+        AMQPValue::Timestamp(v) => Some(v),
+        AMQPValue::ShortShortInt(v) => u64::try_from(v).ok(),
+        AMQPValue::ShortInt(v) => u64::try_from(v).ok(),
+        AMQPValue::LongInt(v) => u64::try_from(v).ok(),
+        AMQPValue::LongLongInt(v) => u64::try_from(v).ok(),
+        _ => None,
+    }
+}
+
+pub fn set_str(headers: &mut FieldTable, key: &str, value: &str) {
+    headers.insert(key.into(), AMQPValue::LongString(value.into()));
+}
+
+pub fn set_u64(headers: &mut FieldTable, key: &str, value: u64) {
+    headers.insert(key.into(), AMQPValue::LongLongInt(value as i64));
+}
@@ -0,0 +1,98 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::{Connection, ConnectionState, RabbitError};
+
+/// Publishes the state of a connection for process supervisors: to a watchdog file
+/// for file based container probes and, with the `systemd` feature, to systemd through
+/// `sd_notify`, signalling `READY=1` on the first connect and a `STATUS` on each change.
+#[derive(Clone, Debug, Default)]
+pub struct HealthExport {
+    file: Option<PathBuf>,
+    #[cfg(feature = "systemd")]
+    systemd: bool,
+}
+
+impl HealthExport {
+    pub fn new() -> Self {
+        HealthExport::default()
+    }
+
+    /// Rewrites `path` with `ready: <endpoint>`, `connecting`, `error: <cause>` or `closed`
+    /// on each state change.
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Notifies systemd, when the process runs as a `Type=notify` service.
+    #[cfg(feature = "systemd")]
+    pub fn with_systemd(mut self) -> Self {
+        self.systemd = true;
+        self
+    }
+
+    /// Exports the states of `connection` until it is closed or parked for good.
+    pub async fn start(self, connection: &Connection) -> Result<JoinHandle<()>, RabbitError> {
+        let mut states = connection.state_watcher().await?;
+        Ok(tokio::spawn(async move {
+            let mut announced = false;
+            loop {
+                let state = states.borrow_and_update().clone();
+                self.export(&state, &mut announced).await;
+                if state.is_final() || states.changed().await.is_err() {
+                    break;
+                }
+            }
+        }))
+    }
+
+    async fn export(&self, state: &ConnectionState, announced: &mut bool) {
+        let status = match state {
+            ConnectionState::None => "connecting".to_owned(),
+            ConnectionState::Ready { endpoint } => format!("ready: {endpoint}"),
+            ConnectionState::Error(e) => format!("error: {e}"),
+            ConnectionState::Closed => "closed".to_owned(),
+            ConnectionState::TopologyFailed { item, error } => {
+                format!("topology failed: {item}: {error}")
+            }
+        };
+        if let Some(path) = &self.file {
+            if let Err(e) = write_atomically(path, &status).await {
+                warn!(error = format!("{e}"), path = %path.display(), "health file not written");
+            }
+        }
+        #[cfg(feature = "systemd")]
+        if self.systemd {
+            use sd_notify::NotifyState;
+
+            let status = format!("connection {status}");
+            let mut notify = vec![NotifyState::Status(&status)];
+            if state.is_ready() && !*announced {
+                notify.push(NotifyState::Ready);
+                *announced = true;
+            }
+            if *state == ConnectionState::Closed {
+                notify.push(NotifyState::Stopping);
+            }
+            if let Err(e) = sd_notify::notify(false, &notify) {
+                warn!(error = format!("{e}"), "sd_notify failed");
+            }
+        }
+        #[cfg(not(feature = "systemd"))]
+        let _ = announced;
+    }
+}
+
+/// Writes next to `path` and renames, so probes never read a partial state.
+async fn write_atomically(path: &Path, status: &str) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".tmp");
+    tokio::fs::write(&partial, format!("{status}\n")).await?;
+    tokio::fs::rename(&partial, path).await
+}
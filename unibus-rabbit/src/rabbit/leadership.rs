@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use lapin::{
+    options::QueueDeclareOptions,
+    protocol::{AMQPErrorKind, AMQPSoftError},
+    types::FieldTable,
+};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, info, trace_span, warn, Instrument};
+
+use super::{Channel, ChannelSource, RabbitError};
+
+/// Leader election within a group of instances, for singleton tasks like
+/// schedulers or outbox relays.
+///
+/// The leader is the instance holding the exclusive queue `<group>.leader`. The
+/// broker deletes an exclusive queue together with the connection that declared
+/// it, so when the leader dies or loses its connection another instance takes the
+/// queue on its next attempt, at most `interval` later.
+pub struct Leadership {
+    state: watch::Receiver<bool>,
+    task: JoinHandle<()>,
+}
+
+impl Leadership {
+    pub fn start(
+        source: impl ChannelSource + 'static,
+        group: impl Into<String>,
+        interval: Duration,
+    ) -> Self {
+        let (tx, rx) = watch::channel(false);
+        let queue = format!("{}.leader", group.into());
+        let span = trace_span!("leadership", queue);
+        let task = tokio::spawn(campaign(source, queue, interval, tx).instrument(span));
+        Leadership { state: rx, task }
+    }
+
+    pub fn is_leader(&self) -> bool {
+        *self.state.borrow()
+    }
+
+    /// Turns true while this instance leads.
+    pub fn watch(&self) -> watch::Receiver<bool> {
+        self.state.clone()
+    }
+}
+
+impl Drop for Leadership {
+    fn drop(&mut self) {
+        // the held channel goes with the task; the queue goes with the connection
+        self.task.abort();
+    }
+}
+
+async fn campaign(
+    source: impl ChannelSource,
+    queue: String,
+    interval: Duration,
+    state: watch::Sender<bool>,
+) {
+    let mut held: Option<Channel> = None;
+    loop {
+        match &held {
+            Some(channel) if channel.status().connected() => {}
+            Some(_) => {
+                held = None;
+                state.send_replace(false);
+                warn!("leadership lost");
+            }
+            None => match claim(&source, &queue).await {
+                Ok(Some(channel)) => {
+                    held = Some(channel);
+                    state.send_replace(true);
+                    info!("leadership acquired");
+                }
+                Ok(None) => debug!("another instance leads"),
+                Err(e) => warn!(error = format!("{e}"), "leadership claim failed"),
+            },
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// The channel that declared the exclusive queue, or `None` when another connection holds it.
+async fn claim(source: &impl ChannelSource, queue: &str) -> Result<Option<Channel>, RabbitError> {
+    let channel = source.create_channel().await?;
+    let declared = channel
+        .queue_declare(
+            queue,
+            QueueDeclareOptions {
+                exclusive: true,
+                auto_delete: true,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await;
+    match declared {
+        Ok(_) => Ok(Some(channel)),
+        Err(lapin::Error::ProtocolError(e))
+            if *e.kind() == AMQPErrorKind::Soft(AMQPSoftError::RESOURCELOCKED) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,103 @@
+//! Connection, publisher and consumer activity recorded through the `metrics` facade
+//! with the `metrics` feature, labeled by connection name and exchange or queue.
+//! Without the feature every call is a no-op.
+//!
+//! * `unibus_connection_state`: gauge, 0 none, 1 ready, 2 error, 3 closed,
+//!   4 topology failed;
+//! * `unibus_reconnects_total`;
+//! * `unibus_published_total`, `unibus_confirmed_total`, `unibus_nacked_total`;
+//! * `unibus_consumed_total`, `unibus_acked_total`, `unibus_requeued_total`,
+//!   `unibus_rejected_total`;
+//! * `unibus_handler_duration_seconds`: histogram of the handler latency;
+//! * `unibus_channels_opened_total` and `unibus_channels_open`, labeled by connection
+//!   and channel purpose.
+
+use std::time::Duration;
+
+use super::ConnectionState;
+
+pub(crate) fn connection_state(connection: &str, state: &ConnectionState) {
+    let value = match state {
+        ConnectionState::None => 0.0,
+        ConnectionState::Ready { .. } => 1.0,
+        ConnectionState::Error(_) => 2.0,
+        ConnectionState::Closed => 3.0,
+        ConnectionState::TopologyFailed { .. } => 4.0,
+    };
+    #[cfg(feature = "metrics")]
+    metrics::gauge!("unibus_connection_state", value, "connection" => connection.to_owned());
+    #[cfg(not(feature = "metrics"))]
+    let _ = (connection, value);
+}
+
+pub(crate) fn reconnected(connection: &str) {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!("unibus_reconnects_total", "connection" => connection.to_owned());
+    #[cfg(not(feature = "metrics"))]
+    let _ = connection;
+}
+
+pub(crate) fn publish(name: &'static str, connection: &str, exchange: &str) {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!(
+        name,
+        "connection" => connection.to_owned(),
+        "exchange" => exchange.to_owned()
+    );
+    #[cfg(not(feature = "metrics"))]
+    let _ = (name, connection, exchange);
+}
+
+pub(crate) fn delivery(name: &'static str, connection: &str, queue: &str) {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!(
+        name,
+        "connection" => connection.to_owned(),
+        "queue" => queue.to_owned()
+    );
+    #[cfg(not(feature = "metrics"))]
+    let _ = (name, connection, queue);
+}
+
+pub(crate) fn handled(connection: &str, queue: &str, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(
+        "unibus_handler_duration_seconds",
+        elapsed.as_secs_f64(),
+        "connection" => connection.to_owned(),
+        "queue" => queue.to_owned()
+    );
+    #[cfg(not(feature = "metrics"))]
+    let _ = (connection, queue, elapsed);
+}
+
+pub(crate) fn channel_opened(connection: &str, purpose: &'static str) {
+    #[cfg(feature = "metrics")]
+    {
+        metrics::increment_counter!(
+            "unibus_channels_opened_total",
+            "connection" => connection.to_owned(),
+            "purpose" => purpose
+        );
+        metrics::increment_gauge!(
+            "unibus_channels_open",
+            1.0,
+            "connection" => connection.to_owned(),
+            "purpose" => purpose
+        );
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (connection, purpose);
+}
+
+pub(crate) fn channel_closed(connection: &str, purpose: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::decrement_gauge!(
+        "unibus_channels_open",
+        1.0,
+        "connection" => connection.to_owned(),
+        "purpose" => purpose
+    );
+    #[cfg(not(feature = "metrics"))]
+    let _ = (connection, purpose);
+}
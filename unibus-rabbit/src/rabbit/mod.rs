@@ -0,0 +1,67 @@
+use actix::prelude::*;
+mod system;
+mod admin;
+pub mod batch;
+mod connection;
+mod cutover;
+pub mod consumer;
+mod error;
+pub mod events;
+pub mod headers;
+mod health;
+mod metrics;
+mod otel;
+mod leadership;
+pub mod partition;
+pub mod propagation;
+mod publisher;
+mod rpc;
+mod size;
+pub mod telemetry;
+pub mod topology;
+mod transport;
+mod tx;
+
+
+pub use connection::{
+    AmqpEndpoint, BudgetPolicy, Channel, ChannelPreset, ChannelPurpose, ChannelSource,
+    ConnectionEvent, ConnectionOptions, ConnectionPool, ConnectionState, Connection,
+    DependentGuard, Endpoints, Failover, StabilityFilter, TimedEvent,
+};
+#[cfg(test)]
+pub(crate) use connection::Unreachable;
+pub use system::*;
+pub use cutover::Cutover;
+pub use error::RabbitError;
+pub use health::HealthExport;
+pub use leadership::Leadership;
+pub use publisher::{
+    OrderedPublisher, OutgoingMessage, PublishGuarantee, PublishReceipt, Publisher, ReplyAddress,
+    RetryPolicy,
+};
+pub(crate) use publisher::PublisherProbe;
+pub use rpc::{Rpc, RpcError, RpcStatus, ScatterQuery};
+pub use size::SizeLimit;
+pub use tx::{TxChannel, Txn};
+
+
+/// Uniformly random delay up to `max`, spreading simultaneous restarts of many instances.
+pub(crate) fn jitter(max: std::time::Duration) -> std::time::Duration {
+    if max.is_zero() {
+        return max;
+    }
+    max.mul_f64(rand::random::<f64>())
+}
+
+pub(self) fn lapin_error_eq(e1: &lapin::Error, e2: &lapin::Error) -> bool {
+    format!("{e1}") == format!("{e2}")
+}
+
+
+
+#[derive(Message)]
+#[rtype(result = "()")]
+pub(self) struct Close;
+
+
+
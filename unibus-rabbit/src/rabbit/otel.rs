@@ -0,0 +1,72 @@
+//! W3C trace context (`traceparent`, `tracestate`) carried in message headers with the
+//! `opentelemetry` feature: injected from the current span on publish, and the parent
+//! of the delivery span on consume, with `messaging.*` attributes. Uses the global
+//! text map propagator; without the feature every call is a no-op.
+
+use lapin::types::FieldTable;
+use tracing::Span;
+
+#[cfg(feature = "opentelemetry")]
+mod carrier {
+    use lapin::types::{AMQPValue, FieldTable};
+    use opentelemetry::propagation::{Extractor, Injector};
+
+    pub(super) struct Inject<'a>(pub(super) &'a mut FieldTable);
+
+    impl Injector for Inject<'_> {
+        fn set(&mut self, key: &str, value: String) {
+            self.0.insert(key.into(), AMQPValue::LongString(value.into()));
+        }
+    }
+
+    pub(super) struct Extract<'a>(pub(super) &'a FieldTable);
+
+    impl Extractor for Extract<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            match self.0.inner().get(key)? {
+                AMQPValue::LongString(s) => std::str::from_utf8(s.as_bytes()).ok(),
+                AMQPValue::ShortString(s) => Some(s.as_str()),
+                _ => None,
+            }
+        }
+
+        fn keys(&self) -> Vec<&str> {
+            self.0.inner().keys().map(|k| k.as_str()).collect()
+        }
+    }
+}
+
+/// Writes the context of the current span into `headers`.
+pub(crate) fn inject(headers: &mut FieldTable) {
+    #[cfg(feature = "opentelemetry")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let context = Span::current().context();
+        opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&context, &mut carrier::Inject(headers))
+        });
+    }
+    #[cfg(not(feature = "opentelemetry"))]
+    let _ = headers;
+}
+
+/// Parents `span`, declared with empty `messaging.*` and `otel.kind` fields, on the
+/// context in `headers` and records the attributes of a delivery from `queue`.
+pub(crate) fn consume(span: &Span, headers: &FieldTable, queue: &str) {
+    #[cfg(feature = "opentelemetry")]
+    {
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+            propagator.extract(&carrier::Extract(headers))
+        });
+        span.set_parent(parent);
+        span.record("messaging.system", "rabbitmq");
+        span.record("messaging.destination", queue);
+        span.record("messaging.operation", "process");
+        span.record("otel.kind", "consumer");
+    }
+    #[cfg(not(feature = "opentelemetry"))]
+    let _ = (span, headers, queue);
+}
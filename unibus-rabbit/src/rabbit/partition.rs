@@ -0,0 +1,296 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use lapin::{types::AMQPValue, ExchangeKind};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{oneshot, watch},
+    task::JoinHandle,
+};
+use tracing::{info, trace_span, warn, Instrument};
+
+use super::{
+    consumer::{Ack, Consumer, ConsumerOptions, Delivery, DeliveryHandler},
+    topology::{Binding, Exchange, Queue, Topology},
+    Connection, OutgoingMessage, Publisher, RabbitError,
+};
+
+/// Settings of a [`Partitioned`] consumer group.
+#[derive(Clone, Debug)]
+pub struct PartitionOptions {
+    pub group: String,
+    pub partitions: u16,
+    pub instance: String,
+    pub heartbeat: Duration,
+    pub prefetch: u16,
+}
+
+impl PartitionOptions {
+    pub fn new(group: impl Into<String>, partitions: u16) -> Self {
+        PartitionOptions {
+            group: group.into(),
+            partitions,
+            instance: uuid::Uuid::new_v4().simple().to_string(),
+            heartbeat: Duration::from_secs(5),
+            prefetch: 10,
+        }
+    }
+
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = instance.into();
+        self
+    }
+
+    /// Presence interval; a member silent for three intervals loses its partitions.
+    pub fn with_heartbeat(mut self, heartbeat: Duration) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    pub fn with_prefetch(mut self, prefetch: u16) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    /// Queue of partition `partition`.
+    pub fn partition_queue(&self, partition: u16) -> String {
+        format!("{}.{partition}", self.group)
+    }
+
+    fn presence_exchange(&self) -> String {
+        format!("{}.presence", self.group)
+    }
+
+    /// Work exchange `<group>` of the consistent hash exchange plugin, spreading messages
+    /// by routing key over the partition queues `<group>.<n>`. Publish to the group name.
+    pub fn topology(&self) -> Vec<Box<dyn Topology>> {
+        let mut topology: Vec<Box<dyn Topology>> = vec![Box::new(Exchange::new(
+            &self.group,
+            ExchangeKind::Custom("x-consistent-hash".into()),
+        ))];
+        for partition in 0..self.partitions {
+            let queue = self.partition_queue(partition);
+            topology.push(Box::new(Queue::new(&queue)));
+            // the binding key of a consistent hash exchange is the weight
+            topology.push(Box::new(Binding::new(queue, &self.group, "1")));
+        }
+        topology
+    }
+}
+
+/// Partitions owned by `instance`: members are ordered by name and partition `p`
+/// goes to member `p % members`, so every instance computes the same assignment.
+pub fn assign(members: &[String], partitions: u16, instance: &str) -> Vec<u16> {
+    let mut members = members.to_vec();
+    members.sort();
+    members.dedup();
+    let Some(index) = members.iter().position(|m| m == instance) else {
+        return Vec::new();
+    };
+    (0..partitions)
+        .filter(|p| *p as usize % members.len() == index)
+        .collect()
+}
+
+#[derive(Serialize, Deserialize)]
+struct Presence {
+    instance: String,
+}
+
+type Members = Arc<Mutex<HashMap<String, Instant>>>;
+
+/// Consumer group cooperatively splitting the partition queues of a consistent hash
+/// exchange among the live instances.
+///
+/// Instances announce themselves on the `<group>.presence` fanout exchange and each
+/// consumes only the partitions [`assign`] gives it, starting and cancelling partition
+/// consumers as members come and go. Messages of a partition wait in its queue while
+/// it changes hands; for up to a few heartbeats two instances may both consume it.
+pub struct Partitioned {
+    assigned: watch::Receiver<Vec<u16>>,
+    presence: Consumer,
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl Partitioned {
+    pub async fn start<H: DeliveryHandler>(
+        connection: Connection,
+        options: PartitionOptions,
+        handler: H,
+    ) -> Result<Self, RabbitError> {
+        let exchange = options.presence_exchange();
+        let queue = format!("{exchange}.{}", options.instance);
+        let mut topology = options.topology();
+        topology.push(Box::new(Exchange::fanout(&exchange)));
+        topology.push(Box::new(
+            Queue::new(&queue)
+                .with_durable(false)
+                .with_auto_delete(true)
+                .with_argument("x-expires", AMQPValue::LongInt(60_000)),
+        ));
+        topology.push(Box::new(Binding::new(&queue, &exchange, "")));
+        connection.declare(&topology).await?;
+
+        let members: Members = Default::default();
+        let seen = members.clone();
+        let presence = Consumer::start(
+            &connection,
+            ConsumerOptions::new(queue),
+            move |delivery: Delivery| {
+                if let Ok(presence) = serde_json::from_slice::<Presence>(&delivery.data) {
+                    seen.lock()
+                        .unwrap()
+                        .insert(presence.instance, Instant::now());
+                }
+                async { Ok(Ack) }
+            },
+        )
+        .await?;
+
+        let (tx, rx) = watch::channel(Vec::new());
+        let (stop, stopped) = oneshot::channel();
+        let span = trace_span!(
+            "partitions",
+            group = options.group,
+            instance = options.instance
+        );
+        let task = tokio::spawn(
+            rebalance(connection, options, Arc::new(handler), members, tx, stopped)
+                .instrument(span),
+        );
+        Ok(Partitioned {
+            assigned: rx,
+            presence,
+            stop,
+            task,
+        })
+    }
+
+    /// Partitions currently consumed by this instance.
+    pub fn assigned(&self) -> watch::Receiver<Vec<u16>> {
+        self.assigned.clone()
+    }
+
+    /// Leaves the group; the other instances take over after three heartbeats.
+    pub async fn stop(self) -> Result<(), RabbitError> {
+        _ = self.stop.send(());
+        _ = self.task.await;
+        self.presence.cancel().await
+    }
+}
+
+async fn rebalance<H: DeliveryHandler>(
+    connection: Connection,
+    options: PartitionOptions,
+    handler: Arc<H>,
+    members: Members,
+    assigned: watch::Sender<Vec<u16>>,
+    mut stopped: oneshot::Receiver<()>,
+) {
+    let publisher = Publisher::new(connection.clone());
+    let announce = Presence {
+        instance: options.instance.clone(),
+    };
+    let mut consumers: BTreeMap<u16, Consumer> = BTreeMap::new();
+    loop {
+        let res = match OutgoingMessage::json(options.presence_exchange(), "", &announce) {
+            Ok(message) => publisher.publish(message).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            warn!(error = format!("{e}"), "presence heartbeat failed");
+        }
+        let live: Vec<String> = {
+            let mut members = members.lock().unwrap();
+            members.retain(|_, seen| seen.elapsed() < options.heartbeat * 3);
+            members.insert(options.instance.clone(), Instant::now());
+            members.keys().cloned().collect()
+        };
+        let owned = assign(&live, options.partitions, &options.instance);
+        let lost: Vec<u16> = consumers
+            .keys()
+            .filter(|p| !owned.contains(p))
+            .copied()
+            .collect();
+        for partition in lost {
+            if let Some(consumer) = consumers.remove(&partition) {
+                info!(partition, "partition released");
+                if let Err(e) = consumer.cancel().await {
+                    warn!(error = format!("{e}"), partition, "partition cancel failed");
+                }
+            }
+        }
+        for &partition in &owned {
+            if consumers.contains_key(&partition) {
+                continue;
+            }
+            let consumer_options = ConsumerOptions::new(options.partition_queue(partition))
+                .with_prefetch(options.prefetch);
+            let started = match Consumer::open(&connection, &consumer_options).await {
+                Ok(channel) => {
+                    Consumer::consume(&connection, channel, consumer_options, handler.clone()).await
+                }
+                Err(e) => Err(e),
+            };
+            match started {
+                Ok(consumer) => {
+                    info!(partition, "partition acquired");
+                    consumers.insert(partition, consumer);
+                }
+                Err(e) => warn!(
+                    error = format!("{e}"),
+                    partition, "partition consume failed"
+                ),
+            }
+        }
+        assigned.send_replace(consumers.keys().copied().collect());
+        tokio::select! {
+            _ = tokio::time::sleep(options.heartbeat) => {}
+            _ = &mut stopped => break,
+        }
+    }
+    for (partition, consumer) in consumers {
+        if let Err(e) = consumer.cancel().await {
+            warn!(error = format!("{e}"), partition, "partition cancel failed");
+        }
+    }
+    assigned.send_replace(Vec::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_partitions_over_sorted_members() {
+        let members = members(&["c", "a", "b"]);
+        assert_eq!(assign(&members, 7, "a"), [0, 3, 6]);
+        assert_eq!(assign(&members, 7, "b"), [1, 4]);
+        assert_eq!(assign(&members, 7, "c"), [2, 5]);
+    }
+
+    #[test]
+    fn every_member_computes_the_same_assignment() {
+        let members = members(&["b", "a", "b", "c"]);
+        let mut all: Vec<u16> = ["a", "b", "c"]
+            .iter()
+            .flat_map(|m| assign(&members, 10, m))
+            .collect();
+        all.sort_unstable();
+        assert_eq!(all, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn unknown_instance_gets_nothing() {
+        assert!(assign(&members(&["a", "b"]), 4, "z").is_empty());
+        assert!(assign(&[], 4, "a").is_empty());
+    }
+}
@@ -0,0 +1,227 @@
+//! Ambient context of the originating request (user id, locale, request id, ...)
+//! carried in message headers from publisher to handler.
+//!
+//! Code runs with a context through [`scope`] and reads it back with [`current`].
+//! A [`Publisher`](super::Publisher) with [`ContextPropagation`] writes the current
+//! context into the headers of each message, and a handler wrapped with
+//! [`ContextPropagation::handler`] runs inside the context read from them.
+//!
+//! The [`Correlation`] ids are always propagated: publishers write them and each
+//! handler runs in a span carrying them, so logs across services line up.
+
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    future::Future,
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use lapin::{types::FieldTable, BasicProperties};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
+
+use super::{
+    consumer::{Ack, AckHandle, Delivery, DeliveryHandler, Nack, Validation},
+    headers,
+};
+
+type Ambient = Arc<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>;
+
+tokio::task_local! {
+    static AMBIENT: Ambient;
+}
+
+/// Runs `future` with `context` as the current context of its type, keeping the
+/// contexts of other types already set.
+pub async fn scope<C, F>(context: C, future: F) -> F::Output
+where
+    C: Send + Sync + 'static,
+    F: Future,
+{
+    let mut ambient = AMBIENT
+        .try_with(|ambient| HashMap::clone(ambient))
+        .unwrap_or_default();
+    ambient.insert(TypeId::of::<C>(), Arc::new(context));
+    AMBIENT.scope(Arc::new(ambient), future).await
+}
+
+/// Context of type `C` the current task runs with, if any.
+pub fn current<C: Clone + 'static>() -> Option<C> {
+    AMBIENT
+        .try_with(|ambient| ambient.get(&TypeId::of::<C>())?.downcast_ref().cloned())
+        .ok()
+        .flatten()
+}
+
+/// Header with the id shared by all messages caused by one originating request.
+pub const TRACE_ID: &str = "x-trace-id";
+/// Header with the id of the originating request, when the application sets one.
+pub const REQUEST_ID: &str = "x-request-id";
+
+/// Ids correlating the logs of everything one request causes, without OpenTelemetry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Correlation {
+    pub trace_id: String,
+    pub request_id: Option<String>,
+}
+
+impl Correlation {
+    /// Correlation of a new trace.
+    pub fn new() -> Self {
+        Correlation {
+            trace_id: uuid::Uuid::new_v4().simple().to_string(),
+            request_id: None,
+        }
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    pub fn from_headers(table: &FieldTable) -> Option<Self> {
+        Some(Correlation {
+            trace_id: headers::get_str(table, TRACE_ID)?,
+            request_id: headers::get_str(table, REQUEST_ID),
+        })
+    }
+
+    /// Writes the ids, keeping ids the message already carries.
+    pub fn write_headers(&self, table: &mut FieldTable) {
+        if !table.inner().contains_key(TRACE_ID) {
+            headers::set_str(table, TRACE_ID, &self.trace_id);
+        }
+        if let Some(request_id) = &self.request_id {
+            if !table.inner().contains_key(REQUEST_ID) {
+                headers::set_str(table, REQUEST_ID, request_id);
+            }
+        }
+    }
+}
+
+impl Default for Correlation {
+    fn default() -> Self {
+        Correlation::new()
+    }
+}
+
+/// `future` with the contexts of the current task, for spawning it on another task.
+pub(crate) fn carry<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let ambient = AMBIENT.try_with(Arc::clone).ok();
+    async move {
+        match ambient {
+            Some(ambient) => AMBIENT.scope(ambient, future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// Writes context into outgoing headers.
+pub(crate) trait Inject: Send + Sync {
+    fn inject(&self, headers: &mut FieldTable);
+}
+
+/// Carries the context `C` as JSON in one header.
+pub struct ContextPropagation<C> {
+    header: Arc<str>,
+    _context: PhantomData<fn() -> C>,
+}
+
+impl<C> Clone for ContextPropagation<C> {
+    fn clone(&self) -> Self {
+        ContextPropagation {
+            header: self.header.clone(),
+            _context: PhantomData,
+        }
+    }
+}
+
+impl<C> ContextPropagation<C>
+where
+    C: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    pub fn new(header: impl Into<String>) -> Self {
+        ContextPropagation {
+            header: header.into().into(),
+            _context: PhantomData,
+        }
+    }
+
+    /// Context carried by `headers`; a context that does not decode is logged and ignored.
+    pub fn extract(&self, headers: &FieldTable) -> Option<C> {
+        let json = headers::get_str(headers, &self.header)?;
+        match serde_json::from_str(&json) {
+            Ok(context) => Some(context),
+            Err(e) => {
+                warn!(
+                    error = format!("{e}"),
+                    header = &*self.header,
+                    "malformed context"
+                );
+                None
+            }
+        }
+    }
+
+    /// Runs `handler` inside the context carried by each delivery.
+    pub fn handler<H: DeliveryHandler>(&self, handler: H) -> Propagating<C, H> {
+        Propagating {
+            propagation: self.clone(),
+            handler,
+        }
+    }
+}
+
+impl<C> Inject for ContextPropagation<C>
+where
+    C: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    fn inject(&self, headers: &mut FieldTable) {
+        let Some(context) = current::<C>() else {
+            return;
+        };
+        match serde_json::to_string(&context) {
+            Ok(json) => headers::set_str(headers, &self.header, &json),
+            Err(e) => warn!(error = format!("{e}"), "context does not serialize"),
+        }
+    }
+}
+
+/// Handler running inside the propagated context, see [`ContextPropagation::handler`].
+pub struct Propagating<C, H> {
+    propagation: ContextPropagation<C>,
+    handler: H,
+}
+
+#[async_trait]
+impl<C, H> DeliveryHandler for Propagating<C, H>
+where
+    C: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    H: DeliveryHandler,
+{
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        self.handler.validate(properties)
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        match self
+            .propagation
+            .extract(&headers::headers(&delivery.properties))
+        {
+            Some(context) => scope(context, self.handler.handle(delivery)).await,
+            None => self.handler.handle(delivery).await,
+        }
+    }
+
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        match self
+            .propagation
+            .extract(&headers::headers(&delivery.properties))
+        {
+            Some(context) => scope(context, self.handler.handle_with(delivery, ack)).await,
+            None => self.handler.handle_with(delivery, ack).await,
+        }
+    }
+}
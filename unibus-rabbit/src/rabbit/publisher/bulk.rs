@@ -0,0 +1,38 @@
+use futures::future::join_all;
+
+use super::{OutgoingMessage, Publisher};
+use crate::{rabbit::RabbitError, shutdown::Stage};
+
+impl Publisher {
+    /// Writes all `messages` to the channel first, then waits for the broker confirms of
+    /// the whole batch at once instead of one round trip per message, e.g. for imports.
+    ///
+    /// Returns the outcome of each message in order; a failed write does not stop the
+    /// rest. Confirms are awaited whatever the guarantee of the publisher, bounded by its
+    /// confirm timeout without republishing; retries are left to the caller.
+    pub async fn publish_batch(
+        &self,
+        messages: Vec<OutgoingMessage>,
+    ) -> Vec<Result<(), RabbitError>> {
+        let _guard = self.shutdown.as_ref().map(|s| s.guard(Stage::Publishers));
+        let mut receipts = Vec::with_capacity(messages.len());
+        for message in messages {
+            receipts.push(self.send(message).await);
+        }
+        let window = self.confirm_timeout.map(|(window, _)| window);
+        join_all(receipts.into_iter().map(|receipt| async move {
+            let mut receipt = receipt?;
+            let Some(window) = window else {
+                return receipt.await;
+            };
+            match tokio::time::timeout(window, &mut receipt).await {
+                Ok(res) => res,
+                Err(_) => {
+                    receipt.detach();
+                    Err(RabbitError::ConfirmTimeout(window))
+                }
+            }
+        }))
+        .await
+    }
+}
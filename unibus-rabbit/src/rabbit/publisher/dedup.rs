@@ -0,0 +1,136 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct Seen {
+    ids: HashMap<String, Instant>,
+    order: VecDeque<(Instant, String)>,
+}
+
+/// Message ids published within the last `window`, shared by publisher clones.
+pub(crate) struct DedupWindow {
+    window: Duration,
+    seen: Mutex<Seen>,
+    suppressed: AtomicU64,
+}
+
+impl DedupWindow {
+    pub(crate) fn new(window: Duration) -> Self {
+        DedupWindow {
+            window,
+            seen: Default::default(),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    /// Records `id` until the returned claim is dropped without being kept;
+    /// `None` when it was already published inside the window.
+    pub(crate) fn claim(self: &Arc<Self>, id: &str) -> Option<Claim> {
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        let Seen { ids, order } = &mut *seen;
+        while let Some((at, _)) = order.front() {
+            if now.duration_since(*at) < self.window {
+                break;
+            }
+            let (at, expired) = order.pop_front().unwrap();
+            if ids.get(&expired) == Some(&at) {
+                ids.remove(&expired);
+            }
+        }
+        if ids.contains_key(id) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        ids.insert(id.to_owned(), now);
+        order.push_back((now, id.to_owned()));
+        Some(Claim {
+            window: self.clone(),
+            id: id.to_owned(),
+            at: now,
+            kept: false,
+        })
+    }
+
+    fn forget(&self, id: &str, at: Instant) {
+        let mut seen = self.seen.lock().unwrap();
+        if seen.ids.get(id) == Some(&at) {
+            seen.ids.remove(id);
+        }
+    }
+
+    pub(crate) fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+}
+
+/// Message id of a publish in flight, forgotten again unless the publish succeeded,
+/// so that a failed publish can be tried once more.
+pub(crate) struct Claim {
+    window: Arc<DedupWindow>,
+    id: String,
+    at: Instant,
+    kept: bool,
+}
+
+impl Claim {
+    pub(crate) fn keep(mut self) {
+        self.kept = true;
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if !self.kept {
+            self.window.forget(&self.id, self.at);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suppresses_ids_seen_inside_the_window() {
+        let window = Arc::new(DedupWindow::new(Duration::from_secs(60)));
+        window.claim("a").unwrap().keep();
+        window.claim("b").unwrap().keep();
+        assert!(window.claim("a").is_none());
+        assert!(window.claim("a").is_none());
+        assert_eq!(window.suppressed(), 2);
+    }
+
+    #[test]
+    fn suppresses_ids_still_in_flight() {
+        let window = Arc::new(DedupWindow::new(Duration::from_secs(60)));
+        let _claim = window.claim("a").unwrap();
+        assert!(window.claim("a").is_none());
+    }
+
+    #[test]
+    fn forgets_ids_of_failed_publishes() {
+        let window = Arc::new(DedupWindow::new(Duration::from_secs(60)));
+        drop(window.claim("a").unwrap());
+        window.claim("a").unwrap().keep();
+        assert!(window.claim("a").is_none());
+        assert_eq!(window.suppressed(), 1);
+    }
+
+    #[test]
+    fn admits_ids_again_once_the_window_passed() {
+        let window = Arc::new(DedupWindow::new(Duration::ZERO));
+        window.claim("a").unwrap().keep();
+        window.claim("a").unwrap().keep();
+        assert_eq!(window.suppressed(), 0);
+        let seen = window.seen.lock().unwrap();
+        assert_eq!(seen.ids.len(), 1);
+        assert_eq!(seen.order.len(), 1);
+    }
+}
@@ -0,0 +1,54 @@
+use lapin::{
+    options::QueueDeclareOptions,
+    protocol::{AMQPErrorKind, AMQPSoftError},
+    types::FieldTable,
+};
+
+use super::{OutgoingMessage, PublishReceipt, Publisher};
+use crate::rabbit::{ChannelPurpose, RabbitError};
+
+impl Publisher {
+    /// Publishes `payload` through the default exchange straight to `queue`.
+    ///
+    /// The queue is checked with a passive declare on a separate channel the first
+    /// time, and [`RabbitError::QueueMissing`] returned instead of a silent drop.
+    /// Existing queues are cached for the life of the publisher and its clones.
+    pub async fn send_to_queue(
+        &self,
+        queue: &str,
+        payload: impl Into<Vec<u8>>,
+    ) -> Result<PublishReceipt, RabbitError> {
+        self.ensure_queue(queue).await?;
+        self.send(OutgoingMessage::new("", queue, payload)).await
+    }
+
+    async fn ensure_queue(&self, queue: &str) -> Result<(), RabbitError> {
+        if self.known_queues.lock().await.contains(queue) {
+            return Ok(());
+        }
+        let channel = self.source.create_channel_for(ChannelPurpose::Admin).await?;
+        let declared = channel
+            .queue_declare(
+                queue,
+                QueueDeclareOptions {
+                    passive: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await;
+        match declared {
+            Ok(_) => {
+                _ = channel.close(200, "OK").await;
+                self.known_queues.lock().await.insert(queue.to_string());
+                Ok(())
+            }
+            Err(lapin::Error::ProtocolError(e))
+                if *e.kind() == AMQPErrorKind::Soft(AMQPSoftError::NOTFOUND) =>
+            {
+                Err(RabbitError::QueueMissing(queue.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+/// How far [`Publisher::publish`](super::Publisher::publish) follows a message before
+/// returning, trading latency for reliability.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PublishGuarantee {
+    /// Returns at once; the message is sent in the background and failures are only logged.
+    FireAndForget,
+    /// Returns once the message is written to the channel, without waiting for a confirm.
+    BrokerReceived,
+    /// Waits for the broker confirm.
+    #[default]
+    Confirmed,
+    /// Marks the message persistent and waits for the broker confirm, which for durable
+    /// queues comes once the message is written to disk.
+    ConfirmedAndPersisted,
+}
+
+impl PublishGuarantee {
+    /// Delivery mode of persistent messages.
+    pub(super) const PERSISTENT: u8 = 2;
+}
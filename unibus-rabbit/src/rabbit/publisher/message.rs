@@ -0,0 +1,72 @@
+use lapin::BasicProperties;
+use serde::Serialize;
+use unibus_core::Envelope;
+
+use crate::rabbit::{consumer::STREAM_FILTER_VALUE, headers, rpc::JSON_CONTENT_TYPE, RabbitError};
+
+#[derive(Clone, Debug)]
+pub struct OutgoingMessage {
+    pub exchange: String,
+    pub routing_key: String,
+    pub payload: Vec<u8>,
+    pub properties: BasicProperties,
+    pub mandatory: bool,
+}
+
+impl OutgoingMessage {
+    pub fn new(
+        exchange: impl Into<String>,
+        routing_key: impl Into<String>,
+        payload: impl Into<Vec<u8>>,
+    ) -> Self {
+        OutgoingMessage {
+            exchange: exchange.into(),
+            routing_key: routing_key.into(),
+            payload: payload.into(),
+            properties: Default::default(),
+            mandatory: false,
+        }
+    }
+
+    pub fn json<T: Serialize>(
+        exchange: impl Into<String>,
+        routing_key: impl Into<String>,
+        body: &T,
+    ) -> Result<Self, RabbitError> {
+        let message = Self::new(exchange, routing_key, serde_json::to_vec(body)?);
+        Ok(message.with_properties(
+            BasicProperties::default().with_content_type(JSON_CONTENT_TYPE.into()),
+        ))
+    }
+
+    pub fn with_properties(mut self, properties: BasicProperties) -> Self {
+        self.properties = properties;
+        self
+    }
+
+    /// Filter value stream consumers select messages by, see
+    /// [`StreamFilter`](crate::rabbit::consumer::StreamFilter).
+    pub fn with_stream_filter_value(mut self, value: &str) -> Self {
+        let mut table = headers::headers(&self.properties);
+        headers::set_str(&mut table, STREAM_FILTER_VALUE, value);
+        self.properties = self.properties.with_headers(table);
+        self
+    }
+
+    pub fn with_mandatory(mut self, mandatory: bool) -> Self {
+        self.mandatory = mandatory;
+        self
+    }
+}
+
+/// Headers of the envelope become AMQP headers, its message type the `type` property.
+impl From<Envelope> for OutgoingMessage {
+    fn from(envelope: Envelope) -> Self {
+        let properties = BasicProperties::default()
+            .with_content_type(envelope.content_type.into())
+            .with_type(envelope.message_type.into())
+            .with_headers(headers::from_map(&envelope.headers));
+        OutgoingMessage::new(envelope.destination, envelope.routing_key, envelope.payload)
+            .with_properties(properties)
+    }
+}
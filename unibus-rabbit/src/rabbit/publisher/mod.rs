@@ -0,0 +1,447 @@
+mod bulk;
+mod dedup;
+mod direct;
+mod guarantee;
+mod message;
+mod ordered;
+mod probe;
+mod receipt;
+mod reply;
+mod retry;
+mod unroutable;
+
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use lapin::options::BasicPublishOptions;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, trace, warn, Instrument};
+
+pub use guarantee::PublishGuarantee;
+pub use message::*;
+pub use ordered::OrderedPublisher;
+pub(crate) use probe::PublisherProbe;
+pub use receipt::*;
+pub use reply::*;
+pub use retry::RetryPolicy;
+pub use unroutable::UnroutableMessage;
+
+use super::{
+    consumer::Feeds,
+    headers, metrics, otel,
+    propagation::{self, ContextPropagation, Correlation, Inject},
+    telemetry, Channel, ChannelPurpose, ChannelSource, RabbitError, SizeLimit,
+};
+use crate::shutdown::{Shutdown, Stage};
+use dedup::DedupWindow;
+use receipt::Pending;
+use unroutable::OnUnroutable;
+
+/// Publishes with confirms on its own channel, reopened after a reconnect.
+/// Clones share the channel and the deduplication window.
+///
+/// Messages carry the [`Correlation`] ids of the current task, or of a new trace.
+#[derive(Clone)]
+pub struct Publisher {
+    source: Arc<dyn ChannelSource>,
+    channel: Arc<Mutex<Option<Channel>>>,
+    dedup: Option<Arc<DedupWindow>>,
+    shutdown: Option<Shutdown>,
+    known_queues: Arc<Mutex<HashSet<String>>>,
+    confirm_timeout: Option<(Duration, bool)>,
+    republished: Arc<AtomicU64>,
+    size_limit: Option<SizeLimit>,
+    guarantee: PublishGuarantee,
+    propagation: Vec<Arc<dyn Inject>>,
+    retry: Option<RetryPolicy>,
+    pending: Arc<AtomicUsize>,
+    mandatory: bool,
+    on_unroutable: Option<OnUnroutable>,
+}
+
+impl Publisher {
+    /// Publisher on `source`, following its shutdown token unless
+    /// [`Publisher::with_shutdown`] sets another.
+    pub fn new(source: impl ChannelSource + 'static) -> Self {
+        Publisher {
+            shutdown: source.shutdown(),
+            source: Arc::new(source),
+            channel: Default::default(),
+            dedup: None,
+            known_queues: Default::default(),
+            confirm_timeout: None,
+            republished: Default::default(),
+            size_limit: None,
+            guarantee: PublishGuarantee::default(),
+            propagation: Vec::new(),
+            retry: None,
+            pending: Default::default(),
+            mandatory: false,
+            on_unroutable: None,
+        }
+    }
+
+    /// Refuses new messages from [`Stage::Publishers`] on; pending confirms hold that
+    /// stage until they settle. Publishes of consumer handlers also hold the shutdown
+    /// of the consumer, see [`ConsumerStatus::feeds`](super::consumer::ConsumerStatus).
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Collapses publishes of a message id already sent within `window` into a no-op.
+    /// Messages without a message id are never deduplicated; the id of a failed publish
+    /// is forgotten again, and retries by the [`RetryPolicy`] are never suppressed.
+    pub fn with_dedup(mut self, window: Duration) -> Self {
+        self.dedup = Some(Arc::new(DedupWindow::new(window)));
+        self
+    }
+
+    /// Publishes suppressed by the deduplication window so far.
+    pub fn suppressed_duplicates(&self) -> u64 {
+        self.dedup.as_ref().map_or(0, |d| d.suppressed())
+    }
+
+    /// Bounds the wait for a confirm in [`Publisher::publish`] to `window`.
+    ///
+    /// Past it the publish fails with [`RabbitError::ConfirmTimeout`], or with
+    /// `republish` the message is sent once more with the same message id, relying on
+    /// consumer side deduplication; messages without a message id are not republished.
+    pub fn with_confirm_timeout(mut self, window: Duration, republish: bool) -> Self {
+        self.confirm_timeout = Some((window, republish));
+        self
+    }
+
+    /// Messages republished after a confirm timeout so far.
+    pub fn republished(&self) -> u64 {
+        self.republished.load(Ordering::Relaxed)
+    }
+
+    /// Publishes of this publisher and its clones still waiting for the broker confirm.
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Guarantee of [`Publisher::publish`]; [`PublishGuarantee::Confirmed`] by default.
+    pub fn with_guarantee(mut self, guarantee: PublishGuarantee) -> Self {
+        self.guarantee = guarantee;
+        self
+    }
+
+    /// Writes the current context `C` into the headers of each message.
+    pub fn with_propagation<C>(mut self, propagation: ContextPropagation<C>) -> Self
+    where
+        C: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        self.propagation.push(Arc::new(propagation));
+        self
+    }
+
+    /// Refuses payloads past the reject threshold with [`RabbitError::PayloadTooLarge`].
+    pub fn with_size_limit(mut self, limit: SizeLimit) -> Self {
+        self.size_limit = Some(limit);
+        self
+    }
+
+    /// Retries failed publishes of [`Publisher::publish`] and [`Publisher::publish_with`]
+    /// by `policy`, once the connection is ready again.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Publishes every message mandatory, so the broker returns what no queue takes
+    /// instead of dropping it; the publish then fails with [`RabbitError::Unroutable`].
+    pub fn with_mandatory(mut self, mandatory: bool) -> Self {
+        self.mandatory = mandatory;
+        self
+    }
+
+    /// Hands each message the broker returned to `callback`, also for receipts that were
+    /// dropped or detached.
+    pub fn with_on_unroutable(
+        mut self,
+        callback: impl Fn(UnroutableMessage) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_unroutable = Some(Arc::new(callback));
+        self
+    }
+
+    async fn channel(&self) -> Result<Channel, RabbitError> {
+        let mut channel = self.channel.lock().await;
+        match &*channel {
+            Some(c) if c.status().connected() => Ok(c.clone()),
+            _ => {
+                trace!("opening publisher channel");
+                let c = self
+                    .source
+                    .create_channel_for(ChannelPurpose::PublishConfirm)
+                    .await?;
+                *channel = Some(c.clone());
+                Ok(c)
+            }
+        }
+    }
+
+    /// Publishes `message` and returns the pending broker confirm.
+    pub async fn send(&self, message: OutgoingMessage) -> Result<PublishReceipt, RabbitError> {
+        self.send_checked(message, true).await
+    }
+
+    /// [`Publisher::send`] past the deduplication window, for a message sent again on
+    /// purpose, e.g. by a relay after a failure.
+    pub(crate) async fn resend(
+        &self,
+        message: OutgoingMessage,
+    ) -> Result<PublishReceipt, RabbitError> {
+        self.send_checked(message, false).await
+    }
+
+    /// [`Publisher::send`], consulting the deduplication window only with `dedup`.
+    async fn send_checked(
+        &self,
+        mut message: OutgoingMessage,
+        dedup: bool,
+    ) -> Result<PublishReceipt, RabbitError> {
+        if self
+            .shutdown
+            .as_ref()
+            .is_some_and(|s| s.reached(Stage::Publishers))
+        {
+            return Err(RabbitError::ShuttingDown);
+        }
+        self.source.ensure_exchange(&message.exchange)?;
+        if let Some(limit) = &self.size_limit {
+            limit.check(message.payload.len(), &message.exchange)?;
+        }
+        let window = self.dedup.as_ref().filter(|_| dedup);
+        let mut claim = None;
+        if let (Some(window), Some(id)) = (window, message.properties.message_id()) {
+            claim = window.claim(id.as_str());
+            if claim.is_none() {
+                debug!(message_id = id.as_str(), "duplicate publish suppressed");
+                return Ok(PublishReceipt::ready());
+            }
+        }
+        let mut table = headers::headers(&message.properties);
+        propagation::current::<Correlation>()
+            .unwrap_or_default()
+            .write_headers(&mut table);
+        for propagation in &self.propagation {
+            propagation.inject(&mut table);
+        }
+        otel::inject(&mut table);
+        message.properties = message.properties.with_headers(table);
+        Ok(self.send_unchecked(message).await?.with_claim(claim))
+    }
+
+    async fn send_unchecked(
+        &self,
+        message: OutgoingMessage,
+    ) -> Result<PublishReceipt, RabbitError> {
+        let channel = self.channel().await?;
+        let confirm = channel
+            .basic_publish(
+                &message.exchange,
+                &message.routing_key,
+                BasicPublishOptions {
+                    mandatory: message.mandatory || self.mandatory,
+                    ..Default::default()
+                },
+                &message.payload,
+                message.properties,
+            )
+            .await?;
+        let connection = self.source.name();
+        metrics::publish("unibus_published_total", &connection, &message.exchange);
+        let mut guards: Vec<_> = self
+            .shutdown
+            .iter()
+            .map(|s| s.guard(Stage::Publishers))
+            .collect();
+        if let Some(feeds) = propagation::current::<Feeds>() {
+            guards.extend(feeds.publish(&connection, self.shutdown.as_ref()));
+        }
+        if let Some(payload) = telemetry::sample_payload(&message.payload) {
+            debug!(
+                exchange = message.exchange,
+                routing_key = message.routing_key,
+                payload,
+                "published"
+            );
+        }
+        let receipt = PublishReceipt::new(confirm, message.exchange, message.routing_key)
+            .with_connection(connection)
+            .with_pending(Pending::new(&self.pending, guards))
+            .with_on_unroutable(self.on_unroutable.clone());
+        Ok(receipt)
+    }
+
+    /// Publishes `message` with the guarantee of the publisher, by default waiting for
+    /// the broker confirm.
+    pub async fn publish(&self, message: OutgoingMessage) -> Result<(), RabbitError> {
+        self.publish_with(message, self.guarantee).await
+    }
+
+    /// Publishes `message` with `guarantee` instead of the guarantee of the publisher.
+    pub async fn publish_with(
+        &self,
+        message: OutgoingMessage,
+        guarantee: PublishGuarantee,
+    ) -> Result<(), RabbitError> {
+        self.publish_retrying(message, guarantee, true).await
+    }
+
+    /// [`Publisher::publish_with`] past the deduplication window, see
+    /// [`Publisher::resend`].
+    pub(crate) async fn resend_with(
+        &self,
+        message: OutgoingMessage,
+        guarantee: PublishGuarantee,
+    ) -> Result<(), RabbitError> {
+        self.publish_retrying(message, guarantee, false).await
+    }
+
+    async fn publish_retrying(
+        &self,
+        message: OutgoingMessage,
+        guarantee: PublishGuarantee,
+        dedup: bool,
+    ) -> Result<(), RabbitError> {
+        let Some(policy) = &self.retry else {
+            return self.publish_once(message, guarantee, dedup).await;
+        };
+        let mut attempt = 1;
+        loop {
+            let dedup = dedup && attempt == 1;
+            let error = match self.publish_once(message.clone(), guarantee, dedup).await {
+                Ok(()) => return Ok(()),
+                Err(e) if policy.retries(attempt, &e) => e,
+                Err(e) => return Err(e),
+            };
+            let backoff = policy.backoff(attempt);
+            warn!(
+                error = format!("{error}"),
+                attempt,
+                exchange = message.exchange,
+                routing_key = message.routing_key,
+                "publish failed, retrying in {backoff:?}"
+            );
+            tokio::time::sleep(backoff).await;
+            self.source.ready().await?;
+            attempt += 1;
+        }
+    }
+
+    async fn publish_once(
+        &self,
+        mut message: OutgoingMessage,
+        guarantee: PublishGuarantee,
+        dedup: bool,
+    ) -> Result<(), RabbitError> {
+        match guarantee {
+            PublishGuarantee::FireAndForget => {
+                let publisher = self.clone();
+                tokio::spawn(
+                    propagation::carry(async move {
+                        match publisher.send_checked(message, dedup).await {
+                            Ok(receipt) => receipt.detach(),
+                            Err(e) => warn!(error = format!("{e}"), "background publish failed"),
+                        }
+                    })
+                    .in_current_span(),
+                );
+                Ok(())
+            }
+            PublishGuarantee::BrokerReceived => {
+                self.send_checked(message, dedup).await?.detach();
+                Ok(())
+            }
+            PublishGuarantee::Confirmed => self.confirmed(message, dedup).await,
+            PublishGuarantee::ConfirmedAndPersisted => {
+                message.properties = message
+                    .properties
+                    .with_delivery_mode(PublishGuarantee::PERSISTENT);
+                self.confirmed(message, dedup).await
+            }
+        }
+    }
+
+    async fn confirmed(&self, message: OutgoingMessage, dedup: bool) -> Result<(), RabbitError> {
+        let _guard = self.shutdown.as_ref().map(|s| s.guard(Stage::Publishers));
+        let Some((window, republish)) = self.confirm_timeout else {
+            return self.send_checked(message, dedup).await?.await;
+        };
+        let retry =
+            (republish && message.properties.message_id().is_some()).then(|| message.clone());
+        let mut receipt = self.send_checked(message, dedup).await?;
+        match tokio::time::timeout(window, &mut receipt).await {
+            Ok(res) => return res,
+            Err(_) => receipt.detach(),
+        }
+        let Some(message) = retry else {
+            return Err(RabbitError::ConfirmTimeout(window));
+        };
+        self.republished.fetch_add(1, Ordering::Relaxed);
+        warn!(
+            message_id = message
+                .properties
+                .message_id()
+                .as_ref()
+                .map(|id| id.as_str()),
+            exchange = message.exchange,
+            routing_key = message.routing_key,
+            "confirm timed out, message republished"
+        );
+        let mut receipt = self.send_unchecked(message).await?;
+        match tokio::time::timeout(window, &mut receipt).await {
+            Ok(res) => res,
+            Err(_) => {
+                receipt.detach();
+                Err(RabbitError::ConfirmTimeout(window))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lapin::BasicProperties;
+
+    use super::*;
+    use crate::rabbit::Unreachable;
+
+    fn message() -> OutgoingMessage {
+        OutgoingMessage::new("orders", "created", "{}")
+            .with_properties(BasicProperties::default().with_message_id("m-1".into()))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_bypass_the_dedup_window() {
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone())
+            .with_dedup(Duration::from_secs(60))
+            .with_retry(RetryPolicy::new(3));
+        let res = publisher.publish(message()).await;
+        assert!(matches!(res, Err(RabbitError::NotConnected)));
+        assert_eq!(source.attempts(), 3);
+        assert_eq!(publisher.suppressed_duplicates(), 0);
+    }
+
+    #[tokio::test]
+    async fn failed_publishes_leave_the_dedup_window() {
+        let source = Unreachable::default();
+        let publisher = Publisher::new(source.clone()).with_dedup(Duration::from_secs(60));
+        assert!(publisher.send(message()).await.is_err());
+        assert!(publisher.clone().send(message()).await.is_err());
+        assert_eq!(source.attempts(), 2);
+        assert_eq!(publisher.suppressed_duplicates(), 0);
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::{OutgoingMessage, PublishGuarantee, Publisher};
+use crate::rabbit::RabbitError;
+
+type KeyLocks = HashMap<(String, String), Arc<tokio::sync::Mutex<()>>>;
+
+/// Publisher that keeps messages with the same exchange and routing key in call order.
+///
+/// A publish waits for the broker confirm of the previous message with its key before
+/// it is sent, whatever the guarantee of the wrapped publisher, and all messages go
+/// through one channel, so a failure is reported before
+/// any later message of that key leaves and can be retried without reordering.
+/// Messages with different keys do not wait for each other. The broker then delivers
+/// a key's messages in that order to a single consumer of a queue; prefetch above one
+/// with concurrent handlers, or several consumers, reorders them again.
+#[derive(Clone)]
+pub struct OrderedPublisher {
+    publisher: Publisher,
+    keys: Arc<Mutex<KeyLocks>>,
+}
+
+impl OrderedPublisher {
+    pub fn new(publisher: Publisher) -> Self {
+        OrderedPublisher {
+            publisher,
+            keys: Default::default(),
+        }
+    }
+
+    pub async fn publish(&self, message: OutgoingMessage) -> Result<(), RabbitError> {
+        let key = (message.exchange.clone(), message.routing_key.clone());
+        let lock = self
+            .keys
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        // the turn passes on only once the broker confirmed
+        let guarantee = match self.publisher.guarantee {
+            PublishGuarantee::ConfirmedAndPersisted => PublishGuarantee::ConfirmedAndPersisted,
+            _ => PublishGuarantee::Confirmed,
+        };
+        let res = {
+            let _turn = lock.lock().await;
+            self.publisher.publish_with(message, guarantee).await
+        };
+        let mut keys = self.keys.lock().unwrap();
+        // the map and this call hold the last references: nobody else is queued for the key
+        if Arc::strong_count(&lock) == 2 {
+            keys.remove(&key);
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::future::join_all;
+
+    use super::*;
+    use crate::rabbit::Unreachable;
+
+    #[tokio::test]
+    async fn drops_the_lock_of_a_key_once_nobody_waits() {
+        let source = Unreachable::default();
+        let publisher = OrderedPublisher::new(Publisher::new(source.clone()));
+        let publishes = ["a", "a", "b", "a", "b"].map(|key| {
+            let publisher = publisher.clone();
+            let message = OutgoingMessage::new("orders", key, "{}");
+            async move { publisher.publish(message).await }
+        });
+        for res in join_all(publishes).await {
+            assert!(matches!(res, Err(RabbitError::NotConnected)));
+        }
+        assert_eq!(source.attempts(), 5);
+        assert!(publisher.keys.lock().unwrap().is_empty());
+    }
+}
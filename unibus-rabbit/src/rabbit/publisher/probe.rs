@@ -0,0 +1,55 @@
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, Weak,
+};
+
+use super::{dedup::DedupWindow, Publisher};
+
+/// Counters of a publisher and its clones that do not keep them alive, for
+/// diagnostics collected over the lifetime of a host.
+#[derive(Clone)]
+pub(crate) struct PublisherProbe {
+    connection: String,
+    pending: Weak<AtomicUsize>,
+    republished: Weak<AtomicU64>,
+    dedup: Option<Weak<DedupWindow>>,
+}
+
+impl PublisherProbe {
+    /// Whether a clone of the publisher is still around.
+    pub(crate) fn alive(&self) -> bool {
+        self.republished.strong_count() > 0
+    }
+
+    pub(crate) fn connection(&self) -> &str {
+        &self.connection
+    }
+
+    pub(crate) fn pending(&self) -> usize {
+        self.pending
+            .upgrade()
+            .map_or(0, |p| p.load(Ordering::SeqCst))
+    }
+
+    pub(crate) fn republished(&self) -> u64 {
+        self.republished
+            .upgrade()
+            .map_or(0, |r| r.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn suppressed_duplicates(&self) -> u64 {
+        let dedup = self.dedup.as_ref().and_then(Weak::upgrade);
+        dedup.map_or(0, |d| d.suppressed())
+    }
+}
+
+impl Publisher {
+    pub(crate) fn probe(&self) -> PublisherProbe {
+        PublisherProbe {
+            connection: self.source.name(),
+            pending: Arc::downgrade(&self.pending),
+            republished: Arc::downgrade(&self.republished),
+            dedup: self.dedup.as_ref().map(Arc::downgrade),
+        }
+    }
+}
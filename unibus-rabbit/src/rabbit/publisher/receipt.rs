@@ -0,0 +1,239 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use lapin::publisher_confirm::{Confirmation, PublisherConfirm};
+use tracing::{debug, warn};
+
+use super::{
+    dedup::Claim,
+    unroutable::{OnUnroutable, UnroutableMessage},
+};
+use crate::{
+    rabbit::{metrics, RabbitError},
+    shutdown::ShutdownGuard,
+};
+
+/// Counts a publish as pending and holds its shutdown guards until its confirm settles.
+pub(crate) struct Pending {
+    count: Arc<AtomicUsize>,
+    _guards: Vec<ShutdownGuard>,
+}
+
+impl Pending {
+    pub(crate) fn new(count: &Arc<AtomicUsize>, guards: Vec<ShutdownGuard>) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Pending {
+            count: count.clone(),
+            _guards: guards,
+        }
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Pending broker confirm of one published message.
+///
+/// Awaiting the receipt yields the publish outcome. A receipt dropped before
+/// completion keeps waiting in the background and logs the outcome with a warning,
+/// since a silently dropped confirm is how messages get lost unnoticed;
+/// use [`PublishReceipt::detach`] when fire-and-forget is intended.
+#[must_use = "a dropped receipt loses the publish outcome; await it or call `detach`"]
+pub struct PublishReceipt {
+    confirm: Option<PublisherConfirm>,
+    exchange: String,
+    routing_key: String,
+    connection: String,
+    detached: bool,
+    pending: Option<Pending>,
+    on_unroutable: Option<OnUnroutable>,
+    claim: Option<Claim>,
+    /// Outcome of a receipt settled without a confirm, see [`PublishReceipt::test_failed`].
+    failure: Option<RabbitError>,
+}
+
+impl PublishReceipt {
+    pub(crate) fn new(confirm: PublisherConfirm, exchange: String, routing_key: String) -> Self {
+        PublishReceipt {
+            confirm: Some(confirm),
+            exchange,
+            routing_key,
+            connection: String::new(),
+            detached: false,
+            pending: None,
+            on_unroutable: None,
+            claim: None,
+            failure: None,
+        }
+    }
+
+    /// Labels the confirm metrics with `connection`.
+    pub(crate) fn with_connection(mut self, connection: String) -> Self {
+        self.connection = connection;
+        self
+    }
+
+    /// Counts the publish in `pending` until the confirm settles.
+    pub(crate) fn with_pending(mut self, pending: Pending) -> Self {
+        self.pending = Some(pending);
+        self
+    }
+
+    /// Hands a returned message to `on_unroutable` once the confirm arrives.
+    pub(crate) fn with_on_unroutable(mut self, on_unroutable: Option<OnUnroutable>) -> Self {
+        self.on_unroutable = on_unroutable;
+        self
+    }
+
+    /// Keeps the message id in the deduplication window only once the publish is confirmed.
+    pub(crate) fn with_claim(mut self, claim: Option<Claim>) -> Self {
+        self.claim = claim;
+        self
+    }
+
+    /// Receipt of a publish that completed without reaching the broker.
+    pub(crate) fn ready() -> Self {
+        PublishReceipt {
+            confirm: None,
+            exchange: String::new(),
+            routing_key: String::new(),
+            connection: String::new(),
+            detached: false,
+            pending: None,
+            on_unroutable: None,
+            claim: None,
+            failure: None,
+        }
+    }
+
+    /// Receipt resolving to a confirmed publish, for handler tests without a broker.
+    pub fn test_confirmed() -> Self {
+        PublishReceipt::ready()
+    }
+
+    /// Receipt resolving to `error`, for handler tests without a broker.
+    pub fn test_failed(error: RabbitError) -> Self {
+        let mut receipt = PublishReceipt::ready();
+        receipt.failure = Some(error);
+        receipt
+    }
+
+    /// Gives up on the outcome; failures are still logged, without the dropped receipt warning.
+    pub fn detach(mut self) {
+        self.detached = true;
+    }
+
+    fn settle(claim: Option<Claim>, outcome: &Result<(), RabbitError>) {
+        if let (Some(claim), Ok(())) = (claim, outcome) {
+            claim.keep();
+        }
+    }
+
+    fn outcome(
+        confirm: Result<Confirmation, lapin::Error>,
+        exchange: &str,
+        routing_key: &str,
+        connection: &str,
+        on_unroutable: Option<&OnUnroutable>,
+    ) -> Result<(), RabbitError> {
+        match confirm? {
+            Confirmation::Ack(Some(returned)) => {
+                if let Some(on_unroutable) = on_unroutable {
+                    on_unroutable(UnroutableMessage::from_return(*returned));
+                }
+                Err(RabbitError::Unroutable {
+                    exchange: exchange.to_owned(),
+                    routing_key: routing_key.to_owned(),
+                })
+            }
+            Confirmation::Nack(_) => {
+                metrics::publish("unibus_nacked_total", connection, exchange);
+                Err(RabbitError::Nacked)
+            }
+            _ => {
+                metrics::publish("unibus_confirmed_total", connection, exchange);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Future for PublishReceipt {
+    type Output = Result<(), RabbitError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.as_mut().get_mut();
+        let Some(confirm) = this.confirm.as_mut() else {
+            return Poll::Ready(this.failure.take().map_or(Ok(()), Err));
+        };
+        match Pin::new(confirm).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(res) => {
+                this.confirm = None;
+                this.pending = None;
+                let outcome = Self::outcome(
+                    res,
+                    &this.exchange,
+                    &this.routing_key,
+                    &this.connection,
+                    this.on_unroutable.as_ref(),
+                );
+                Self::settle(this.claim.take(), &outcome);
+                Poll::Ready(outcome)
+            }
+        }
+    }
+}
+
+impl Drop for PublishReceipt {
+    fn drop(&mut self) {
+        let Some(confirm) = self.confirm.take() else {
+            return;
+        };
+        let exchange = std::mem::take(&mut self.exchange);
+        let routing_key = std::mem::take(&mut self.routing_key);
+        let connection = std::mem::take(&mut self.connection);
+        let detached = self.detached;
+        let pending = self.pending.take();
+        let on_unroutable = self.on_unroutable.take();
+        let claim = self.claim.take();
+        if !detached {
+            warn!(exchange, routing_key, "publish receipt dropped before confirm");
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        runtime.spawn(async move {
+            let confirm = confirm.await;
+            drop(pending);
+            let outcome = Self::outcome(
+                confirm,
+                &exchange,
+                &routing_key,
+                &connection,
+                on_unroutable.as_ref(),
+            );
+            Self::settle(claim, &outcome);
+            match outcome {
+                Ok(()) if detached => {}
+                Ok(()) => debug!(exchange, routing_key, "dropped publish confirmed"),
+                Err(e) => warn!(
+                    exchange,
+                    routing_key,
+                    error = format!("{e}"),
+                    "dropped publish failed"
+                ),
+            }
+        });
+    }
+}
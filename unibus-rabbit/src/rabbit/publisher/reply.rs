@@ -0,0 +1,85 @@
+use lapin::{types::FieldTable, BasicProperties};
+use tracing::info;
+
+use super::{OutgoingMessage, PublishGuarantee, Publisher};
+use crate::rabbit::{headers, RabbitError};
+
+const REPLY_EXCHANGE: &str = "x-reply-exchange";
+const REPLY_INSTANCE: &str = "x-reply-instance";
+const REPLY_FALLBACK: &str = "x-reply-fallback";
+
+/// Where results of a long-running flow go back to: the originating instance
+/// on a control exchange, or the shared queue when that instance is gone.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplyAddress {
+    pub exchange: String,
+    pub instance: String,
+    pub fallback_queue: String,
+}
+
+impl ReplyAddress {
+    pub fn write_headers(&self, headers: &mut FieldTable) {
+        headers::set_str(headers, REPLY_EXCHANGE, &self.exchange);
+        headers::set_str(headers, REPLY_INSTANCE, &self.instance);
+        headers::set_str(headers, REPLY_FALLBACK, &self.fallback_queue);
+    }
+
+    pub fn from_headers(headers: &FieldTable) -> Option<Self> {
+        Some(ReplyAddress {
+            exchange: headers::get_str(headers, REPLY_EXCHANGE)?,
+            instance: headers::get_str(headers, REPLY_INSTANCE)?,
+            fallback_queue: headers::get_str(headers, REPLY_FALLBACK)?,
+        })
+    }
+}
+
+impl Publisher {
+    /// Routes a reply to the instance in `address`, republishing to the
+    /// fallback queue when no instance queue is bound any more.
+    ///
+    /// Both publishes are mandatory and confirmed whatever the guarantee of the
+    /// publisher, since a gone instance only shows as a returned message.
+    pub async fn reply_to_instance(
+        &self,
+        address: &ReplyAddress,
+        payload: Vec<u8>,
+        properties: BasicProperties,
+    ) -> Result<(), RabbitError> {
+        let direct = OutgoingMessage::new(&address.exchange, &address.instance, payload.clone())
+            .with_properties(properties.clone())
+            .with_mandatory(true);
+        match self.publish_with(direct, PublishGuarantee::Confirmed).await {
+            Err(RabbitError::Unroutable { .. }) => {
+                info!(instance = address.instance, "instance gone, reply sent to shared queue");
+                let shared = OutgoingMessage::new("", &address.fallback_queue, payload)
+                    .with_properties(properties)
+                    .with_mandatory(true);
+                self.publish_with(shared, PublishGuarantee::Confirmed).await
+            }
+            res => res,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rabbit::Unreachable;
+
+    #[tokio::test]
+    async fn waits_for_the_outcome_under_any_guarantee() {
+        let source = Unreachable::default();
+        let publisher =
+            Publisher::new(source.clone()).with_guarantee(PublishGuarantee::FireAndForget);
+        let address = ReplyAddress {
+            exchange: "control".into(),
+            instance: "instance-1".into(),
+            fallback_queue: "replies".into(),
+        };
+        let res = publisher
+            .reply_to_instance(&address, b"{}".to_vec(), BasicProperties::default())
+            .await;
+        assert!(matches!(res, Err(RabbitError::NotConnected)));
+        assert_eq!(source.attempts(), 1);
+    }
+}
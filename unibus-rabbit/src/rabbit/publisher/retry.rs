@@ -0,0 +1,93 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::rabbit::{jitter, RabbitError};
+
+type RetryOn = Arc<dyn Fn(&RabbitError) -> bool + Send + Sync>;
+
+/// When and how often [`Publisher::publish`](super::Publisher::publish) tries again
+/// after a failure, see [`Publisher::with_retry`](super::Publisher::with_retry).
+///
+/// Before each new attempt the publisher backs off exponentially, with jitter, and
+/// then waits for the connection to report ready.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    retry_on: RetryOn,
+}
+
+impl Default for RetryPolicy {
+    /// Five attempts from 100ms up to 10s apart, on [`RetryPolicy::transient`] errors.
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            retry_on: Arc::new(RetryPolicy::transient),
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("initial_backoff", &self.initial_backoff)
+            .field("max_backoff", &self.max_backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32) -> Self {
+        RetryPolicy::default().with_max_attempts(max_attempts)
+    }
+
+    /// Attempts in total, the first one included.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Backoff before the second attempt, doubled for each further one up to `max`.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Retries only the errors `retry_on` accepts, instead of [`RetryPolicy::transient`].
+    pub fn with_retry_on(
+        mut self,
+        retry_on: impl Fn(&RabbitError) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_on = Arc::new(retry_on);
+        self
+    }
+
+    /// Errors of a lost or not yet ready connection, which a reconnect may cure.
+    pub fn transient(error: &RabbitError) -> bool {
+        matches!(
+            error,
+            RabbitError::NotConnected
+                | RabbitError::Amqp(_)
+                | RabbitError::ChannelBudgetExceeded(_)
+                | RabbitError::ConfirmTimeout(_)
+        )
+    }
+
+    pub(super) fn retries(&self, attempt: u32, error: &RabbitError) -> bool {
+        attempt < self.max_attempts && (self.retry_on)(error)
+    }
+
+    /// Half of the exponential delay after `attempt`, plus up to the other half at random.
+    pub(super) fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        delay / 2 + jitter(delay / 2)
+    }
+}
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use lapin::{message::BasicReturnMessage, BasicProperties};
+
+/// Mandatory message the broker returned because no queue was bound to take it.
+#[derive(Clone, Debug)]
+pub struct UnroutableMessage {
+    pub exchange: String,
+    pub routing_key: String,
+    /// `NO_ROUTE` (312) for an unroutable message.
+    pub reply_code: u16,
+    pub reply_text: String,
+    pub properties: BasicProperties,
+    pub payload: Vec<u8>,
+}
+
+impl UnroutableMessage {
+    pub(crate) fn from_return(returned: BasicReturnMessage) -> Self {
+        let BasicReturnMessage {
+            delivery,
+            reply_code,
+            reply_text,
+        } = returned;
+        UnroutableMessage {
+            exchange: delivery.exchange.to_string(),
+            routing_key: delivery.routing_key.to_string(),
+            reply_code,
+            reply_text: reply_text.to_string(),
+            properties: delivery.properties,
+            payload: delivery.data,
+        }
+    }
+}
+
+/// Callback of [`Publisher::with_on_unroutable`](super::Publisher::with_on_unroutable).
+pub(crate) type OnUnroutable = Arc<dyn Fn(UnroutableMessage) + Send + Sync>;
@@ -0,0 +1,263 @@
+use std::{collections::BTreeMap, future::Future, sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use lapin::{
+    options::{BasicConsumeOptions, BasicPublishOptions},
+    types::FieldTable,
+    BasicProperties,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{trace, warn};
+use uuid::Uuid;
+
+pub use unibus_core::rpc::{RpcError, RpcStatus};
+
+use super::{
+    consumer::{Ack, Consumer, ConsumerOptions, Delivery},
+    headers,
+    topology::{Binding, Queue},
+    ChannelPurpose, Connection, OutgoingMessage, PublishGuarantee, PublishReceipt, Publisher,
+    RabbitError,
+};
+
+pub(crate) const DIRECT_REPLY_TO: &str = "amq.rabbitmq.reply-to";
+pub(crate) const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Request broadcast to every responder bound to a fan-out exchange.
+pub trait ScatterQuery: Serialize {
+    type Reply: DeserializeOwned;
+
+    /// Fan-out exchange the responders bind their queues to.
+    fn exchange() -> &'static str;
+}
+
+/// Request/response calls over queues, with replies through direct reply-to.
+pub struct Rpc {
+    connection: Connection,
+}
+
+impl Rpc {
+    pub fn new(connection: Connection) -> Self {
+        Rpc { connection }
+    }
+
+    /// Publishes `request` to the query exchange and collects replies until
+    /// `min_responses` arrived or `timeout` elapsed, whichever comes first.
+    /// Replies collected so far are returned in both cases.
+    pub async fn scatter<Q: ScatterQuery>(
+        &self,
+        request: &Q,
+        min_responses: usize,
+        timeout: Duration,
+    ) -> Result<Vec<Q::Reply>, RabbitError> {
+        let payload = serde_json::to_vec(request)?;
+        let channel = self.connection.create_channel().await?;
+        let mut replies = channel
+            .basic_consume(
+                DIRECT_REPLY_TO,
+                "",
+                BasicConsumeOptions {
+                    no_ack: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        let correlation_id = Uuid::new_v4().to_string();
+        channel
+            .basic_publish(
+                Q::exchange(),
+                "",
+                BasicPublishOptions::default(),
+                &payload,
+                BasicProperties::default()
+                    .with_content_type(JSON_CONTENT_TYPE.into())
+                    .with_correlation_id(correlation_id.as_str().into())
+                    .with_reply_to(DIRECT_REPLY_TO.into()),
+            )
+            .await?;
+
+        let mut collected = Vec::new();
+        let deadline = tokio::time::sleep(timeout);
+        tokio::pin!(deadline);
+        while collected.len() < min_responses {
+            tokio::select! {
+                _ = &mut deadline => break,
+                delivery = replies.next() => match delivery {
+                    Some(Ok(delivery)) => {
+                        let matches = delivery
+                            .properties
+                            .correlation_id()
+                            .as_ref()
+                            .is_some_and(|id| id.as_str() == correlation_id);
+                        if !matches {
+                            trace!("skipped reply with foreign correlation id");
+                            continue;
+                        }
+                        let (delivery, _) = Delivery::from_lapin(delivery);
+                        match delivery.json() {
+                            Ok(reply) => collected.push(reply),
+                            Err(e) => warn!(error = format!("{e}"), "malformed scatter reply"),
+                        }
+                    }
+                    Some(Err(e)) => return Err(e.into()),
+                    None => break,
+                },
+            }
+        }
+
+        _ = channel.close(0, "scatter finished").await;
+        Ok(collected)
+    }
+
+    /// Sends `request` to `queue` and waits up to `timeout` for the reply.
+    ///
+    /// The request is published mandatory with a confirm, so a missing queue fails with
+    /// [`RabbitError::Unroutable`] instead of running into the timeout. Failures reported
+    /// by the server come back as [`RabbitError::Rpc`].
+    pub async fn call<Req, Resp>(
+        &self,
+        queue: &str,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Resp, RabbitError>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let payload = serde_json::to_vec(request)?;
+        let channel = self
+            .connection
+            .create_channel_for(ChannelPurpose::PublishConfirm)
+            .await?;
+        let mut replies = channel
+            .basic_consume(
+                DIRECT_REPLY_TO,
+                "",
+                BasicConsumeOptions {
+                    no_ack: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+
+        let correlation_id = Uuid::new_v4().to_string();
+        let confirm = channel
+            .basic_publish(
+                "",
+                queue,
+                BasicPublishOptions {
+                    mandatory: true,
+                    ..Default::default()
+                },
+                &payload,
+                BasicProperties::default()
+                    .with_content_type(JSON_CONTENT_TYPE.into())
+                    .with_correlation_id(correlation_id.as_str().into())
+                    .with_reply_to(DIRECT_REPLY_TO.into()),
+            )
+            .await?;
+
+        let reply = tokio::time::timeout(timeout, async {
+            PublishReceipt::new(confirm, String::new(), queue.to_owned()).await?;
+            while let Some(delivery) = replies.next().await {
+                let (delivery, _) = Delivery::from_lapin(delivery?);
+                let matches = delivery
+                    .properties
+                    .correlation_id()
+                    .as_ref()
+                    .is_some_and(|id| id.as_str() == correlation_id);
+                if !matches {
+                    trace!("skipped reply with foreign correlation id");
+                    continue;
+                }
+                let table = headers::headers(&delivery.properties);
+                if let Some(e) = RpcError::from_headers(&headers::to_map(&table)) {
+                    return Err(RabbitError::Rpc(e));
+                }
+                return delivery
+                    .json()
+                    .map_err(|e| RabbitError::Serialization(e.source));
+            }
+            Err(RabbitError::NotConnected)
+        })
+        .await;
+
+        _ = channel.close(0, "call finished").await;
+        reply.unwrap_or(Err(RabbitError::RpcTimeout(timeout)))
+    }
+
+    /// Declares `queue` and answers each request on it with the result of `handler`,
+    /// sent to the reply address of the request; an [`RpcError`] travels in headers.
+    /// Requests that do not decode as `Req` are answered with
+    /// [`RpcStatus::InvalidArgument`]; requests without a reply address are only handled.
+    pub async fn serve<Req, Resp, H, Fut>(
+        &self,
+        queue: &str,
+        handler: H,
+    ) -> Result<Consumer, RabbitError>
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Resp: Serialize + Send + 'static,
+        H: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, RpcError>> + Send,
+    {
+        self.connection
+            .declare(&[Box::new(Queue::new(queue))])
+            .await?;
+        // requests reach the queue through the default exchange
+        self.connection.register(&Binding::new(queue, "", queue));
+        let publisher = Publisher::new(self.connection.clone());
+        let handler = Arc::new(handler);
+        Consumer::start(
+            &self.connection,
+            ConsumerOptions::new(queue),
+            move |delivery: Delivery| {
+                let publisher = publisher.clone();
+                let handler = handler.clone();
+                async move {
+                    let outcome = match delivery.json() {
+                        Ok(request) => handler(request).await.and_then(|response| {
+                            serde_json::to_vec(&response).map_err(|e| {
+                                RpcError::internal(format!("response does not serialize: {e}"))
+                            })
+                        }),
+                        Err(e) => Err(RpcError::invalid_argument(format!("{e}"))),
+                    };
+                    let Some(reply_to) = delivery.properties.reply_to() else {
+                        warn!("request without reply address, response dropped");
+                        return Ok(Ack);
+                    };
+                    let mut reply_headers = BTreeMap::new();
+                    let payload = match outcome {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            warn!(error = format!("{e}"), "request failed");
+                            e.write_headers(&mut reply_headers);
+                            Vec::new()
+                        }
+                    };
+                    let mut properties = BasicProperties::default()
+                        .with_content_type(JSON_CONTENT_TYPE.into())
+                        .with_headers(headers::from_map(&reply_headers));
+                    if let Some(id) = delivery.properties.correlation_id() {
+                        properties = properties.with_correlation_id(id.clone());
+                    }
+                    let reply = OutgoingMessage::new("", reply_to.as_str(), payload)
+                        .with_properties(properties);
+                    // the caller may be gone; direct reply-to drops such replies
+                    if let Err(e) = publisher
+                        .publish_with(reply, PublishGuarantee::BrokerReceived)
+                        .await
+                    {
+                        warn!(error = format!("{e}"), "reply failed");
+                    }
+                    Ok(Ack)
+                }
+            },
+        )
+        .await
+    }
+}
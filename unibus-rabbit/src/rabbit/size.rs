@@ -0,0 +1,36 @@
+use tracing::warn;
+
+use super::RabbitError;
+
+/// Payload size thresholds: above `warn` a message is logged, above `reject` it is refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SizeLimit {
+    pub warn: usize,
+    pub reject: usize,
+}
+
+impl SizeLimit {
+    pub fn new(warn: usize, reject: usize) -> Self {
+        SizeLimit { warn, reject }
+    }
+
+    /// Only refuses, without a warning threshold.
+    pub fn reject_above(reject: usize) -> Self {
+        SizeLimit::new(reject, reject)
+    }
+
+    /// Warns past the warning threshold; fails with [`RabbitError::PayloadTooLarge`]
+    /// past the reject threshold.
+    pub(crate) fn check(&self, size: usize, target: &str) -> Result<(), RabbitError> {
+        if size > self.reject {
+            return Err(RabbitError::PayloadTooLarge {
+                size,
+                limit: self.reject,
+            });
+        }
+        if size > self.warn {
+            warn!(size, limit = self.warn, target, "large payload");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashMap, io, sync::Arc, thread, time::Duration};
+
+use actix::{prelude::*, WeakAddr};
+
+use tokio::sync::{oneshot, watch};
+use tracing::{error, info};
+
+use super::{
+    connection::{CloseConnection, ConnectionActor, GetStateWatch, Connection, EventLog},
+    ConnectionOptions, ConnectionPool, ConnectionState, RabbitError,
+};
+use crate::shutdown::Shutdown;
+
+/// Connection actors started by the client with their close timeout.
+#[derive(Default)]
+struct RabbitActor {
+    connections: Vec<(WeakAddr<ConnectionActor>, Duration)>,
+}
+
+impl Actor for RabbitActor {
+    type Context = Context<Self>;
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("rabbit client system started");
+    }
+    fn stopped(&mut self, ctx: &mut Self::Context) {
+        System::current().stop();
+        info!("rabbit client system stopped");
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "Connection")]
+struct Open(ConnectionOptions);
+
+impl Handler<Open> for RabbitActor {
+    type Result = MessageResult<Open>;
+    fn handle(&mut self, msg: Open, ctx: &mut Self::Context) -> Self::Result {
+        let events = EventLog::new(msg.0.history);
+        let addr = ConnectionActor::new(msg.0.clone(), events.clone()).start();
+        self.connections.retain(|(c, _)| c.upgrade().is_some());
+        self.connections.push((addr.downgrade(), msg.0.close_timeout));
+        MessageResult(Connection::new(addr, events, &msg.0))
+    }
+}
+
+impl Handler<super::Close> for RabbitActor {
+    type Result = ();
+    fn handle(&mut self, _: super::Close, ctx: &mut Self::Context) -> Self::Result {
+        ctx.stop();
+    }
+}
+
+#[derive(Message)]
+#[rtype(result = "()")]
+struct CloseAll;
+
+impl Handler<CloseAll> for RabbitActor {
+    type Result = ResponseActFuture<Self, ()>;
+    fn handle(&mut self, _: CloseAll, _: &mut Self::Context) -> Self::Result {
+        let closing = self
+            .connections
+            .drain(..)
+            .filter_map(|(c, timeout)| Some((c.upgrade()?, timeout)))
+            .map(|(c, timeout)| async move {
+                // a connection closed meanwhile has nothing left to do
+                _ = c
+                    .send(CloseConnection {
+                        code: 0,
+                        text: "client closed".to_owned(),
+                        timeout,
+                    })
+                    .await;
+            })
+            .collect::<Vec<_>>();
+        Box::pin(
+            futures::future::join_all(closing)
+                .into_actor(self)
+                .map(|_, _, ctx| ctx.stop()),
+        )
+    }
+}
+
+pub struct RabbitClient {
+    addr: Addr<RabbitActor>,
+    /// Result of the system run, sent when the rabbit thread finishes.
+    finished: Option<oneshot::Receiver<io::Result<()>>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for RabbitClient {
+    fn drop(&mut self) {
+        self.addr.do_send(super::Close);
+    }
+}
+
+impl RabbitClient {
+    pub async fn connect(&self, options : ConnectionOptions) -> Result<Connection, MailboxError> {
+        self.addr.send(Open(options)).await
+    }
+
+    /// Closes every connection opened by the client, each within its close timeout,
+    /// then stops the rabbit system and waits for its thread to finish.
+    pub async fn close(mut self) -> Result<(), RabbitError> {
+        self.addr.send(CloseAll).await?;
+        let finished = self.finished.take().expect("close runs once");
+        let run = finished
+            .await
+            .unwrap_or_else(|_| Err(io::Error::other("rabbit thread exited early")));
+        if let Some(thread) = self.thread.take() {
+            let joined = tokio::task::spawn_blocking(move || thread.join()).await;
+            if !matches!(joined, Ok(Ok(()))) {
+                return Err(RabbitError::System(io::Error::other("rabbit thread panicked")));
+            }
+        }
+        run.map_err(RabbitError::System)
+    }
+
+    /// Opens `size` connections with the same options, named `<name>-<n>`. They share
+    /// the shutdown token of the options, or one of the pool's own when it has none.
+    pub async fn connect_pool(
+        &self,
+        mut options: ConnectionOptions,
+        size: usize,
+    ) -> Result<ConnectionPool, RabbitError> {
+        options.shutdown.get_or_insert_with(Shutdown::new);
+        let mut connections = Vec::with_capacity(size);
+        for n in 0..size {
+            let mut options = options.clone();
+            options.name = format!("{}-{n}", options.name);
+            connections.push(self.connect(options).await?);
+        }
+        ConnectionPool::new(connections).await
+    }
+}
+
+/// How the dedicated rabbit thread drives actors and broker I/O.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuntimeMode {
+    /// Actors and broker I/O share one thread, isolated from the application runtime.
+    CurrentThread,
+    /// Actors stay on the rabbit thread, spawned I/O tasks run on `worker_threads` workers.
+    MultiThread { worker_threads: usize },
+}
+
+type ThreadHook = Arc<dyn Fn() + Send + Sync>;
+
+pub struct ClientBuilder {
+    thread_name: String,
+    runtime: RuntimeMode,
+    on_thread_start: Option<ThreadHook>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder {
+            thread_name: "unibus-rabbit".to_owned(),
+            runtime: RuntimeMode::CurrentThread,
+            on_thread_start: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn with_thread_name(mut self, name: impl Into<String>) -> Self {
+        self.thread_name = name.into();
+        self
+    }
+
+    pub fn with_runtime(mut self, runtime: RuntimeMode) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Runs `hook` first on every thread of the rabbit runtime, e.g. to pin it to a core.
+    pub fn with_on_thread_start(mut self, hook: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_thread_start = Some(Arc::new(hook));
+        self
+    }
+
+    fn build_runtime(&self) -> io::Result<tokio::runtime::Runtime> {
+        let mut builder = match self.runtime {
+            RuntimeMode::CurrentThread => tokio::runtime::Builder::new_current_thread(),
+            RuntimeMode::MultiThread { worker_threads } => {
+                let mut builder = tokio::runtime::Builder::new_multi_thread();
+                builder
+                    .worker_threads(worker_threads)
+                    .thread_name(format!("{}-worker", self.thread_name));
+                if let Some(hook) = self.on_thread_start.clone() {
+                    builder.on_thread_start(move || hook());
+                }
+                builder
+            }
+        };
+        builder.enable_all().build()
+    }
+
+    pub async fn start(self) -> io::Result<RabbitClient> {
+        let (tx, rx) = oneshot::channel::<io::Result<Addr<RabbitActor>>>();
+        let (done, finished) = oneshot::channel();
+        let thread = thread::Builder::new()
+            .name(self.thread_name.clone())
+            .spawn(move || {
+                if let Some(hook) = &self.on_thread_start {
+                    hook();
+                }
+                let runtime = match self.build_runtime() {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        _ = tx.send(Err(e));
+                        return;
+                    }
+                };
+                let sys = System::with_tokio_rt(|| runtime);
+                sys.block_on(async move {
+                    let addr = RabbitActor::default().start();
+                    _ = tx.send(Ok(addr));
+                });
+                let run = sys.run();
+                match &run {
+                    Ok(_) => info!("system finished"),
+                    Err(e) => error!(error = format!("{e}"), "system finished"),
+                };
+                _ = done.send(run);
+            })?;
+        let addr = rx
+            .await
+            .map_err(|_| io::Error::other("rabbit thread exited during startup"))??;
+        Ok(RabbitClient {
+            addr,
+            finished: Some(finished),
+            thread: Some(thread),
+        })
+    }
+}
+
+pub async fn start() -> RabbitClient {
+    ClientBuilder::default()
+        .start()
+        .await
+        .expect("rabbit client runtime started")
+}
@@ -0,0 +1,103 @@
+//! Runtime control over the bus's own tracing: the most verbose level let through for
+//! events and spans of the unibus crates, and the fraction of published and consumed
+//! messages whose payload is traced. The setting is process wide and can be changed at
+//! any time, see [`BusHost::set_telemetry`](crate::bus::BusHost::set_telemetry).
+//!
+//! The level only applies where [`filter`] is installed as a per-layer filter, e.g.
+//! `fmt::layer().with_filter(unibus_rabbit::rabbit::telemetry::filter())`.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use tracing::{level_filters::LevelFilter, subscriber::Interest, Metadata};
+use tracing_subscriber::layer::{Context, Filter};
+
+/// Traced payloads are cut after this many bytes.
+const PAYLOAD_LIMIT: usize = 4096;
+
+static LEVEL: AtomicUsize = AtomicUsize::new(5);
+static SAMPLING: AtomicU64 = AtomicU64::new(0);
+
+/// Verbosity of the bus's own tracing and the payload sampling rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Telemetry {
+    /// Everything by default, leaving it to the subscriber's own filters.
+    pub level: LevelFilter,
+    /// Fraction of messages, from 0 to 1, whose payload is traced; none by default.
+    pub sampling: f64,
+}
+
+fn level_index(level: LevelFilter) -> usize {
+    match level {
+        LevelFilter::OFF => 0,
+        LevelFilter::ERROR => 1,
+        LevelFilter::WARN => 2,
+        LevelFilter::INFO => 3,
+        LevelFilter::DEBUG => 4,
+        _ => 5,
+    }
+}
+
+fn level_of(index: usize) -> LevelFilter {
+    match index {
+        0 => LevelFilter::OFF,
+        1 => LevelFilter::ERROR,
+        2 => LevelFilter::WARN,
+        3 => LevelFilter::INFO,
+        4 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+pub fn current() -> Telemetry {
+    Telemetry {
+        level: level_of(LEVEL.load(Ordering::Relaxed)),
+        sampling: f64::from_bits(SAMPLING.load(Ordering::Relaxed)),
+    }
+}
+
+/// Applies `telemetry` from now on; the sampling rate is clamped to `0..=1`.
+pub fn set(telemetry: Telemetry) {
+    let sampling = match telemetry.sampling.is_nan() {
+        true => 0.0,
+        false => telemetry.sampling.clamp(0.0, 1.0),
+    };
+    LEVEL.store(level_index(telemetry.level), Ordering::Relaxed);
+    SAMPLING.store(sampling.to_bits(), Ordering::Relaxed);
+}
+
+/// The payload as text when this message is sampled.
+pub(crate) fn sample_payload(payload: &[u8]) -> Option<String> {
+    let sampling = f64::from_bits(SAMPLING.load(Ordering::Relaxed));
+    if sampling <= 0.0 || rand::random::<f64>() >= sampling {
+        return None;
+    }
+    let cut = payload.len().min(PAYLOAD_LIMIT);
+    Some(String::from_utf8_lossy(&payload[..cut]).into_owned())
+}
+
+fn is_bus(target: &str) -> bool {
+    target.starts_with("unibus_rabbit") || target.starts_with("unibus_core")
+}
+
+/// Per-layer filter applying the runtime level to the unibus targets and letting
+/// everything else through.
+pub fn filter() -> TelemetryFilter {
+    TelemetryFilter
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TelemetryFilter;
+
+impl<S> Filter<S> for TelemetryFilter {
+    fn enabled(&self, meta: &Metadata<'_>, _: &Context<'_, S>) -> bool {
+        !is_bus(meta.target()) || *meta.level() <= current().level
+    }
+
+    /// The level may change at any time, so unibus callsites are asked every time.
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        match is_bus(meta.target()) {
+            true => Interest::sometimes(),
+            false => Interest::always(),
+        }
+    }
+}
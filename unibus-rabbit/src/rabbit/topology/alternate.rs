@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use lapin::types::{AMQPValue, FieldTable};
+
+use super::{Binding, Definitions, Exchange, Queue, Topology, TopologyRegistry};
+use crate::rabbit::{
+    consumer::{Consumer, ConsumerOptions, DeliveryHandler},
+    ChannelSource, RabbitError,
+};
+
+/// Catch-all for messages an exchange can not route: a fanout alternate exchange
+/// with a durable queue bound to it. Attach with [`Exchange::with_unroutable`].
+#[derive(Clone, Debug)]
+pub struct Unroutable {
+    pub exchange: String,
+    pub queue: String,
+}
+
+impl Unroutable {
+    pub fn new(exchange: impl Into<String>, queue: impl Into<String>) -> Self {
+        Unroutable {
+            exchange: exchange.into(),
+            queue: queue.into(),
+        }
+    }
+
+    /// `<exchange>.unroutable` for both the alternate exchange and its queue.
+    pub fn for_exchange(exchange: &str) -> Self {
+        let name = format!("{exchange}.unroutable");
+        Self::new(name.clone(), name)
+    }
+
+    /// Starts `handler` on the catch-all queue, so unroutable messages are observed.
+    pub async fn consume(
+        &self,
+        source: &dyn ChannelSource,
+        handler: impl DeliveryHandler,
+    ) -> Result<Consumer, RabbitError> {
+        Consumer::start(source, ConsumerOptions::new(&self.queue), handler).await
+    }
+
+    fn parts(&self) -> (Exchange, Queue, Binding) {
+        (
+            Exchange::fanout(&self.exchange),
+            Queue::new(&self.queue),
+            Binding::new(&self.queue, &self.exchange, ""),
+        )
+    }
+}
+
+#[async_trait]
+impl Topology for Unroutable {
+    async fn declare(&self, channel: &lapin::Channel) -> Result<(), lapin::Error> {
+        let (exchange, queue, binding) = self.parts();
+        exchange.declare(channel).await?;
+        queue.declare(channel).await?;
+        binding.declare(channel).await
+    }
+
+    fn register(&self, registry: &mut TopologyRegistry) {
+        let (exchange, queue, binding) = self.parts();
+        exchange.register(registry);
+        queue.register(registry);
+        binding.register(registry);
+    }
+
+    fn define(&self, definitions: &mut Definitions) {
+        let (exchange, queue, binding) = self.parts();
+        definitions.add_exchange(&exchange);
+        definitions.add_queue(&queue);
+        definitions.add_binding(&binding);
+    }
+}
+
+pub(super) fn alternate_argument(arguments: &mut FieldTable, unroutable: &Unroutable) {
+    arguments.insert(
+        "alternate-exchange".into(),
+        AMQPValue::LongString(unroutable.exchange.as_str().into()),
+    );
+}
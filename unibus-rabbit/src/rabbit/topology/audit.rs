@@ -0,0 +1,143 @@
+use std::{collections::HashMap, fmt};
+
+use serde_json::Value;
+use tracing::warn;
+
+use super::{definitions::QueueDefinition, Definitions, Topology};
+
+/// Configuration that likely loses messages, found by [`audit`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AuditWarning {
+    /// A durable queue that auto-deletes or expires, bound to a durable exchange:
+    /// messages published while it is gone are dropped.
+    ExpiringDurableQueue { queue: String, exchange: String },
+    /// Messages are dead-lettered into a queue that auto-deletes or expires, so dead
+    /// letters vanish with it.
+    ExpiringDeadLetterTarget { queue: String, source: String },
+    /// A durable queue with a dead letter exchange that auto-deletes or expires:
+    /// deleting a queue discards its messages without dead-lettering them.
+    ExpiringWithDeadLetter { queue: String },
+}
+
+impl AuditWarning {
+    pub fn queue(&self) -> &str {
+        match self {
+            AuditWarning::ExpiringDurableQueue { queue, .. }
+            | AuditWarning::ExpiringDeadLetterTarget { queue, .. }
+            | AuditWarning::ExpiringWithDeadLetter { queue } => queue,
+        }
+    }
+}
+
+impl fmt::Display for AuditWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditWarning::ExpiringDurableQueue { queue, exchange } => write!(
+                f,
+                "durable queue {queue} expires or auto-deletes while bound to durable \
+                 exchange {exchange}; messages routed while it is gone are lost"
+            ),
+            AuditWarning::ExpiringDeadLetterTarget { queue, source } => write!(
+                f,
+                "queue {queue} receives the dead letters of {source} but expires or \
+                 auto-deletes; dead letters are lost with it"
+            ),
+            AuditWarning::ExpiringWithDeadLetter { queue } => write!(
+                f,
+                "durable queue {queue} expires or auto-deletes; its messages are discarded \
+                 on deletion, not dead-lettered"
+            ),
+        }
+    }
+}
+
+/// Checks `topology` for auto-delete and `x-expires` queues in places where they
+/// likely lose messages. [`Connection::declare`](crate::rabbit::Connection::declare)
+/// logs the warnings when applying a topology.
+pub fn audit(topology: &[Box<dyn Topology>]) -> Vec<AuditWarning> {
+    let mut definitions = Definitions::default();
+    for item in topology {
+        item.define(&mut definitions);
+    }
+    audit_definitions(&definitions)
+}
+
+pub(crate) fn audit_definitions(definitions: &Definitions) -> Vec<AuditWarning> {
+    let durable: HashMap<_, _> = definitions
+        .exchanges
+        .iter()
+        .map(|e| (e.name.as_str(), e.durable))
+        .collect();
+    let queues: HashMap<_, _> = definitions
+        .queues
+        .iter()
+        .map(|q| (q.name.as_str(), q))
+        .collect();
+    let mut warnings = Vec::new();
+
+    for queue in definitions.queues.iter().filter(|q| expiring(q)) {
+        if queue.durable {
+            let exchange = definitions.bindings.iter().find(|b| {
+                b.destination == queue.name
+                    // exchanges declared elsewhere are most likely durable
+                    && durable.get(b.source.as_str()).copied().unwrap_or(true)
+            });
+            if let Some(binding) = exchange {
+                warnings.push(AuditWarning::ExpiringDurableQueue {
+                    queue: queue.name.clone(),
+                    exchange: binding.source.clone(),
+                });
+            }
+            if queue.arguments.contains_key("x-dead-letter-exchange") {
+                warnings.push(AuditWarning::ExpiringWithDeadLetter {
+                    queue: queue.name.clone(),
+                });
+            }
+        }
+    }
+
+    for source in &definitions.queues {
+        let Some(exchange) = string(source, "x-dead-letter-exchange") else {
+            continue;
+        };
+        let key = string(source, "x-dead-letter-routing-key");
+        let targets: Vec<&str> = if exchange.is_empty() {
+            // the default exchange routes by queue name
+            key.into_iter().collect()
+        } else {
+            definitions
+                .bindings
+                .iter()
+                .filter(|b| b.source == exchange)
+                .map(|b| b.destination.as_str())
+                .collect()
+        };
+        for target in targets {
+            if queues.get(target).is_some_and(|q| expiring(q)) {
+                warnings.push(AuditWarning::ExpiringDeadLetterTarget {
+                    queue: target.to_owned(),
+                    source: source.name.clone(),
+                });
+            }
+        }
+    }
+    warnings
+}
+
+/// Logs the [`audit`] warnings of `definitions`.
+pub(crate) fn warn_audit(definitions: &Definitions) {
+    for warning in audit_definitions(definitions) {
+        warn!(queue = warning.queue(), "{warning}");
+    }
+}
+
+fn expiring(queue: &QueueDefinition) -> bool {
+    queue.auto_delete || queue.arguments.contains_key("x-expires")
+}
+
+fn string<'a>(queue: &'a QueueDefinition, key: &str) -> Option<&'a str> {
+    match queue.arguments.get(key)? {
+        Value::String(value) => Some(value),
+        _ => None,
+    }
+}
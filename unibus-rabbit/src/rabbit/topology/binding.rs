@@ -0,0 +1,70 @@
+use async_trait::async_trait;
+use lapin::{
+    options::QueueBindOptions,
+    types::{AMQPValue, FieldTable},
+};
+
+use super::{Definitions, Topology, TopologyRegistry};
+
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub queue: String,
+    pub exchange: String,
+    pub routing_key: String,
+    pub arguments: FieldTable,
+}
+
+impl Binding {
+    pub fn new(
+        queue: impl Into<String>,
+        exchange: impl Into<String>,
+        routing_key: impl Into<String>,
+    ) -> Self {
+        Binding {
+            queue: queue.into(),
+            exchange: exchange.into(),
+            routing_key: routing_key.into(),
+            arguments: Default::default(),
+        }
+    }
+
+    pub fn with_argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.arguments.insert(key.into(), value);
+        self
+    }
+
+    /// Removes the binding from the broker; unbinding a missing binding succeeds.
+    pub async fn unbind(&self, channel: &lapin::Channel) -> Result<(), lapin::Error> {
+        channel
+            .queue_unbind(
+                &self.queue,
+                &self.exchange,
+                &self.routing_key,
+                self.arguments.clone(),
+            )
+            .await
+    }
+}
+
+#[async_trait]
+impl Topology for Binding {
+    async fn declare(&self, channel: &lapin::Channel) -> Result<(), lapin::Error> {
+        channel
+            .queue_bind(
+                &self.queue,
+                &self.exchange,
+                &self.routing_key,
+                QueueBindOptions::default(),
+                self.arguments.clone(),
+            )
+            .await
+    }
+
+    fn register(&self, registry: &mut TopologyRegistry) {
+        registry.add_binding(&self.queue, &self.exchange);
+    }
+
+    fn define(&self, definitions: &mut Definitions) {
+        definitions.add_binding(self);
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+
+use lapin::ExchangeKind;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use super::{
+    file::{BindingEntry, ExchangeEntry, QueueEntry, TopologyDocument, TopologyFileError},
+    Binding, Exchange, Queue, Topology,
+};
+use crate::rabbit::headers;
+
+const DEFAULT_VHOST: &str = "/";
+
+/// Exchanges, queues and bindings in the format of the management plugin's
+/// definitions export; other sections of an export are ignored.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Definitions {
+    #[serde(default)]
+    pub exchanges: Vec<ExchangeDefinition>,
+    #[serde(default)]
+    pub queues: Vec<QueueDefinition>,
+    #[serde(default)]
+    pub bindings: Vec<BindingDefinition>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExchangeDefinition {
+    pub name: String,
+    #[serde(default = "default_vhost")]
+    pub vhost: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub durable: bool,
+    pub auto_delete: bool,
+    #[serde(default)]
+    pub internal: bool,
+    #[serde(default)]
+    pub arguments: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueueDefinition {
+    pub name: String,
+    #[serde(default = "default_vhost")]
+    pub vhost: String,
+    pub durable: bool,
+    pub auto_delete: bool,
+    #[serde(default)]
+    pub arguments: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BindingDefinition {
+    pub source: String,
+    #[serde(default = "default_vhost")]
+    pub vhost: String,
+    pub destination: String,
+    /// `queue`, or `exchange` for exchange to exchange bindings.
+    pub destination_type: String,
+    #[serde(default)]
+    pub routing_key: String,
+    #[serde(default)]
+    pub arguments: BTreeMap<String, Value>,
+}
+
+fn default_vhost() -> String {
+    DEFAULT_VHOST.to_owned()
+}
+
+impl Definitions {
+    pub fn add_exchange(&mut self, exchange: &Exchange) {
+        self.exchanges.push(ExchangeDefinition {
+            name: exchange.name.clone(),
+            vhost: default_vhost(),
+            kind: kind_name(&exchange.kind).to_owned(),
+            durable: exchange.durable,
+            auto_delete: exchange.auto_delete,
+            internal: exchange.internal,
+            arguments: arguments(&exchange.arguments),
+        });
+    }
+
+    /// Adds `queue`; exclusive queues live with one connection and are left out,
+    /// as in a broker export.
+    pub fn add_queue(&mut self, queue: &Queue) {
+        if queue.exclusive {
+            return;
+        }
+        self.queues.push(QueueDefinition {
+            name: queue.name.clone(),
+            vhost: default_vhost(),
+            durable: queue.durable,
+            auto_delete: queue.auto_delete,
+            arguments: arguments(&queue.arguments),
+        });
+    }
+
+    pub fn add_binding(&mut self, binding: &Binding) {
+        self.bindings.push(BindingDefinition {
+            source: binding.exchange.clone(),
+            vhost: default_vhost(),
+            destination: binding.queue.clone(),
+            destination_type: "queue".to_owned(),
+            routing_key: binding.routing_key.clone(),
+            arguments: arguments(&binding.arguments),
+        });
+    }
+}
+
+fn kind_name(kind: &ExchangeKind) -> &str {
+    match kind {
+        ExchangeKind::Custom(custom) => custom,
+        ExchangeKind::Direct => "direct",
+        ExchangeKind::Fanout => "fanout",
+        ExchangeKind::Headers => "headers",
+        ExchangeKind::Topic => "topic",
+    }
+}
+
+fn arguments(table: &lapin::types::FieldTable) -> BTreeMap<String, Value> {
+    table
+        .inner()
+        .iter()
+        .map(|(key, value)| (key.to_string(), headers::to_json(value)))
+        .collect()
+}
+
+/// Definitions JSON of `topology`, importable through the management plugin.
+pub fn to_definitions_json(topology: &[Box<dyn Topology>]) -> Result<String, serde_json::Error> {
+    let mut definitions = Definitions::default();
+    for item in topology {
+        item.define(&mut definitions);
+    }
+    serde_json::to_string_pretty(&definitions)
+}
+
+/// Declarations of the exchanges, queues and bindings of a definitions export,
+/// checked like a topology file. Exchange to exchange bindings are skipped.
+pub fn from_definitions_json(json: &str) -> Result<Vec<Box<dyn Topology>>, TopologyFileError> {
+    let definitions: Definitions =
+        serde_json::from_str(json).map_err(|e| TopologyFileError::Parse(format!("{e}")))?;
+    let document = TopologyDocument {
+        exchanges: definitions
+            .exchanges
+            .into_iter()
+            .map(|e| ExchangeEntry {
+                name: e.name,
+                kind: e.kind,
+                durable: e.durable,
+                auto_delete: e.auto_delete,
+                internal: e.internal,
+                arguments: e.arguments,
+            })
+            .collect(),
+        queues: definitions
+            .queues
+            .into_iter()
+            .map(|q| QueueEntry {
+                name: q.name,
+                kind: None,
+                durable: q.durable,
+                exclusive: false,
+                auto_delete: q.auto_delete,
+                arguments: q.arguments,
+            })
+            .collect(),
+        bindings: definitions
+            .bindings
+            .into_iter()
+            .filter(|b| {
+                let to_queue = b.destination_type == "queue";
+                if !to_queue {
+                    warn!(
+                        source = b.source,
+                        destination = b.destination,
+                        "exchange to exchange binding skipped"
+                    );
+                }
+                to_queue
+            })
+            .map(|b| BindingEntry {
+                queue: b.destination,
+                exchange: b.source,
+                routing_key: b.routing_key,
+                arguments: b.arguments,
+            })
+            .collect(),
+    };
+    document.into_topology()
+}
+
+#[cfg(test)]
+mod tests {
+    use lapin::types::AMQPValue;
+
+    use super::*;
+
+    fn topology() -> Vec<Box<dyn Topology>> {
+        vec![
+            Box::new(
+                Exchange::topic("orders")
+                    .with_argument("alternate-exchange", AMQPValue::LongString("lost".into())),
+            ),
+            Box::new(Exchange::fanout("lost").with_internal(true)),
+            Box::new(Queue::quorum("billing").with_delivery_limit(5)),
+            Box::new(Queue::new("replies").with_exclusive(true)),
+            Box::new(
+                Queue::new("audit")
+                    .with_durable(false)
+                    .with_auto_delete(true),
+            ),
+            Box::new(Binding::new("billing", "orders", "order.*")),
+            Box::new(Binding::new("audit", "lost", "")),
+        ]
+    }
+
+    #[test]
+    fn round_trips_through_the_export() {
+        let exported = to_definitions_json(&topology()).unwrap();
+        let imported = from_definitions_json(&exported).unwrap();
+        assert_eq!(to_definitions_json(&imported).unwrap(), exported);
+
+        let definitions: Definitions = serde_json::from_str(&exported).unwrap();
+        let queues: Vec<&str> = definitions.queues.iter().map(|q| q.name.as_str()).collect();
+        assert_eq!(
+            queues,
+            ["billing", "audit"],
+            "exclusive queues are left out"
+        );
+        assert_eq!(definitions.queues[0].arguments["x-delivery-limit"], 5);
+        assert!(definitions.exchanges[1].internal);
+        assert_eq!(definitions.bindings[0].vhost, "/");
+    }
+
+    #[test]
+    fn imports_a_broker_export() {
+        let export = r#"{
+            "rabbit_version": "3.12.0",
+            "users": [],
+            "exchanges": [
+                { "name": "orders", "type": "topic", "durable": true, "auto_delete": false },
+                { "name": "orders.eu", "type": "topic", "durable": true, "auto_delete": false }
+            ],
+            "queues": [{ "name": "billing", "vhost": "/", "durable": true, "auto_delete": false }],
+            "bindings": [
+                { "source": "orders", "destination": "billing", "destination_type": "queue",
+                  "routing_key": "order.*" },
+                { "source": "orders", "destination": "orders.eu", "destination_type": "exchange",
+                  "routing_key": "order.eu.#" }
+            ]
+        }"#;
+        let imported = from_definitions_json(export).unwrap();
+        assert_eq!(
+            imported.len(),
+            4,
+            "exchange to exchange bindings are skipped"
+        );
+    }
+
+    #[test]
+    fn checks_imports_like_topology_files() {
+        let export = r#"{
+            "bindings": [
+                { "source": "orders", "destination": "billing", "destination_type": "queue" }
+            ]
+        }"#;
+        assert!(matches!(
+            from_definitions_json(export),
+            Err(TopologyFileError::Invalid { .. })
+        ));
+        assert!(matches!(
+            from_definitions_json("{\"queues\": 1}"),
+            Err(TopologyFileError::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use lapin::{
+    options::ExchangeDeclareOptions,
+    types::{AMQPValue, FieldTable},
+    ExchangeKind,
+};
+
+use super::{
+    alternate::alternate_argument, Definitions, Topology, TopologyRegistry, Unroutable,
+};
+
+#[derive(Clone, Debug)]
+pub struct Exchange {
+    pub name: String,
+    pub kind: ExchangeKind,
+    pub durable: bool,
+    pub auto_delete: bool,
+    pub internal: bool,
+    pub arguments: FieldTable,
+    pub unroutable: Option<Unroutable>,
+}
+
+impl Exchange {
+    pub fn new(name: impl Into<String>, kind: ExchangeKind) -> Self {
+        Exchange {
+            name: name.into(),
+            kind,
+            durable: true,
+            auto_delete: false,
+            internal: false,
+            arguments: Default::default(),
+            unroutable: None,
+        }
+    }
+
+    pub fn direct(name: impl Into<String>) -> Self {
+        Self::new(name, ExchangeKind::Direct)
+    }
+
+    pub fn fanout(name: impl Into<String>) -> Self {
+        Self::new(name, ExchangeKind::Fanout)
+    }
+
+    pub fn topic(name: impl Into<String>) -> Self {
+        Self::new(name, ExchangeKind::Topic)
+    }
+
+    pub fn headers(name: impl Into<String>) -> Self {
+        Self::new(name, ExchangeKind::Headers)
+    }
+
+    pub fn with_durable(mut self, durable: bool) -> Self {
+        self.durable = durable;
+        self
+    }
+
+    pub fn with_auto_delete(mut self, auto_delete: bool) -> Self {
+        self.auto_delete = auto_delete;
+        self
+    }
+
+    pub fn with_internal(mut self, internal: bool) -> Self {
+        self.internal = internal;
+        self
+    }
+
+    /// Routes messages no binding matches to `unroutable`, declared along with this exchange.
+    pub fn with_unroutable(mut self, unroutable: Unroutable) -> Self {
+        alternate_argument(&mut self.arguments, &unroutable);
+        self.unroutable = Some(unroutable);
+        self
+    }
+
+    /// [`Exchange::with_unroutable`] with the default `<name>.unroutable` catch-all.
+    pub fn with_catch_all(self) -> Self {
+        let unroutable = Unroutable::for_exchange(&self.name);
+        self.with_unroutable(unroutable)
+    }
+
+    pub fn with_argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.arguments.insert(key.into(), value);
+        self
+    }
+}
+
+#[async_trait]
+impl Topology for Exchange {
+    async fn declare(&self, channel: &lapin::Channel) -> Result<(), lapin::Error> {
+        if let Some(unroutable) = &self.unroutable {
+            unroutable.declare(channel).await?;
+        }
+        channel
+            .exchange_declare(
+                &self.name,
+                self.kind.clone(),
+                ExchangeDeclareOptions {
+                    durable: self.durable,
+                    auto_delete: self.auto_delete,
+                    internal: self.internal,
+                    ..Default::default()
+                },
+                self.arguments.clone(),
+            )
+            .await
+    }
+
+    fn register(&self, registry: &mut TopologyRegistry) {
+        if let Some(unroutable) = &self.unroutable {
+            unroutable.register(registry);
+        }
+        registry.add_exchange(&self.name);
+    }
+
+    fn define(&self, definitions: &mut Definitions) {
+        if let Some(unroutable) = &self.unroutable {
+            unroutable.define(definitions);
+        }
+        definitions.add_exchange(self);
+    }
+}
@@ -0,0 +1,427 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+    path::Path,
+};
+
+use lapin::{types::AMQPValue, ExchangeKind};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::{Binding, Exchange, Queue, Topology};
+
+/// Format of a topology document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Format of a file by its extension.
+    pub fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TopologyFileError {
+    #[error("can not read topology file: {0}")]
+    Io(#[from] io::Error),
+    #[error("unknown topology file format of {0}, expected .yaml, .yml or .toml")]
+    UnknownFormat(String),
+    #[error("{0:?} topology files need the `{1}` feature")]
+    Unsupported(Format, &'static str),
+    #[error("malformed topology document: {0}")]
+    Parse(String),
+    /// An entry is inconsistent; `entry` names it, e.g. `queues[2] (orders)`.
+    #[error("{entry}: {message}")]
+    Invalid { entry: String, message: String },
+}
+
+/// Exchanges, queues and bindings as written in a topology file:
+///
+/// ```yaml
+/// exchanges:
+///   - { name: orders, type: topic }
+/// queues:
+///   - { name: billing.orders, type: quorum, arguments: { x-delivery-limit: 5 } }
+/// bindings:
+///   - { queue: billing.orders, exchange: orders, routing_key: "order.*" }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TopologyDocument {
+    #[serde(default)]
+    pub exchanges: Vec<ExchangeEntry>,
+    #[serde(default)]
+    pub queues: Vec<QueueEntry>,
+    #[serde(default)]
+    pub bindings: Vec<BindingEntry>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExchangeEntry {
+    pub name: String,
+    /// `direct`, `fanout`, `topic`, `headers` or a plugin type like `x-consistent-hash`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default = "yes")]
+    pub durable: bool,
+    #[serde(default)]
+    pub auto_delete: bool,
+    #[serde(default)]
+    pub internal: bool,
+    #[serde(default)]
+    pub arguments: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueueEntry {
+    pub name: String,
+    /// `classic` (the default), `quorum` or `stream`.
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>,
+    #[serde(default = "yes")]
+    pub durable: bool,
+    #[serde(default)]
+    pub exclusive: bool,
+    #[serde(default)]
+    pub auto_delete: bool,
+    #[serde(default)]
+    pub arguments: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BindingEntry {
+    pub queue: String,
+    pub exchange: String,
+    #[serde(default)]
+    pub routing_key: String,
+    #[serde(default)]
+    pub arguments: BTreeMap<String, Value>,
+}
+
+fn yes() -> bool {
+    true
+}
+
+/// Loads the topology file at `path`, in the format of its extension.
+pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<Box<dyn Topology>>, TopologyFileError> {
+    let path = path.as_ref();
+    let format = Format::of(path)
+        .ok_or_else(|| TopologyFileError::UnknownFormat(path.display().to_string()))?;
+    from_str(&fs::read_to_string(path)?, format)
+}
+
+/// Parses and validates a topology document.
+pub fn from_str(text: &str, format: Format) -> Result<Vec<Box<dyn Topology>>, TopologyFileError> {
+    TopologyDocument::parse(text, format)?.into_topology()
+}
+
+impl TopologyDocument {
+    pub fn parse(text: &str, format: Format) -> Result<Self, TopologyFileError> {
+        match format {
+            Format::Yaml => parse_yaml(text),
+            Format::Toml => parse_toml(text),
+        }
+    }
+
+    /// Checks the entries against each other and builds the declarations, exchanges
+    /// first, then queues, then bindings.
+    pub fn into_topology(self) -> Result<Vec<Box<dyn Topology>>, TopologyFileError> {
+        let mut topology: Vec<Box<dyn Topology>> = Vec::new();
+        let mut exchanges = HashSet::new();
+        for (i, entry) in self.exchanges.into_iter().enumerate() {
+            let at = format!("exchanges[{i}] ({})", entry.name);
+            if entry.name.is_empty() || entry.name.starts_with("amq.") {
+                return Err(invalid(
+                    &at,
+                    "exchange names must not be empty or start with amq.",
+                ));
+            }
+            if !exchanges.insert(entry.name.clone()) {
+                return Err(invalid(&at, "exchange declared twice"));
+            }
+            let kind = match entry.kind.as_str() {
+                "direct" => ExchangeKind::Direct,
+                "fanout" => ExchangeKind::Fanout,
+                "topic" => ExchangeKind::Topic,
+                "headers" => ExchangeKind::Headers,
+                custom if custom.starts_with("x-") => ExchangeKind::Custom(custom.to_owned()),
+                other => return Err(invalid(&at, &format!("unknown exchange type {other}"))),
+            };
+            let mut exchange = Exchange::new(entry.name, kind)
+                .with_durable(entry.durable)
+                .with_auto_delete(entry.auto_delete)
+                .with_internal(entry.internal);
+            for (key, value) in &entry.arguments {
+                exchange = exchange.with_argument(key, argument(&at, key, value)?);
+            }
+            topology.push(Box::new(exchange));
+        }
+
+        let mut queues = HashSet::new();
+        for (i, entry) in self.queues.into_iter().enumerate() {
+            let at = format!("queues[{i}] ({})", entry.name);
+            if entry.name.is_empty() {
+                return Err(invalid(&at, "queue names must not be empty"));
+            }
+            if !queues.insert(entry.name.clone()) {
+                return Err(invalid(&at, "queue declared twice"));
+            }
+            let mut queue = match entry.kind.as_deref() {
+                None | Some("classic") => Queue::new(entry.name),
+                Some("quorum") => Queue::quorum(entry.name),
+                Some("stream") => Queue::stream(entry.name),
+                Some(other) => return Err(invalid(&at, &format!("unknown queue type {other}"))),
+            };
+            if queue.arguments.inner().contains_key("x-queue-type")
+                && (!entry.durable || entry.exclusive || entry.auto_delete)
+            {
+                return Err(invalid(
+                    &at,
+                    "quorum and stream queues are durable and shared",
+                ));
+            }
+            queue = queue
+                .with_durable(entry.durable)
+                .with_exclusive(entry.exclusive)
+                .with_auto_delete(entry.auto_delete);
+            for (key, value) in &entry.arguments {
+                queue = queue.with_argument(key, argument(&at, key, value)?);
+            }
+            topology.push(Box::new(queue));
+        }
+
+        for (i, entry) in self.bindings.into_iter().enumerate() {
+            let at = format!("bindings[{i}] ({} -> {})", entry.exchange, entry.queue);
+            if !queues.contains(&entry.queue) {
+                return Err(invalid(
+                    &at,
+                    &format!("queue {} is not declared", entry.queue),
+                ));
+            }
+            if !exchanges.contains(&entry.exchange) && !entry.exchange.starts_with("amq.") {
+                return Err(invalid(
+                    &at,
+                    &format!("exchange {} is not declared", entry.exchange),
+                ));
+            }
+            let mut binding = Binding::new(entry.queue, entry.exchange, entry.routing_key);
+            for (key, value) in &entry.arguments {
+                binding = binding.with_argument(key, argument(&at, key, value)?);
+            }
+            topology.push(Box::new(binding));
+        }
+        Ok(topology)
+    }
+}
+
+#[cfg(feature = "yaml")]
+fn parse_yaml(text: &str) -> Result<TopologyDocument, TopologyFileError> {
+    serde_yaml::from_str(text).map_err(|e| TopologyFileError::Parse(format!("{e}")))
+}
+
+#[cfg(not(feature = "yaml"))]
+fn parse_yaml(_: &str) -> Result<TopologyDocument, TopologyFileError> {
+    Err(TopologyFileError::Unsupported(Format::Yaml, "yaml"))
+}
+
+#[cfg(feature = "toml")]
+fn parse_toml(text: &str) -> Result<TopologyDocument, TopologyFileError> {
+    toml::from_str(text).map_err(|e| TopologyFileError::Parse(format!("{e}")))
+}
+
+#[cfg(not(feature = "toml"))]
+fn parse_toml(_: &str) -> Result<TopologyDocument, TopologyFileError> {
+    Err(TopologyFileError::Unsupported(Format::Toml, "toml"))
+}
+
+fn invalid(entry: &str, message: &str) -> TopologyFileError {
+    TopologyFileError::Invalid {
+        entry: entry.to_owned(),
+        message: message.to_owned(),
+    }
+}
+
+fn argument(entry: &str, key: &str, value: &Value) -> Result<AMQPValue, TopologyFileError> {
+    match value {
+        Value::Bool(b) => Ok(AMQPValue::Boolean(*b)),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => Ok(AMQPValue::LongLongInt(i)),
+            (None, Some(f)) => Ok(AMQPValue::Double(f)),
+            _ => Err(invalid(entry, &format!("argument {key} is out of range"))),
+        },
+        Value::String(s) => Ok(AMQPValue::LongString(s.as_str().into())),
+        _ => Err(invalid(
+            entry,
+            &format!("argument {key} must be a boolean, number or string"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::rabbit::topology::Definitions;
+
+    fn defined(topology: &[Box<dyn Topology>]) -> Definitions {
+        let mut definitions = Definitions::default();
+        for item in topology {
+            item.define(&mut definitions);
+        }
+        definitions
+    }
+
+    fn document(value: Value) -> TopologyDocument {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn rejected(value: Value) -> String {
+        match document(value).into_topology() {
+            Err(TopologyFileError::Invalid { entry, .. }) => entry,
+            Err(other) => panic!("expected an invalid entry, got {other}"),
+            Ok(_) => panic!("expected an invalid entry"),
+        }
+    }
+
+    #[test]
+    fn builds_declarations_in_dependency_order() {
+        let topology = document(json!({
+            "bindings": [{ "queue": "billing", "exchange": "orders", "routing_key": "order.*" }],
+            "queues": [{
+                "name": "billing",
+                "type": "quorum",
+                "arguments": { "x-delivery-limit": 5 },
+            }],
+            "exchanges": [{ "name": "orders", "type": "topic", "durable": false }],
+        }))
+        .into_topology()
+        .unwrap();
+        assert_eq!(topology.len(), 3);
+        let definitions = defined(&topology);
+        assert_eq!(definitions.exchanges[0].name, "orders");
+        assert_eq!(definitions.exchanges[0].kind, "topic");
+        assert!(!definitions.exchanges[0].durable);
+        assert_eq!(definitions.queues[0].name, "billing");
+        assert_eq!(definitions.queues[0].arguments["x-queue-type"], "quorum");
+        assert_eq!(definitions.queues[0].arguments["x-delivery-limit"], 5);
+        assert_eq!(definitions.bindings[0].source, "orders");
+        assert_eq!(definitions.bindings[0].routing_key, "order.*");
+    }
+
+    #[test]
+    fn names_the_inconsistent_entry() {
+        let entry = rejected(json!({
+            "exchanges": [
+                { "name": "orders", "type": "topic" },
+                { "name": "orders", "type": "fanout" },
+            ],
+        }));
+        assert_eq!(entry, "exchanges[1] (orders)");
+        let entry = rejected(json!({ "exchanges": [{ "name": "amq.orders", "type": "topic" }] }));
+        assert_eq!(entry, "exchanges[0] (amq.orders)");
+        let entry = rejected(json!({ "exchanges": [{ "name": "orders", "type": "ring" }] }));
+        assert_eq!(entry, "exchanges[0] (orders)");
+        let entry = rejected(
+            json!({ "queues": [{ "name": "billing", "type": "quorum", "exclusive": true }] }),
+        );
+        assert_eq!(entry, "queues[0] (billing)");
+        let entry =
+            rejected(json!({ "bindings": [{ "queue": "billing", "exchange": "amq.topic" }] }));
+        assert_eq!(entry, "bindings[0] (amq.topic -> billing)");
+        let entry = rejected(json!({
+            "queues": [{ "name": "billing" }],
+            "bindings": [{ "queue": "billing", "exchange": "orders" }],
+        }));
+        assert_eq!(entry, "bindings[0] (orders -> billing)");
+        let entry =
+            rejected(json!({ "queues": [{ "name": "billing", "arguments": { "x-args": [1] } }] }));
+        assert_eq!(entry, "queues[0] (billing)");
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let parsed = serde_json::from_value::<TopologyDocument>(json!({ "policies": [] }));
+        assert!(parsed.is_err());
+    }
+
+    #[test]
+    fn picks_the_format_by_extension() {
+        assert_eq!(Format::of(Path::new("topology.yml")), Some(Format::Yaml));
+        assert_eq!(Format::of(Path::new("topology.yaml")), Some(Format::Yaml));
+        assert_eq!(Format::of(Path::new("topology.toml")), Some(Format::Toml));
+        assert_eq!(Format::of(Path::new("topology.json")), None);
+        assert!(matches!(
+            from_file("topology.json"),
+            Err(TopologyFileError::UnknownFormat(_))
+        ));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn loads_yaml() {
+        let topology = from_str(
+            r#"
+exchanges:
+  - { name: orders, type: topic }
+queues:
+  - { name: billing.orders, type: quorum, arguments: { x-delivery-limit: 5 } }
+bindings:
+  - { queue: billing.orders, exchange: orders, routing_key: "order.*" }
+"#,
+            Format::Yaml,
+        )
+        .unwrap();
+        let definitions = defined(&topology);
+        assert_eq!(definitions.exchanges.len(), 1);
+        assert_eq!(definitions.queues[0].name, "billing.orders");
+        assert_eq!(definitions.bindings[0].routing_key, "order.*");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn loads_toml() {
+        let topology = from_str(
+            r#"
+            [[exchanges]]
+            name = "orders"
+            type = "topic"
+
+            [[queues]]
+            name = "billing.orders"
+            arguments = { x-max-length = 1000 }
+
+            [[bindings]]
+            queue = "billing.orders"
+            exchange = "orders"
+            routing_key = "order.*"
+            "#,
+            Format::Toml,
+        )
+        .unwrap();
+        let definitions = defined(&topology);
+        assert_eq!(definitions.exchanges[0].kind, "topic");
+        assert_eq!(definitions.queues[0].arguments["x-max-length"], 1000);
+        assert_eq!(definitions.bindings[0].source, "orders");
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn needs_the_feature_of_the_format() {
+        assert!(matches!(
+            from_str("queues: []", Format::Yaml),
+            Err(TopologyFileError::Unsupported(Format::Yaml, "yaml"))
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+mod alternate;
+mod audit;
+mod binding;
+pub mod definitions;
+mod exchange;
+pub mod file;
+mod queue;
+mod registry;
+mod retry;
+mod validate;
+
+use async_trait::async_trait;
+
+pub use alternate::Unroutable;
+pub use audit::{audit, AuditWarning};
+pub(crate) use audit::warn_audit;
+pub use binding::*;
+pub use definitions::{from_definitions_json, to_definitions_json, Definitions};
+pub use exchange::*;
+pub use file::{from_file, TopologyFileError};
+pub use queue::*;
+pub use registry::TopologyRegistry;
+pub use retry::{RetryTopology, Retrying};
+pub use validate::{Finding, ObjectRef, TopologyValidator, ValidationReport};
+pub use unibus_macros::topology;
+
+/// Broker object declared on a channel, on startup and after each reconnect.
+#[async_trait]
+pub trait Topology: Send + Sync {
+    async fn declare(&self, channel: &lapin::Channel) -> Result<(), lapin::Error>;
+
+    /// Records the declared names, so strict connections can check call sites against them.
+    fn register(&self, _registry: &mut TopologyRegistry) {}
+
+    /// Adds the object to a definitions export, see [`to_definitions_json`].
+    fn define(&self, _definitions: &mut Definitions) {}
+}
@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use lapin::{
+    options::QueueDeclareOptions,
+    types::{AMQPValue, FieldTable},
+};
+
+use super::{Definitions, Topology, TopologyRegistry};
+
+/// How a quorum queue dead-letters, see [`Queue::with_dead_letter_strategy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeadLetterStrategy {
+    /// The default: dead-lettered messages may be lost if the target is unavailable.
+    AtMostOnce,
+    /// The queue keeps dead-lettered messages until the target confirmed them.
+    AtLeastOnce,
+}
+
+impl DeadLetterStrategy {
+    fn as_str(self) -> &'static str {
+        match self {
+            DeadLetterStrategy::AtMostOnce => "at-most-once",
+            DeadLetterStrategy::AtLeastOnce => "at-least-once",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Queue {
+    pub name: String,
+    pub durable: bool,
+    pub exclusive: bool,
+    pub auto_delete: bool,
+    pub arguments: FieldTable,
+}
+
+impl Queue {
+    pub fn new(name: impl Into<String>) -> Self {
+        Queue {
+            name: name.into(),
+            durable: true,
+            exclusive: false,
+            auto_delete: false,
+            arguments: Default::default(),
+        }
+    }
+
+    /// Replicated quorum queue; quorum queues are always durable.
+    pub fn quorum(name: impl Into<String>) -> Self {
+        Queue::new(name).with_argument("x-queue-type", AMQPValue::LongString("quorum".into()))
+    }
+
+    /// Append-only stream queue, read from any offset with
+    /// [`ConsumerOptions::with_stream_offset`](crate::rabbit::consumer::ConsumerOptions::with_stream_offset).
+    pub fn stream(name: impl Into<String>) -> Self {
+        Queue::new(name).with_argument("x-queue-type", AMQPValue::LongString("stream".into()))
+    }
+
+    /// Retention of a stream queue, e.g. `7D` or `12h`.
+    pub fn with_max_age(self, max_age: &str) -> Self {
+        self.with_argument("x-max-age", AMQPValue::LongString(max_age.into()))
+    }
+
+    /// Size of the segment files a stream queue is stored in.
+    pub fn with_max_segment_size(self, bytes: u64) -> Self {
+        self.with_argument(
+            "x-stream-max-segment-size-bytes",
+            AMQPValue::LongLongInt(bytes as i64),
+        )
+    }
+
+    /// Size of the per-chunk bloom filter of a stream queue, 16 to 255 bytes; larger
+    /// filters skip more chunks when many filter values are in use.
+    pub fn with_stream_filter_size(self, bytes: u8) -> Self {
+        self.with_argument(
+            "x-stream-filter-size-bytes",
+            AMQPValue::ShortShortUInt(bytes),
+        )
+    }
+
+    /// Number of replicas a quorum queue starts with.
+    pub fn with_initial_group_size(self, size: u32) -> Self {
+        self.with_argument("x-quorum-initial-group-size", AMQPValue::LongUInt(size))
+    }
+
+    /// Redeliveries of a message to a quorum queue before it is dropped or dead-lettered.
+    pub fn with_delivery_limit(self, limit: u32) -> Self {
+        self.with_argument("x-delivery-limit", AMQPValue::LongUInt(limit))
+    }
+
+    /// Dead-letter strategy of a quorum queue; at-least-once also sets the
+    /// `reject-publish` overflow behaviour it requires.
+    pub fn with_dead_letter_strategy(self, strategy: DeadLetterStrategy) -> Self {
+        let queue = self.with_argument(
+            "x-dead-letter-strategy",
+            AMQPValue::LongString(strategy.as_str().into()),
+        );
+        match strategy {
+            DeadLetterStrategy::AtLeastOnce => {
+                queue.with_argument("x-overflow", AMQPValue::LongString("reject-publish".into()))
+            }
+            DeadLetterStrategy::AtMostOnce => queue,
+        }
+    }
+
+    pub fn with_durable(mut self, durable: bool) -> Self {
+        self.durable = durable;
+        self
+    }
+
+    pub fn with_exclusive(mut self, exclusive: bool) -> Self {
+        self.exclusive = exclusive;
+        self
+    }
+
+    pub fn with_auto_delete(mut self, auto_delete: bool) -> Self {
+        self.auto_delete = auto_delete;
+        self
+    }
+
+    pub fn with_argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.arguments.insert(key.into(), value);
+        self
+    }
+}
+
+#[async_trait]
+impl Topology for Queue {
+    async fn declare(&self, channel: &lapin::Channel) -> Result<(), lapin::Error> {
+        channel
+            .queue_declare(
+                &self.name,
+                QueueDeclareOptions {
+                    durable: self.durable,
+                    exclusive: self.exclusive,
+                    auto_delete: self.auto_delete,
+                    ..Default::default()
+                },
+                self.arguments.clone(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    fn register(&self, registry: &mut TopologyRegistry) {
+        registry.add_queue(&self.name);
+        if self.exclusive {
+            registry.add_exclusive_queue(&self.name);
+        }
+    }
+
+    fn define(&self, definitions: &mut Definitions) {
+        definitions.add_queue(self);
+    }
+}
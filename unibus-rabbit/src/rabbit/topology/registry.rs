@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+
+use crate::rabbit::RabbitError;
+
+/// Names of the broker objects declared through a connection, checked in strict mode.
+#[derive(Clone, Debug, Default)]
+pub struct TopologyRegistry {
+    exchanges: HashSet<String>,
+    queues: HashSet<String>,
+    bindings: HashSet<(String, String)>,
+    exclusive: HashSet<String>,
+}
+
+impl TopologyRegistry {
+    pub fn add_exchange(&mut self, name: &str) {
+        self.exchanges.insert(name.to_owned());
+    }
+
+    pub fn add_queue(&mut self, name: &str) {
+        self.queues.insert(name.to_owned());
+    }
+
+    pub fn add_exclusive_queue(&mut self, name: &str) {
+        self.exclusive.insert(name.to_owned());
+    }
+
+    pub fn add_binding(&mut self, queue: &str, exchange: &str) {
+        self.bindings.insert((queue.to_owned(), exchange.to_owned()));
+    }
+
+    /// The default exchange and the broker predeclared `amq.*` exchanges always exist.
+    pub fn has_exchange(&self, name: &str) -> bool {
+        name.is_empty() || name.starts_with("amq.") || self.exchanges.contains(name)
+    }
+
+    pub fn has_queue(&self, name: &str) -> bool {
+        self.queues.contains(name)
+    }
+
+    pub fn is_exclusive(&self, queue: &str) -> bool {
+        self.exclusive.contains(queue)
+    }
+
+    pub fn is_bound(&self, queue: &str) -> bool {
+        self.bindings.iter().any(|(q, _)| q == queue)
+    }
+
+    pub fn ensure_exchange(&self, name: &str) -> Result<(), RabbitError> {
+        if self.has_exchange(name) {
+            Ok(())
+        } else {
+            Err(RabbitError::UndeclaredExchange(name.to_owned()))
+        }
+    }
+
+    pub fn ensure_bound(&self, queue: &str) -> Result<(), RabbitError> {
+        if self.is_bound(queue) {
+            Ok(())
+        } else {
+            Err(RabbitError::UnboundQueue(queue.to_owned()))
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use lapin::{types::AMQPValue, BasicProperties};
+use tracing::warn;
+
+use super::{Binding, Definitions, Exchange, Queue, Topology, TopologyRegistry};
+use crate::rabbit::{
+    consumer::{
+        Ack, AckHandle, Death, Delivery, DeliveryHandler, Inner, Nack, Settler, Validation,
+    },
+    headers, OutgoingMessage, Publisher,
+};
+
+/// Work queue with delayed retries and a dead letter queue, for `name`:
+///
+/// * `name`, dead-lettering rejected messages to the `<name>.retry` exchange;
+/// * `<name>.retry`, a fanout exchange and a queue holding them for the backoff,
+///   then dead-lettering them back to `name`;
+/// * `<name>.dlq`, where [`RetryTopology::handler`] parks messages out of attempts.
+#[derive(Clone, Debug)]
+pub struct RetryTopology {
+    pub name: String,
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+impl RetryTopology {
+    /// Three attempts, five seconds apart.
+    pub fn new(name: impl Into<String>) -> Self {
+        RetryTopology {
+            name: name.into(),
+            attempts: 3,
+            backoff: Duration::from_secs(5),
+        }
+    }
+
+    /// Deliveries of a message before it is parked, the first one included.
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Delay before a rejected message is delivered again.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn retry_queue(&self) -> String {
+        format!("{}.retry", self.name)
+    }
+
+    pub fn dead_letter_queue(&self) -> String {
+        format!("{}.dlq", self.name)
+    }
+
+    /// 1 for the first delivery, counted from the `x-death` header of `delivery`.
+    pub fn attempt(&self, delivery: &Delivery) -> u32 {
+        let rejected = Death::from_headers(&headers::headers(&delivery.properties))
+            .into_iter()
+            .filter(|death| death.queue == self.name)
+            .map(|death| death.count)
+            .sum::<u64>();
+        u32::try_from(rejected)
+            .unwrap_or(u32::MAX)
+            .saturating_add(1)
+    }
+
+    /// Wraps `handler` for the `name` queue: a delivery nacked without requeue is
+    /// retried after the backoff, and on its last attempt published to the dead
+    /// letter queue with `publisher` instead.
+    pub fn handler<H: DeliveryHandler>(&self, publisher: Publisher, handler: H) -> Retrying<H> {
+        Retrying {
+            topology: self.clone(),
+            publisher,
+            handler,
+        }
+    }
+
+    fn parts(&self) -> (Queue, Exchange, Queue, Binding, Queue) {
+        let retry = self.retry_queue();
+        let backoff = u32::try_from(self.backoff.as_millis()).unwrap_or(u32::MAX);
+        (
+            Queue::new(&self.name).with_argument(
+                "x-dead-letter-exchange",
+                AMQPValue::LongString(retry.as_str().into()),
+            ),
+            Exchange::fanout(&retry),
+            Queue::new(&retry)
+                .with_argument("x-message-ttl", AMQPValue::LongUInt(backoff))
+                .with_argument("x-dead-letter-exchange", AMQPValue::LongString("".into()))
+                .with_argument(
+                    "x-dead-letter-routing-key",
+                    AMQPValue::LongString(self.name.as_str().into()),
+                ),
+            Binding::new(&retry, &retry, ""),
+            Queue::new(self.dead_letter_queue()),
+        )
+    }
+}
+
+#[async_trait]
+impl Topology for RetryTopology {
+    async fn declare(&self, channel: &lapin::Channel) -> Result<(), lapin::Error> {
+        let (queue, exchange, retry, binding, dead_letter) = self.parts();
+        exchange.declare(channel).await?;
+        retry.declare(channel).await?;
+        binding.declare(channel).await?;
+        dead_letter.declare(channel).await?;
+        queue.declare(channel).await
+    }
+
+    fn register(&self, registry: &mut TopologyRegistry) {
+        let (queue, exchange, retry, binding, dead_letter) = self.parts();
+        exchange.register(registry);
+        retry.register(registry);
+        binding.register(registry);
+        dead_letter.register(registry);
+        queue.register(registry);
+    }
+
+    fn define(&self, definitions: &mut Definitions) {
+        let (queue, exchange, retry, binding, dead_letter) = self.parts();
+        definitions.add_exchange(&exchange);
+        definitions.add_queue(&retry);
+        definitions.add_binding(&binding);
+        definitions.add_queue(&dead_letter);
+        definitions.add_queue(&queue);
+    }
+}
+
+/// Handler of a [`RetryTopology`] queue, see [`RetryTopology::handler`].
+pub struct Retrying<H> {
+    topology: RetryTopology,
+    publisher: Publisher,
+    handler: H,
+}
+
+#[async_trait]
+impl<H: DeliveryHandler> DeliveryHandler for Retrying<H> {
+    fn validate(&self, properties: &BasicProperties) -> Validation {
+        self.handler.validate(properties)
+    }
+
+    async fn handle(&self, delivery: Delivery) -> Result<Ack, Nack> {
+        self.attempt(delivery, Inner::new(&self.handler, None))
+            .await
+    }
+
+    async fn handle_with(&self, delivery: Delivery, ack: AckHandle) {
+        let settler = Settler::new(ack);
+        let outcome = self
+            .attempt(delivery, Inner::new(&self.handler, Some(&settler)))
+            .await;
+        settler.finish(outcome).await
+    }
+}
+
+impl<H: DeliveryHandler> Retrying<H> {
+    async fn attempt(&self, delivery: Delivery, inner: Inner<'_, H>) -> Result<Ack, Nack> {
+        let attempt = self.topology.attempt(&delivery);
+        let parked =
+            OutgoingMessage::new("", self.topology.dead_letter_queue(), delivery.data.clone())
+                .with_properties(delivery.properties.clone());
+        match inner.handle(delivery).await {
+            Err(Nack { requeue: false }) if attempt >= self.topology.attempts => {
+                match self.publisher.publish(parked).await {
+                    Ok(()) => Ok(Ack),
+                    Err(e) => {
+                        warn!(
+                            error = format!("{e}"),
+                            queue = self.topology.name,
+                            "parking a message out of attempts failed"
+                        );
+                        Err(Nack { requeue: true })
+                    }
+                }
+            }
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use lapin::{
+    options::{ExchangeDeclareOptions, QueueDeclareOptions},
+    protocol::{AMQPErrorKind, AMQPSoftError},
+    types::FieldTable,
+    ExchangeKind,
+};
+use serde_json::Value;
+
+use super::{
+    definitions::{BindingDefinition, ExchangeDefinition, QueueDefinition},
+    Definitions, Topology,
+};
+use crate::rabbit::{ChannelPurpose, Connection, RabbitError};
+
+/// Broker object a [`Finding`] is about.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ObjectRef {
+    Exchange(String),
+    Queue(String),
+    Binding {
+        exchange: String,
+        queue: String,
+        routing_key: String,
+    },
+}
+
+impl ObjectRef {
+    /// First object `item` adds to a definitions export, `None` for items that add none.
+    pub(crate) fn of(item: &dyn Topology) -> Option<Self> {
+        let mut definitions = Definitions::default();
+        item.define(&mut definitions);
+        let Definitions {
+            exchanges,
+            queues,
+            bindings,
+        } = definitions;
+        exchanges
+            .into_iter()
+            .map(|e| ObjectRef::Exchange(e.name))
+            .chain(queues.into_iter().map(|q| ObjectRef::Queue(q.name)))
+            .chain(bindings.into_iter().map(|b| ObjectRef::Binding {
+                exchange: b.source,
+                queue: b.destination,
+                routing_key: b.routing_key,
+            }))
+            .next()
+    }
+}
+
+impl fmt::Display for ObjectRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectRef::Exchange(name) => write!(f, "exchange {name}"),
+            ObjectRef::Queue(name) => write!(f, "queue {name}"),
+            ObjectRef::Binding {
+                exchange,
+                queue,
+                routing_key,
+            } => write!(f, "binding {exchange} -> {queue} ({routing_key})"),
+        }
+    }
+}
+
+/// One difference between the desired topology and the broker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    Missing(ObjectRef),
+    Mismatched {
+        object: ObjectRef,
+        /// `type`, `durable`, `auto_delete`, `internal` or `arguments.<name>`.
+        field: String,
+        expected: String,
+        actual: String,
+    },
+    /// On the broker but not in the desired topology.
+    Extra(ObjectRef),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Missing(object) => write!(f, "{object} is missing"),
+            Finding::Mismatched {
+                object,
+                field,
+                expected,
+                actual,
+            } => write!(f, "{object}: {field} is {actual}, expected {expected}"),
+            Finding::Extra(object) => write!(f, "{object} is not in the topology"),
+        }
+    }
+}
+
+/// Differences found by a [`TopologyValidator`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return f.write_str("topology matches");
+        }
+        for finding in &self.findings {
+            writeln!(f, "{finding}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks a broker against the desired topology without declaring anything.
+///
+/// AMQP can only tell whether exchanges and queues exist, see
+/// [`TopologyValidator::check_live`]; kinds, arguments and bindings are compared
+/// against the broker's definitions export with [`TopologyValidator::compare`].
+pub struct TopologyValidator {
+    desired: Definitions,
+}
+
+impl TopologyValidator {
+    pub fn new(topology: &[Box<dyn Topology>]) -> Self {
+        let mut desired = Definitions::default();
+        for item in topology {
+            item.define(&mut desired);
+        }
+        TopologyValidator { desired }
+    }
+
+    /// Passive declarations of the desired exchanges and queues on `connection`,
+    /// reporting the missing ones.
+    pub async fn check_live(
+        &self,
+        connection: &Connection,
+    ) -> Result<ValidationReport, RabbitError> {
+        let mut report = ValidationReport::default();
+        let mut channel = connection.create_channel_for(ChannelPurpose::Admin).await?;
+        for exchange in &self.desired.exchanges {
+            let declared = channel
+                .exchange_declare(
+                    &exchange.name,
+                    ExchangeKind::Direct,
+                    ExchangeDeclareOptions {
+                        passive: true,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await;
+            if not_found(declared)? {
+                report
+                    .findings
+                    .push(Finding::Missing(ObjectRef::Exchange(exchange.name.clone())));
+                // a failed passive declaration closes the channel
+                channel = connection.create_channel_for(ChannelPurpose::Admin).await?;
+            }
+        }
+        for queue in &self.desired.queues {
+            let declared = channel
+                .queue_declare(
+                    &queue.name,
+                    QueueDeclareOptions {
+                        passive: true,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await
+                .map(|_| ());
+            if not_found(declared)? {
+                report
+                    .findings
+                    .push(Finding::Missing(ObjectRef::Queue(queue.name.clone())));
+                channel = connection.create_channel_for(ChannelPurpose::Admin).await?;
+            }
+        }
+        _ = channel.close(0, "topology checked").await;
+        Ok(report)
+    }
+
+    /// Full comparison with `actual`, e.g. parsed from a definitions export of the
+    /// vhost. Default and `amq.*` exchanges are not reported as extra.
+    pub fn compare(&self, actual: &Definitions) -> ValidationReport {
+        let mut findings = Vec::new();
+
+        let exchanges: HashMap<_, _> = actual.exchanges.iter().map(|e| (&e.name, e)).collect();
+        for desired in &self.desired.exchanges {
+            let object = ObjectRef::Exchange(desired.name.clone());
+            match exchanges.get(&desired.name) {
+                Some(actual) => compare_exchange(&object, desired, actual, &mut findings),
+                None => findings.push(Finding::Missing(object)),
+            }
+        }
+        let known: Vec<_> = self.desired.exchanges.iter().map(|e| &e.name).collect();
+        for actual in &actual.exchanges {
+            if !known.contains(&&actual.name) && !builtin(&actual.name) {
+                findings.push(Finding::Extra(ObjectRef::Exchange(actual.name.clone())));
+            }
+        }
+
+        let queues: HashMap<_, _> = actual.queues.iter().map(|q| (&q.name, q)).collect();
+        for desired in &self.desired.queues {
+            let object = ObjectRef::Queue(desired.name.clone());
+            match queues.get(&desired.name) {
+                Some(actual) => compare_queue(&object, desired, actual, &mut findings),
+                None => findings.push(Finding::Missing(object)),
+            }
+        }
+        let known: Vec<_> = self.desired.queues.iter().map(|q| &q.name).collect();
+        for actual in &actual.queues {
+            if !known.contains(&&actual.name) {
+                findings.push(Finding::Extra(ObjectRef::Queue(actual.name.clone())));
+            }
+        }
+
+        let bindings: HashMap<_, _> = actual
+            .bindings
+            .iter()
+            .map(|b| (binding_ref(b), b))
+            .collect();
+        for desired in &self.desired.bindings {
+            let object = binding_ref(desired);
+            match bindings.get(&object) {
+                Some(actual) => compare_arguments(
+                    &object,
+                    &desired.arguments,
+                    &actual.arguments,
+                    &mut findings,
+                ),
+                None => findings.push(Finding::Missing(object)),
+            }
+        }
+        let known: Vec<_> = self.desired.bindings.iter().map(binding_ref).collect();
+        for actual in &actual.bindings {
+            let object = binding_ref(actual);
+            if !known.contains(&object) && !builtin(&actual.source) {
+                findings.push(Finding::Extra(object));
+            }
+        }
+
+        ValidationReport { findings }
+    }
+}
+
+fn not_found(declared: Result<(), lapin::Error>) -> Result<bool, RabbitError> {
+    match declared {
+        Ok(()) => Ok(false),
+        Err(lapin::Error::ProtocolError(e))
+            if *e.kind() == AMQPErrorKind::Soft(AMQPSoftError::NOTFOUND) =>
+        {
+            Ok(true)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn builtin(exchange: &str) -> bool {
+    exchange.is_empty() || exchange.starts_with("amq.")
+}
+
+fn binding_ref(binding: &BindingDefinition) -> ObjectRef {
+    ObjectRef::Binding {
+        exchange: binding.source.clone(),
+        queue: binding.destination.clone(),
+        routing_key: binding.routing_key.clone(),
+    }
+}
+
+fn field<T: PartialEq + fmt::Display>(
+    object: &ObjectRef,
+    name: &str,
+    expected: T,
+    actual: T,
+    findings: &mut Vec<Finding>,
+) {
+    if expected != actual {
+        findings.push(Finding::Mismatched {
+            object: object.clone(),
+            field: name.to_owned(),
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        });
+    }
+}
+
+fn compare_exchange(
+    object: &ObjectRef,
+    desired: &ExchangeDefinition,
+    actual: &ExchangeDefinition,
+    findings: &mut Vec<Finding>,
+) {
+    field(object, "type", &desired.kind, &actual.kind, findings);
+    field(object, "durable", desired.durable, actual.durable, findings);
+    field(
+        object,
+        "auto_delete",
+        desired.auto_delete,
+        actual.auto_delete,
+        findings,
+    );
+    field(
+        object,
+        "internal",
+        desired.internal,
+        actual.internal,
+        findings,
+    );
+    compare_arguments(object, &desired.arguments, &actual.arguments, findings);
+}
+
+fn compare_queue(
+    object: &ObjectRef,
+    desired: &QueueDefinition,
+    actual: &QueueDefinition,
+    findings: &mut Vec<Finding>,
+) {
+    field(object, "durable", desired.durable, actual.durable, findings);
+    field(
+        object,
+        "auto_delete",
+        desired.auto_delete,
+        actual.auto_delete,
+        findings,
+    );
+    compare_arguments(object, &desired.arguments, &actual.arguments, findings);
+}
+
+fn compare_arguments(
+    object: &ObjectRef,
+    desired: &BTreeMap<String, Value>,
+    actual: &BTreeMap<String, Value>,
+    findings: &mut Vec<Finding>,
+) {
+    let keys = desired
+        .keys()
+        .chain(actual.keys().filter(|k| !desired.contains_key(*k)));
+    for key in keys {
+        let show = |value: Option<&Value>| value.map_or("unset".to_owned(), Value::to_string);
+        let (expected, actual) = (desired.get(key), actual.get(key));
+        field(
+            object,
+            &format!("arguments.{key}"),
+            show(expected),
+            show(actual),
+            findings,
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use unibus_core::{
+    rpc::RpcError,
+    transport::{Call, EnvelopeHandler, Publish, Route, Subscribe},
+    Envelope,
+};
+
+use super::{
+    consumer::{Consumer, ConsumerOptions, Delivery},
+    topology::{Binding, Queue, Topology},
+    Connection, OutgoingMessage, Publisher, RabbitError, Rpc,
+};
+
+#[async_trait]
+impl Publish for Publisher {
+    type Error = RabbitError;
+
+    async fn publish(&self, envelope: Envelope) -> Result<(), RabbitError> {
+        Publisher::publish(self, OutgoingMessage::from(envelope)).await
+    }
+}
+
+#[async_trait]
+impl Subscribe for Connection {
+    type Error = RabbitError;
+    type Subscription = Consumer;
+
+    /// Declares `queue` and binds it to the exchanges of `routes`, which must exist;
+    /// routes of the default exchange `""` need no binding.
+    async fn subscribe<H: EnvelopeHandler>(
+        &self,
+        queue: &str,
+        routes: &[Route],
+        handler: H,
+    ) -> Result<Consumer, RabbitError> {
+        let mut topology: Vec<Box<dyn Topology>> = vec![Box::new(Queue::new(queue))];
+        for route in routes.iter().filter(|route| !route.destination.is_empty()) {
+            topology.push(Box::new(Binding::new(
+                queue,
+                &route.destination,
+                &route.pattern,
+            )));
+        }
+        self.declare(&topology).await?;
+        if routes.iter().any(|route| route.destination.is_empty()) {
+            self.register(&Binding::new(queue, "", queue));
+        }
+        let handler = Arc::new(handler);
+        Consumer::start(
+            self,
+            ConsumerOptions::new(queue),
+            move |delivery: Delivery| {
+                let handler = handler.clone();
+                async move { handler.handle(Envelope::from(delivery)).await }
+            },
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl Call for Rpc {
+    type Error = RabbitError;
+    type Server = Consumer;
+
+    async fn call<Req, Resp>(
+        &self,
+        queue: &str,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Resp, RabbitError>
+    where
+        Req: Serialize + Sync,
+        Resp: DeserializeOwned + Send,
+    {
+        Rpc::call(self, queue, request, timeout).await
+    }
+
+    async fn serve<Req, Resp, H, Fut>(
+        &self,
+        queue: &str,
+        handler: H,
+    ) -> Result<Consumer, RabbitError>
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Resp: Serialize + Send + 'static,
+        H: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, RpcError>> + Send,
+    {
+        Rpc::serve(self, queue, handler).await
+    }
+}
@@ -0,0 +1,127 @@
+use std::{future::Future, sync::Arc};
+
+use lapin::options::{BasicGetOptions, BasicPublishOptions};
+use serde::de::DeserializeOwned;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::rabbit::{
+    consumer::{AckToken, ConsumerOptions, Delivery, MessageStream},
+    headers,
+    propagation::{self, Correlation},
+    Channel, ChannelSource, OutgoingMessage, RabbitError,
+};
+
+/// Channel in AMQP transaction mode, an alternative to publisher confirms for
+/// acking a message and publishing others atomically.
+///
+/// Only deliveries received on this channel, through [`TxChannel::get`] or
+/// [`TxChannel::stream`], are acknowledged within its transactions; tokens of other
+/// channels settle immediately.
+pub struct TxChannel {
+    source: Arc<dyn ChannelSource>,
+    channel: Channel,
+    running: Mutex<()>,
+}
+
+impl TxChannel {
+    pub async fn open(source: &dyn ChannelSource) -> Result<Self, RabbitError> {
+        let channel = source.create_channel().await?;
+        channel.tx_select().await?;
+        Ok(TxChannel {
+            source: source.share(),
+            channel,
+            running: Mutex::new(()),
+        })
+    }
+
+    /// Fetches one message from `queue`, to be settled in a transaction.
+    pub async fn get(&self, queue: &str) -> Result<Option<(Delivery, AckToken)>, RabbitError> {
+        let message = self
+            .channel
+            .basic_get(queue, BasicGetOptions { no_ack: false })
+            .await?;
+        Ok(message.map(|message| {
+            let (delivery, acker) = Delivery::from_lapin(message.delivery);
+            (delivery, AckToken::new(acker))
+        }))
+    }
+
+    /// Subscribes to `options.queue` on this channel, see [`MessageStream::open`].
+    pub async fn stream<T: DeserializeOwned>(
+        &self,
+        options: ConsumerOptions,
+    ) -> Result<MessageStream<T>, RabbitError> {
+        MessageStream::on_channel(self.channel.clone(), options).await
+    }
+
+    /// Runs `body` in a transaction: committed when it succeeds, rolled back when
+    /// it fails. Transactions on one channel run one at a time.
+    pub async fn tx<F, Fut, R>(&self, body: F) -> Result<R, RabbitError>
+    where
+        F: FnOnce(Txn) -> Fut,
+        Fut: Future<Output = Result<R, RabbitError>>,
+    {
+        let _running = self.running.lock().await;
+        let txn = Txn {
+            source: self.source.clone(),
+            channel: self.channel.clone(),
+        };
+        match body(txn).await {
+            Ok(result) => {
+                self.channel.tx_commit().await?;
+                Ok(result)
+            }
+            Err(e) => {
+                if let Err(rollback) = self.channel.tx_rollback().await {
+                    warn!(error = format!("{rollback}"), "transaction rollback failed");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn close(self) -> Result<(), RabbitError> {
+        Ok(self.channel.close(0, "transactions finished").await?)
+    }
+}
+
+/// Operations of one transaction, see [`TxChannel::tx`].
+pub struct Txn {
+    source: Arc<dyn ChannelSource>,
+    channel: Channel,
+}
+
+impl Txn {
+    /// Publishes `message` when the transaction commits.
+    pub async fn publish(&self, mut message: OutgoingMessage) -> Result<(), RabbitError> {
+        self.source.ensure_exchange(&message.exchange)?;
+        let mut table = headers::headers(&message.properties);
+        propagation::current::<Correlation>()
+            .unwrap_or_default()
+            .write_headers(&mut table);
+        message.properties = message.properties.with_headers(table);
+        self.channel
+            .basic_publish(
+                &message.exchange,
+                &message.routing_key,
+                BasicPublishOptions {
+                    mandatory: message.mandatory,
+                    ..Default::default()
+                },
+                &message.payload,
+                message.properties,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Acknowledges the delivery of `token` when the transaction commits.
+    pub async fn ack(&self, token: AckToken) -> Result<(), RabbitError> {
+        token.ack().await
+    }
+
+    pub async fn nack(&self, token: AckToken, requeue: bool) -> Result<(), RabbitError> {
+        token.nack(requeue).await
+    }
+}
@@ -0,0 +1,9 @@
+//! Compile tests of the `BusMessage` derive, `topology!` and `#[handler]`; run with
+//! `TRYBUILD=overwrite` to refresh the expected errors after a diagnostic changes.
+
+#[test]
+fn macros() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/pass/*.rs");
+    t.compile_fail("tests/ui/fail/*.rs");
+}
@@ -0,0 +1,92 @@
+//! Per-key ordering of [`OrderedPublisher`] against a live broker.
+//!
+//! Needs `AMQP_ADDR`, e.g. `amqp://127.0.0.1:5672/%2f`; run with `--ignored`.
+
+use std::time::Duration;
+
+use futures::future::join_all;
+use tokio::sync::mpsc;
+use unibus_rabbit::rabbit::{
+    self,
+    consumer::{Ack, Consumer, Delivery},
+    topology::{Binding, Exchange, Queue, Topology},
+    ConnectionOptions, OrderedPublisher, OutgoingMessage, PublishGuarantee, Publisher,
+};
+
+const MESSAGES: usize = 200;
+
+fn broker() -> ConnectionOptions {
+    let addr = std::env::var("AMQP_ADDR").expect("AMQP_ADDR is set");
+    ConnectionOptions::new(addr.as_str(), "ordering-test").expect("AMQP_ADDR is a broker uri")
+}
+
+async fn consumed(
+    connection: &rabbit::Connection,
+    queue: &str,
+) -> (Consumer, mpsc::UnboundedReceiver<usize>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let consumer = connection
+        .consume(queue, 1, move |delivery: Delivery| {
+            let tx = tx.clone();
+            async move {
+                let n: usize = serde_json::from_slice(&delivery.data).unwrap();
+                _ = tx.send(n);
+                Ok(Ack)
+            }
+        })
+        .await
+        .unwrap();
+    (consumer, rx)
+}
+
+async fn assert_in_order(mut received: mpsc::UnboundedReceiver<usize>) {
+    for expected in 0..MESSAGES {
+        let n = tokio::time::timeout(Duration::from_secs(10), received.recv())
+            .await
+            .expect("message in time")
+            .expect("consumer running");
+        assert_eq!(n, expected);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs AMQP_ADDR"]
+async fn keys_are_consumed_in_publish_order() {
+    let client = rabbit::start().await;
+    let connection = client.connect(broker()).await.unwrap();
+    connection.ready().await.unwrap();
+
+    let exchange = format!("unibus.test.ordered.{}", uuid::Uuid::new_v4().simple());
+    let queues = [format!("{exchange}.a"), format!("{exchange}.b")];
+    let mut topology: Vec<Box<dyn Topology>> =
+        vec![Box::new(Exchange::direct(&exchange).with_auto_delete(true))];
+    for (queue, key) in queues.iter().zip(["a", "b"]) {
+        topology.push(Box::new(Queue::new(queue).with_auto_delete(true)));
+        topology.push(Box::new(Binding::new(queue, &exchange, key)));
+    }
+    connection.declare(&topology).await.unwrap();
+    let (consumer_a, received_a) = consumed(&connection, &queues[0]).await;
+    let (consumer_b, received_b) = consumed(&connection, &queues[1]).await;
+
+    // without confirms of its own, a fire-and-forget publisher would let sends overtake
+    let publisher = OrderedPublisher::new(
+        Publisher::new(connection.clone()).with_guarantee(PublishGuarantee::FireAndForget),
+    );
+    let publishes = (0..MESSAGES).flat_map(|n| {
+        ["a", "b"].map(|key| {
+            let message = OutgoingMessage::json(&exchange, key, &n).unwrap();
+            let publisher = publisher.clone();
+            async move { publisher.publish(message).await }
+        })
+    });
+    for res in join_all(publishes).await {
+        res.unwrap();
+    }
+
+    assert_in_order(received_a).await;
+    assert_in_order(received_b).await;
+    consumer_a.cancel().await.unwrap();
+    consumer_b.cancel().await.unwrap();
+    connection.shutdown(Duration::from_secs(5)).await.unwrap();
+    client.close().await.unwrap();
+}
@@ -0,0 +1,111 @@
+//! [`process_and_publish`] against a live broker.
+//!
+//! Needs `AMQP_ADDR`, e.g. `amqp://127.0.0.1:5672/%2f`; run with `--ignored`.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use lapin::types::FieldTable;
+use tokio::sync::mpsc;
+use unibus_rabbit::rabbit::{
+    self,
+    consumer::{process_and_publish, Ack, Delivery, DeliveryHandler},
+    topology::{Queue, Topology},
+    Channel, ChannelSource, Connection, ConnectionOptions, OutgoingMessage, Publisher, RabbitError,
+    RetryPolicy,
+};
+
+fn broker() -> ConnectionOptions {
+    let addr = std::env::var("AMQP_ADDR").expect("AMQP_ADDR is set");
+    ConnectionOptions::new(addr.as_str(), "relay-test").expect("AMQP_ADDR is a broker uri")
+}
+
+/// Connection whose first channel fails to open.
+#[derive(Clone)]
+struct FailsOnce {
+    connection: Connection,
+    failed: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl ChannelSource for FailsOnce {
+    async fn create_channel(&self) -> Result<Channel, RabbitError> {
+        if !self.failed.swap(true, Ordering::SeqCst) {
+            return Err(RabbitError::NotConnected);
+        }
+        self.connection.create_channel().await
+    }
+
+    async fn ready(&self) -> Result<(), RabbitError> {
+        self.connection.ready().await
+    }
+
+    fn share(&self) -> Arc<dyn ChannelSource> {
+        Arc::new(self.clone())
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs AMQP_ADDR"]
+async fn results_are_confirmed_before_ack_after_a_failed_publish() {
+    let client = rabbit::start().await;
+    let connection = client.connect(broker()).await.unwrap();
+    connection.ready().await.unwrap();
+
+    let queue = format!("unibus.test.relay.{}", uuid::Uuid::new_v4().simple());
+    let topology: Vec<Box<dyn Topology>> =
+        vec![Box::new(Queue::new(&queue).with_auto_delete(true))];
+    connection.declare(&topology).await.unwrap();
+    let (tx, mut received) = mpsc::unbounded_channel();
+    let consumer = connection
+        .consume(&queue, 10, move |delivery: Delivery| {
+            let tx = tx.clone();
+            async move {
+                _ = tx.send(delivery.data);
+                Ok(Ack)
+            }
+        })
+        .await
+        .unwrap();
+
+    let source = FailsOnce {
+        connection: connection.clone(),
+        failed: Default::default(),
+    };
+    let policy =
+        RetryPolicy::new(3).with_backoff(Duration::from_millis(10), Duration::from_millis(10));
+    let publisher = Publisher::new(source.clone()).with_retry(policy);
+    let results = queue.clone();
+    let handler = process_and_publish(publisher, move |_| {
+        let queue = results.clone();
+        async move {
+            Ok(vec![
+                OutgoingMessage::new("", &queue, "1"),
+                OutgoingMessage::new("", &queue, "2"),
+            ])
+        }
+    });
+    let delivery = Delivery::test(FieldTable::default(), "{}");
+    assert_eq!(handler.handle(delivery).await, Ok(Ack));
+    assert!(source.failed.load(Ordering::SeqCst));
+
+    let mut payloads = Vec::new();
+    for _ in 0..2 {
+        let payload = tokio::time::timeout(Duration::from_secs(10), received.recv())
+            .await
+            .expect("result in time")
+            .expect("consumer running");
+        payloads.push(payload);
+    }
+    payloads.sort();
+    assert_eq!(payloads, [b"1".to_vec(), b"2".to_vec()]);
+    consumer.cancel().await.unwrap();
+    connection.shutdown(Duration::from_secs(5)).await.unwrap();
+    client.close().await.unwrap();
+}
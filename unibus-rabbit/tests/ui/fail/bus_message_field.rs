@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+use unibus_rabbit::bus::BusMessage;
+
+#[derive(Serialize, Deserialize, BusMessage)]
+#[bus(routing_key = "order.{region}")]
+struct OrderPlaced {
+    id: u64,
+}
+
+fn main() {}
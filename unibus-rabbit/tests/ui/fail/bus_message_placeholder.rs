@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+use unibus_rabbit::bus::BusMessage;
+
+#[derive(Serialize, Deserialize, BusMessage)]
+#[bus(routing_key = "order.{}")]
+struct Empty {
+    region: String,
+}
+
+#[derive(Serialize, Deserialize, BusMessage)]
+#[bus(routing_key = "order.{0}")]
+struct Positional(String);
+
+#[derive(Serialize, Deserialize, BusMessage)]
+#[bus(routing_key = "order.{address.region}")]
+struct Nested {
+    address: String,
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use unibus_rabbit::rabbit::consumer::{Ack, Delivery, Nack};
+
+#[unibus_rabbit::handler(queue = "billing.orders")]
+fn not_async(_delivery: Delivery) -> Result<Ack, Nack> {
+    Ok(Ack)
+}
+
+#[unibus_rabbit::handler(bind = "orders/order.*")]
+async fn no_queue(_delivery: Delivery) -> Result<Ack, Nack> {
+    Ok(Ack)
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use unibus_rabbit::rabbit::topology::topology;
+
+fn main() {
+    let _ = topology! {
+        exchange "orders": topic;
+        queue "billing";
+        bind "shipping" => "orders": "order.*";
+        bind "billing" => "payments";
+    };
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use unibus_rabbit::bus::BusMessage;
+
+#[derive(Serialize, Deserialize, BusMessage)]
+#[bus(exchange = "orders", routing_key = "order.{region}.{ r#type }")]
+struct OrderPlaced {
+    region: String,
+    r#type: String,
+}
+
+fn main() {
+    let placed = OrderPlaced {
+        region: "eu".to_owned(),
+        r#type: "retail".to_owned(),
+    };
+    assert_eq!(placed.routing_key(), "order.eu.retail");
+}
@@ -0,0 +1,11 @@
+use unibus_rabbit::rabbit::consumer::{Ack, Delivery, Nack};
+
+#[unibus_rabbit::handler(queue = "billing.matches", bind = "orders/order.*")]
+async fn r#match(_delivery: Delivery) -> Result<Ack, Nack> {
+    Ok(Ack)
+}
+
+fn main() {
+    assert_eq!(MATCH.queue, "billing.matches");
+    assert_eq!(MATCH.bindings, &[("orders", "order.*")]);
+}
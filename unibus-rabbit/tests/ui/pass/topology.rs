@@ -0,0 +1,11 @@
+use unibus_rabbit::rabbit::topology::topology;
+
+fn main() {
+    let items = topology! {
+        exchange "orders": topic;
+        queue "billing";
+        bind "billing" => "orders": "order.*";
+        bind "billing" => "amq.direct";
+    };
+    assert_eq!(items.len(), 4);
+}
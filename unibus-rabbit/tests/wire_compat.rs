@@ -0,0 +1,256 @@
+//! Replays the wire fixtures archived under `tests/wire/<version>` against the current
+//! decoders, so messages still in flight from older deploys keep being understood.
+//!
+//! Every released `major.minor` gets a directory of its own; fixtures are only ever
+//! added, never edited, once that version shipped.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use lapin::{
+    types::{AMQPValue, FieldArray, FieldTable},
+    BasicProperties,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use unibus_core::{BusMessage, MESSAGE_VERSION};
+use unibus_rabbit::{
+    bus::FaultInfo,
+    rabbit::{consumer::Delivery, headers, topology::RetryTopology, OutgoingMessage},
+};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, BusMessage)]
+#[bus(exchange = "orders", routing_key = "order.{region}", version = 2)]
+struct OrderPlaced {
+    region: String,
+    id: u64,
+}
+
+#[derive(Deserialize)]
+struct Fixture {
+    exchange: String,
+    routing_key: String,
+    properties: Properties,
+    payload: Value,
+    expect: Expect,
+}
+
+#[derive(Deserialize)]
+struct Properties {
+    content_type: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct Expect {
+    message: String,
+    version: u64,
+    fault: Option<ExpectedFault>,
+    retry: Option<ExpectedRetry>,
+}
+
+#[derive(Deserialize)]
+struct ExpectedFault {
+    reason: Option<String>,
+    exchange: String,
+    routing_key: String,
+    retries: u64,
+}
+
+#[derive(Deserialize)]
+struct ExpectedRetry {
+    queue: String,
+    attempt: u32,
+}
+
+/// Header value written as `{ "<AMQPValue variant>": value }`.
+fn amqp(value: &Value) -> Result<AMQPValue, String> {
+    let Some((kind, value)) = value.as_object().and_then(|o| o.iter().next()) else {
+        return Err(format!("untagged header value {value}"));
+    };
+    let int = || {
+        value
+            .as_i64()
+            .ok_or_else(|| format!("{kind} needs an integer"))
+    };
+    Ok(match kind.as_str() {
+        "Boolean" => AMQPValue::Boolean(value.as_bool().ok_or("Boolean needs a bool")?),
+        "LongInt" => AMQPValue::LongInt(int()?.try_into().map_err(|_| "LongInt overflow")?),
+        "LongUInt" => AMQPValue::LongUInt(int()?.try_into().map_err(|_| "LongUInt overflow")?),
+        "LongLongInt" => AMQPValue::LongLongInt(int()?),
+        "Timestamp" => AMQPValue::Timestamp(int()?.try_into().map_err(|_| "negative Timestamp")?),
+        "ShortString" => AMQPValue::ShortString(string(value)?.into()),
+        "LongString" => AMQPValue::LongString(string(value)?.into()),
+        "FieldArray" => {
+            let values = value.as_array().ok_or("FieldArray needs an array")?;
+            let values = values.iter().map(amqp).collect::<Result<Vec<_>, _>>()?;
+            AMQPValue::FieldArray(FieldArray::from(values))
+        }
+        "FieldTable" => {
+            let entries = value.as_object().ok_or("FieldTable needs an object")?;
+            AMQPValue::FieldTable(table(entries.iter())?)
+        }
+        "Void" => AMQPValue::Void,
+        other => return Err(format!("unknown header type {other}")),
+    })
+}
+
+fn string(value: &Value) -> Result<&str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("expected a string, found {value}"))
+}
+
+fn table<'a>(entries: impl Iterator<Item = (&'a String, &'a Value)>) -> Result<FieldTable, String> {
+    let mut table = FieldTable::default();
+    for (key, value) in entries {
+        table.insert(key.as_str().into(), amqp(value)?);
+    }
+    Ok(table)
+}
+
+impl Fixture {
+    fn delivery(&self) -> Result<Delivery, String> {
+        let mut properties =
+            BasicProperties::default().with_headers(table(self.properties.headers.iter())?);
+        if let Some(content_type) = &self.properties.content_type {
+            properties = properties.with_content_type(content_type.as_str().into());
+        }
+        if let Some(kind) = &self.properties.kind {
+            properties = properties.with_type(kind.as_str().into());
+        }
+        Ok(Delivery {
+            delivery_tag: 1,
+            exchange: self.exchange.clone(),
+            routing_key: self.routing_key.clone(),
+            redelivered: false,
+            properties,
+            data: serde_json::to_vec(&self.payload).map_err(|e| e.to_string())?,
+        })
+    }
+
+    fn check(&self) -> Result<(), String> {
+        let delivery = self.delivery()?;
+        let table = headers::headers(&delivery.properties);
+
+        let version = headers::get_u64(&table, MESSAGE_VERSION);
+        if version != Some(self.expect.version) {
+            return Err(format!(
+                "version {version:?}, expected {}",
+                self.expect.version
+            ));
+        }
+        match self.expect.message.as_str() {
+            OrderPlaced::MESSAGE_TYPE => self.check_message::<OrderPlaced>(&delivery)?,
+            other => return Err(format!("no message type {other} to decode with")),
+        }
+
+        if let Some(expected) = &self.expect.fault {
+            let fault = FaultInfo::from_headers(&table);
+            let expected = FaultInfo {
+                reason: expected.reason.clone(),
+                exchange: expected.exchange.clone(),
+                routing_key: expected.routing_key.clone(),
+                retries: expected.retries,
+            };
+            if fault != expected {
+                return Err(format!("fault {fault:?}, expected {expected:?}"));
+            }
+        }
+
+        if let Some(expected) = &self.expect.retry {
+            let attempt = RetryTopology::new(&expected.queue).attempt(&delivery);
+            if attempt != expected.attempt {
+                return Err(format!("attempt {attempt}, expected {}", expected.attempt));
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes the payload as `T` and checks the current encoder still writes the
+    /// archived envelope for it.
+    fn check_message<T: BusMessage>(&self, delivery: &Delivery) -> Result<(), String> {
+        let message: T = delivery.json().map_err(|e| e.to_string())?;
+        let encoded = OutgoingMessage::from(message.to_envelope().map_err(|e| e.to_string())?);
+        let properties = &encoded.properties;
+        if properties.kind() != delivery.properties.kind() {
+            return Err(format!(
+                "type {:?} is now {:?}",
+                delivery.properties.kind(),
+                properties.kind()
+            ));
+        }
+        if properties.content_type() != delivery.properties.content_type() {
+            return Err(format!(
+                "content type {:?} is now {:?}",
+                delivery.properties.content_type(),
+                properties.content_type()
+            ));
+        }
+        let archived = headers::headers(&delivery.properties);
+        for (key, value) in headers::headers(properties).inner() {
+            if archived.inner().get(key) != Some(value) {
+                return Err(format!("header {key} is now written as {value:?}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn fixtures() -> Vec<(String, Result<Fixture, String>)> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/wire");
+    let mut fixtures = Vec::new();
+    for version in fs::read_dir(&root).expect("tests/wire is readable") {
+        let version = version.expect("tests/wire entry").path();
+        for file in fs::read_dir(&version).expect("fixture directory is readable") {
+            let path = file.expect("fixture entry").path();
+            let name = path
+                .strip_prefix(&root)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            let fixture = fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| serde_json::from_slice(&data).map_err(|e| e.to_string()));
+            fixtures.push((name, fixture));
+        }
+    }
+    fixtures.sort_by(|a, b| a.0.cmp(&b.0));
+    fixtures
+}
+
+#[test]
+fn archived_fixtures_decode() {
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no wire fixtures found");
+    let failures: Vec<String> = fixtures
+        .into_iter()
+        .filter_map(|(name, fixture)| {
+            fixture
+                .and_then(|fixture| fixture.check())
+                .err()
+                .map(|e| format!("{name}: {e}"))
+        })
+        .collect();
+    assert!(
+        failures.is_empty(),
+        "incompatible wire fixtures:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn current_version_is_archived() {
+    let version = env!("CARGO_PKG_VERSION");
+    let minor = version.rsplit_once('.').map_or(version, |(minor, _)| minor);
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/wire")
+        .join(minor);
+    assert!(
+        dir.is_dir(),
+        "archive the wire format of {version} under {}",
+        dir.display()
+    );
+}